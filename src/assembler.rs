@@ -1,85 +1,179 @@
-use crate::assembler::lexer::Lexer;
-use crate::assembler::parser::Parser;
-
-mod util;
-mod lexer;
-mod parser;
-mod token;
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use crate::{
+  assembler::{
+    emitter::Emitter,
+    lexer::Lexer,
+    parser::Parser,
+    statement::{Statement, StatementKind},
+  },
+  error::AssembleError,
+};
+
+mod conditional;
+mod emitter;
+mod expression;
 mod keyword;
+mod lexer;
+mod listing;
 mod literal;
+mod macros;
+mod parser;
+mod program;
+mod statement;
 mod symbol;
+#[cfg(test)]
+mod tests;
+mod token;
+mod util;
 
-pub struct Interpreter {
+pub use listing::{Listing, ListingLine};
+pub use program::{Program, Segment};
+pub use token::Position;
+
+/// Assembles 6502 source into a [`Program`] using the default settings
+pub fn assemble(source: &str) -> Result<Program, AssembleError> {
+  Assembler::new().assemble(source)
+}
+
+pub struct Assembler {
   lexer: Lexer,
   parser: Parser,
+  fill: u8,
+  definitions: Vec<Statement>,
+  list: bool,
+  listing: Option<Listing>,
 }
 
-impl Default for Interpreter {
+impl Default for Assembler {
   fn default() -> Self {
     Self::new()
   }
 }
 
-impl Interpreter {
-  pub fn new() -> Interpreter {
-    let error_handler = Handle::new(ErrorHandler::new());
-    let lexer = Lexer::new(error_handler.clone());
-    let parser = Parser::new(error_handler.clone());
-    let binder = Binder::new(error_handler.clone());
+impl Assembler {
+  pub fn new() -> Self {
+    let lexer = Lexer::new();
+    let parser = Parser::new();
 
     Self {
-      error_handler,
       lexer,
       parser,
-      binder,
-      show_tokens: false,
-      show_tree: false,
+      fill: 0x00,
+      definitions: Vec::new(),
+      list: false,
+      listing: None,
     }
   }
 
-  pub fn run(&mut self, source: String) -> Result<String, KonError> {
-    self.error_handler.get_mut().clear();
-    let tokens = self.lexer.lex(&source);
+  /// Sets the byte used for holes in the image and for `.fill` without a
+  /// value. Defaults to `0x00`; use `0xEA` (`NOP`) to match [`Ram::new`].
+  ///
+  /// [`Ram::new`]: crate::core::ram::Ram::new
+  pub fn set_fill(&mut self, fill: u8) {
+    self.fill = fill;
+  }
 
-    if self.show_tokens {
-      println!("{tokens:#?}");
-      self.show_tokens = false;
-    }
+  /// Enables or disables producing a [`Listing`] alongside the program
+  pub fn set_listing(&mut self, enabled: bool) {
+    self.list = enabled;
+  }
 
-    let tree = self.parser.parse(&tokens);
+  /// Listing of the last successful assembly, if listings are enabled
+  pub fn listing(&self) -> Option<&Listing> {
+    self.listing.as_ref()
+  }
 
-    if self.show_tree {
-      print!("{}", tree); // tree has trailing newline due to recursive impl
-      self.show_tree = false;
+  /// Predefines a symbol from a command line style `NAME=value` definition,
+  /// where `value` may be any constant expression. A bare `NAME` is defined as
+  /// `1`.
+  pub fn define(&mut self, definition: &str) -> Result<(), AssembleError> {
+    let definition = match definition.contains('=') {
+      true => definition.to_string(),
+      false => format!("{definition}=1"),
+    };
+
+    let file: Arc<Path> = Arc::from(Path::new("<command line>"));
+    let tokens = self.lexer.lex(&definition, Some(file.clone()))?;
+    let statements = self.parser.parse(&tokens, &self.definitions)?;
+    match statements.as_slice() {
+      [statement @ Statement {
+        kind: StatementKind::Assignment { .. },
+        ..
+      }] => {
+        self.definitions.push(statement.clone());
+        Ok(())
+      }
+      _ => Err(AssembleError::Syntax {
+        position: Position {
+          file: Some(file),
+          ..Position::new(1, 1)
+        },
+        message: format!("expected `NAME=value` but got `{definition}`"),
+      }),
     }
+  }
 
-    let bound_tree = self.binder.bind(tree.root);
-
-    self.error_handler.get().try_report_errors()?;
-
-    // print!("Result: ");
-
-    match bound_tree.evaluate() {
-      Ok(result) => {
-        if let Some(&value) = result.downcast_ref::<i64>() {
-          return Ok(value.to_string());
-        }
-
-        if let Some(value) = result.downcast_ref::<String>() {
-          return Ok(value.clone());
-        }
+  /// Adds a directory to search for `.include` and `.incbin` files that are
+  /// not found next to the file including them. Directories are searched in
+  /// the order they were added.
+  pub fn add_include_path(&mut self, path: impl Into<PathBuf>) {
+    self.parser.add_include_path(path.into());
+  }
 
-        Err(KonError::Evaluation("resultant data type not supported".into()))
-      }
-      Err(error) => Err(KonError::Evaluation(format!("{error}"))),
-    }
+  /// Assembles `source`, resolving relative includes against the current
+  /// directory
+  pub fn assemble(&mut self, source: &str) -> Result<Program, AssembleError> {
+    self.assemble_source(source, None)
   }
 
-  pub fn show_tree(&mut self) {
-    self.show_tree = true;
+  /// Assembles the file at `path`, resolving relative includes against its
+  /// directory
+  pub fn assemble_file(&mut self, path: impl AsRef<Path>) -> Result<Program, AssembleError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|error| AssembleError::Read {
+      position: Position::default(),
+      path: path.display().to_string(),
+      error,
+    })?;
+
+    self.assemble_source(&source, Some(Arc::from(path)))
   }
 
-  pub fn show_next_tokens(&mut self) {
-    self.show_tokens = true;
+  fn assemble_source(&mut self, source: &str, file: Option<Arc<Path>>) -> Result<Program, AssembleError> {
+    self.listing = None;
+
+    let tokens = self.lexer.lex(source, file.clone())?;
+    let mut statements = self.definitions.clone();
+    statements.extend(self.parser.parse(&tokens, &self.definitions)?);
+
+    let mut emitter = Emitter::new(self.fill);
+    let program = emitter.emit(&statements)?;
+
+    if self.list {
+      let mut sources: HashMap<_, _> = self
+        .parser
+        .sources()
+        .iter()
+        .map(|(file, source)| (Some(file.clone()), source.as_str()))
+        .collect();
+      sources.insert(file.clone(), source);
+
+      self.listing = Some(Listing::new(
+        &statements,
+        emitter.records(),
+        &program,
+        emitter.symbols(),
+        file,
+        &sources,
+      ));
+    }
+
+    Ok(program)
   }
 }
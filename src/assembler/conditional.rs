@@ -0,0 +1,119 @@
+use super::{statement::Directive, token::Position};
+use crate::error::AssembleError;
+
+/// An open `.if`, `.ifdef` or `.ifndef` block
+pub struct Conditional {
+  pub position: Position,
+  /// Whether the condition depends on values only known once assembling, in
+  /// which case the parser keeps every branch and the emitter picks one
+  pub deferred: bool,
+  /// Whether the enclosing block is being assembled
+  pub enclosing: bool,
+  /// Whether the current branch is being assembled
+  pub active: bool,
+  /// Whether any branch so far has been taken
+  pub taken: bool,
+  /// Whether `.else` has been seen
+  pub otherwise: bool,
+}
+
+impl Conditional {
+  /// A block whose first branch is taken as `decision` says, or that is
+  /// deferred if it is `None`
+  pub fn new(position: Position, enclosing: bool, decision: Option<bool>) -> Self {
+    Self {
+      position,
+      deferred: decision.is_none(),
+      enclosing,
+      active: decision.unwrap_or(true),
+      taken: decision.unwrap_or(false),
+      otherwise: false,
+    }
+  }
+
+  /// Continues with an `.elseif` branch that is taken if `active`
+  pub fn else_if(&mut self, active: bool) {
+    self.active = active;
+    self.taken |= active;
+  }
+
+  /// Continues with the `.else` branch, which is taken if no branch before it
+  /// was. Every branch of a deferred block stays active.
+  pub fn otherwise(&mut self) {
+    self.otherwise = true;
+    if !self.deferred {
+      self.active = self.enclosing && !self.taken;
+      self.taken = true;
+    }
+  }
+}
+
+/// The conditionals open at the current statement, innermost last
+#[derive(Default)]
+pub struct Conditionals(Vec<Conditional>);
+
+impl Conditionals {
+  pub fn clear(&mut self) {
+    self.0.clear();
+  }
+
+  pub fn open(&mut self, conditional: Conditional) {
+    self.0.push(conditional);
+  }
+
+  /// Returns the innermost open conditional, checking that `directive` can
+  /// follow it
+  pub fn innermost(&mut self, position: &Position, directive: &Directive) -> Result<&mut Conditional, AssembleError> {
+    let name = match directive {
+      Directive::ElseIf(_) => ".elseif",
+      Directive::Else => ".else",
+      _ => ".endif",
+    };
+
+    match self.0.last_mut() {
+      Some(conditional) if conditional.otherwise && !matches!(directive, Directive::EndIf) => {
+        Err(AssembleError::Syntax {
+          position: position.clone(),
+          message: format!("`{name}` after `.else`"),
+        })
+      }
+      Some(conditional) => Ok(conditional),
+      None => Err(AssembleError::Syntax {
+        position: position.clone(),
+        message: format!("`{name}` without `.if`"),
+      }),
+    }
+  }
+
+  /// Closes the innermost conditional at an `.endif`
+  pub fn close(&mut self, position: &Position) -> Result<Conditional, AssembleError> {
+    self.innermost(position, &Directive::EndIf)?;
+    Ok(self.0.pop().expect("innermost conditional exists"))
+  }
+
+  /// Whether the current branch of every open conditional is taken
+  pub fn active(&self) -> bool {
+    self.0.last().is_none_or(|conditional| conditional.active)
+  }
+
+  /// Whether the enclosing block of the innermost conditional is taken
+  pub fn enclosing(&self) -> bool {
+    self.0.last().is_some_and(|conditional| conditional.enclosing)
+  }
+
+  /// Whether any open conditional is deferred
+  pub fn deferred(&self) -> bool {
+    self.0.iter().any(|conditional| conditional.deferred)
+  }
+
+  /// Fails if a conditional was left open at the end of the source
+  pub fn finish(&self) -> Result<(), AssembleError> {
+    match self.0.first() {
+      Some(conditional) => Err(AssembleError::Syntax {
+        position: conditional.position.clone(),
+        message: "conditional is missing `.endif`".into(),
+      }),
+      None => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,561 @@
+use std::collections::HashMap;
+
+use super::{
+  conditional::{Conditional, Conditionals},
+  expression::Expression,
+  program::Program,
+  statement::{Directive, Operand, Statement, StatementKind},
+  token::Position,
+};
+use crate::{
+  core::{
+    decode::lookup,
+    instruction::{AddressingModeDiscriminants as Mode, OpCode},
+  },
+  error::AssembleError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pass {
+  /// Assigns addresses to labels and sizes to instructions without emitting
+  /// anything, so later statements can refer to labels defined after them
+  Collect,
+  /// Emits bytes with every label resolved
+  Emit,
+}
+
+/// A label or constant
+struct Definition {
+  value: i64,
+  position: Position,
+}
+
+/// Location and size of the output of one assembled statement
+pub struct Record {
+  /// Index of the statement
+  pub index: usize,
+  /// Location counter before the statement
+  pub address: u32,
+  /// Number of bytes written
+  pub length: usize,
+}
+
+/// Turns parsed statements into machine code
+pub struct Emitter {
+  pass: Pass,
+  symbols: HashMap<String, Definition>,
+  /// Constants that could not be evaluated when first seen because they
+  /// refer to symbols defined later, by statement index
+  pending: Vec<usize>,
+  /// Addressing modes chosen during [`Pass::Collect`], keyed by statement
+  /// index. Reused when emitting so instruction sizes cannot change between
+  /// passes.
+  modes: HashMap<usize, Mode>,
+  /// Conditions evaluated during [`Pass::Collect`], keyed by statement index,
+  /// so both passes assemble the same branches
+  decisions: HashMap<usize, bool>,
+  conditionals: Conditionals,
+  program_counter: u32,
+  /// Byte used by `.fill` when no value is given
+  fill: u8,
+  written: Vec<bool>,
+  program: Program,
+  /// Statements assembled during [`Pass::Emit`], in order
+  records: Vec<Record>,
+  /// Bytes written so far during [`Pass::Emit`]
+  emitted: usize,
+}
+
+impl Emitter {
+  pub fn new(fill: u8) -> Self {
+    Self {
+      pass: Pass::Collect,
+      symbols: Default::default(),
+      pending: Default::default(),
+      modes: Default::default(),
+      decisions: Default::default(),
+      conditionals: Default::default(),
+      program_counter: 0,
+      fill,
+      written: vec![false; 0x10000],
+      program: Program::new(fill),
+      records: Default::default(),
+      emitted: 0,
+    }
+  }
+
+  /// Statements that were assembled by the last call to [`Emitter::emit`]
+  pub fn records(&self) -> &[Record] {
+    &self.records
+  }
+
+  /// Labels and constants defined by the last call to [`Emitter::emit`]
+  pub fn symbols(&self) -> impl Iterator<Item = (&str, i64)> {
+    self
+      .symbols
+      .iter()
+      .map(|(name, definition)| (name.as_str(), definition.value))
+  }
+
+  pub fn emit(&mut self, statements: &[Statement]) -> Result<Program, AssembleError> {
+    for pass in [Pass::Collect, Pass::Emit] {
+      self.pass = pass;
+      self.program_counter = 0;
+
+      for (index, statement) in statements.iter().enumerate() {
+        self
+          .statement(index, statement)
+          .map_err(|error| statement.annotate(error))?;
+      }
+
+      self.conditionals.finish()?;
+
+      if pass == Pass::Collect {
+        self.resolve_pending(statements)?;
+      }
+    }
+
+    Ok(std::mem::replace(&mut self.program, Program::new(self.fill)))
+  }
+
+  /// Defines the constants that referred to symbols defined after them,
+  /// repeating until every constant is known
+  fn resolve_pending(&mut self, statements: &[Statement]) -> Result<(), AssembleError> {
+    while !self.pending.is_empty() {
+      let pending = std::mem::take(&mut self.pending);
+      let count = pending.len();
+
+      for &index in &pending {
+        let statement = &statements[index];
+        self
+          .statement(index, statement)
+          .map_err(|error| statement.annotate(error))?;
+      }
+
+      if self.pending.len() == count {
+        // no progress was made, so the remaining constants refer to symbols
+        // that are never defined
+        let statement = &statements[self.pending[0]];
+        if let StatementKind::Assignment { value, .. } = &statement.kind {
+          self.evaluate(value).map_err(|error| statement.annotate(error))?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn statement(&mut self, index: usize, statement: &Statement) -> Result<(), AssembleError> {
+    let Statement { position, kind, .. } = statement;
+    // conditionals are tracked even in branches that are not taken so nested
+    // blocks are matched up correctly
+    let conditional = matches!(kind, StatementKind::Directive(directive) if directive.is_conditional());
+    if !conditional && !self.active() {
+      return Ok(());
+    }
+
+    let address = self.program_counter;
+    let emitted = self.emitted;
+    match kind {
+      StatementKind::Label { name } => self.label(position, name),
+      StatementKind::Assignment { name, value } => self.assignment(index, position, name, value),
+      StatementKind::Instruction { opcode, operand } => self.instruction(index, position, *opcode, operand),
+      StatementKind::Directive(directive) => self.directive(index, position, directive),
+    }?;
+
+    if self.pass == Pass::Emit {
+      self.records.push(Record {
+        index,
+        address,
+        length: self.emitted - emitted,
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Whether statements are currently being assembled, i.e. not inside a
+  /// branch of a conditional that was not taken
+  fn active(&self) -> bool {
+    self.conditionals.active()
+  }
+
+  fn conditional(&mut self, index: usize, position: &Position, directive: &Directive) -> Result<(), AssembleError> {
+    match directive {
+      Directive::If(_) | Directive::IfDef(_) | Directive::IfNDef(_) => {
+        let enclosing = self.active();
+        let active = enclosing && self.decide(index, directive)?;
+        self
+          .conditionals
+          .open(Conditional::new(position.clone(), enclosing, Some(active)));
+      }
+      Directive::ElseIf(_) => {
+        let conditional = self.conditionals.innermost(position, directive)?;
+        let (enclosing, taken) = (conditional.enclosing, conditional.taken);
+        let active = enclosing && !taken && self.decide(index, directive)?;
+        self.conditionals.innermost(position, directive)?.else_if(active);
+      }
+      Directive::Else => self.conditionals.innermost(position, directive)?.otherwise(),
+      _ => {
+        self.conditionals.close(position)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Evaluates the condition of a conditional directive while collecting and
+  /// reuses the result when emitting
+  fn decide(&mut self, index: usize, directive: &Directive) -> Result<bool, AssembleError> {
+    if self.pass == Pass::Emit {
+      return Ok(self.decisions[&index]);
+    }
+
+    let decision = match directive {
+      Directive::If(condition) | Directive::ElseIf(condition) => self.evaluate(condition)? != 0,
+      Directive::IfDef(name) => self.symbols.contains_key(name),
+      Directive::IfNDef(name) => !self.symbols.contains_key(name),
+      _ => false,
+    };
+    self.decisions.insert(index, decision);
+
+    Ok(decision)
+  }
+
+  fn label(&mut self, position: &Position, name: &str) -> Result<(), AssembleError> {
+    if self.pass == Pass::Emit {
+      return Ok(());
+    }
+
+    let address = self.address(position)?;
+    self.define(position, name, address as i64)
+  }
+
+  fn assignment(
+    &mut self,
+    index: usize,
+    position: &Position,
+    name: &str,
+    value: &Expression,
+  ) -> Result<(), AssembleError> {
+    if self.pass == Pass::Emit {
+      return Ok(());
+    }
+
+    match self.try_evaluate(value)? {
+      Some(value) => self.define(position, name, value),
+      None => {
+        self.pending.push(index);
+        Ok(())
+      }
+    }
+  }
+
+  fn define(&mut self, position: &Position, name: &str, value: i64) -> Result<(), AssembleError> {
+    if let Some(symbol) = self.symbols.get(name) {
+      return Err(AssembleError::DuplicateLabel {
+        position: position.clone(),
+        name: name.into(),
+        previous: symbol.position.clone(),
+      });
+    }
+
+    self.symbols.insert(name.into(), Definition {
+      value,
+      position: position.clone(),
+    });
+
+    Ok(())
+  }
+
+  fn directive(&mut self, index: usize, position: &Position, directive: &Directive) -> Result<(), AssembleError> {
+    match directive {
+      Directive::If(_)
+      | Directive::ElseIf(_)
+      | Directive::Else
+      | Directive::EndIf
+      | Directive::IfDef(_)
+      | Directive::IfNDef(_) => self.conditional(index, position, directive)?,
+      Directive::Org(expression) => {
+        let origin = self.evaluate(expression)?;
+        if !(0..=0xFFFF).contains(&origin) {
+          return Err(AssembleError::AddressOutOfRange {
+            position: expression.position().clone(),
+            address: origin as u32,
+          });
+        }
+        self.program_counter = origin as u32;
+      }
+      Directive::Byte(expressions) => {
+        for expression in expressions {
+          let value = self.emit_value(expression, 8)?;
+          self.bytes(position, &[value as u8])?;
+        }
+      }
+      Directive::Word(expressions) => {
+        for expression in expressions {
+          let value = self.emit_value(expression, 16)?;
+          self.bytes(position, &(value as u16).to_le_bytes())?;
+        }
+      }
+      Directive::Ascii(text) => self.bytes(position, text.as_bytes())?,
+      Directive::Asciiz(text) => {
+        self.bytes(position, text.as_bytes())?;
+        self.bytes(position, &[0x00])?;
+      }
+      Directive::Res { count, value } => {
+        let count = self.count(count)?;
+        self.pad(position, count, value.as_ref())?;
+      }
+      Directive::Align { alignment, value } => {
+        let alignment = self.count(alignment)?;
+        if alignment == 0 {
+          return Err(AssembleError::Syntax {
+            position: position.clone(),
+            message: "alignment must be greater than zero".into(),
+          });
+        }
+        let count = (alignment - self.program_counter % alignment) % alignment;
+        self.pad(position, count, value.as_ref())?;
+      }
+      Directive::Fill { count, value } => {
+        let count = self.count(count)?;
+        let value = match value {
+          Some(value) => self.emit_value(value, 8)? as u8,
+          None => self.fill,
+        };
+        self.bytes(position, &vec![value; count as usize])?;
+      }
+      Directive::Include(_) => (),
+      Directive::IncBin { data, offset, length } => {
+        let start = match offset {
+          Some(offset) => self.count(offset)? as usize,
+          None => 0,
+        };
+        let end = match length {
+          Some(length) => start + self.count(length)? as usize,
+          None => data.len().max(start),
+        };
+        if end > data.len() {
+          return Err(AssembleError::Syntax {
+            position: position.clone(),
+            message: format!("range {start}..{end} exceeds the file size of {} bytes", data.len()),
+          });
+        }
+        self.bytes(position, &data[start..end])?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Evaluates a value that only needs to be known once emitting, checking
+  /// that it fits in `bits` bits
+  fn emit_value(&self, expression: &Expression, bits: u8) -> Result<i64, AssembleError> {
+    match self.pass {
+      Pass::Collect => Ok(0),
+      Pass::Emit => Self::fit(expression.position(), self.evaluate(expression)?, bits),
+    }
+  }
+
+  /// Evaluates a byte count, which must be known in the first pass since it
+  /// moves the location counter
+  fn count(&self, expression: &Expression) -> Result<u32, AssembleError> {
+    let count = self.evaluate(expression)?;
+    if (0..=0x10000).contains(&count) {
+      Ok(count as u32)
+    } else {
+      Err(AssembleError::ValueOutOfRange {
+        position: expression.position().clone(),
+        value: count,
+        bits: 16,
+      })
+    }
+  }
+
+  /// Skips `count` bytes, writing `value` into them if one is given
+  fn pad(&mut self, position: &Position, count: u32, value: Option<&Expression>) -> Result<(), AssembleError> {
+    match value {
+      Some(value) => {
+        let value = self.emit_value(value, 8)? as u8;
+        self.bytes(position, &vec![value; count as usize])
+      }
+      None => {
+        let end = self.program_counter + count;
+        if end > 0x10000 {
+          return Err(AssembleError::AddressOutOfRange {
+            position: position.clone(),
+            address: end - 1,
+          });
+        }
+        self.program_counter = end;
+        Ok(())
+      }
+    }
+  }
+
+  fn instruction(
+    &mut self,
+    index: usize,
+    position: &Position,
+    opcode: OpCode,
+    operand: &Operand,
+  ) -> Result<(), AssembleError> {
+    let mode = match self.pass {
+      Pass::Collect => {
+        let mode = self.mode(opcode, operand)?;
+        self.modes.insert(index, mode);
+        mode
+      }
+      Pass::Emit => self.modes[&index],
+    };
+
+    let Some(byte) = Self::encode(opcode, mode) else {
+      return Err(AssembleError::InvalidAddressingMode {
+        position: position.clone(),
+        opcode,
+        mode: format!("{mode:?}"),
+      });
+    };
+    self.bytes(position, &[byte])?;
+
+    let Some(expression) = operand.expression() else {
+      return Ok(());
+    };
+
+    if self.pass == Pass::Collect {
+      return self.bytes(position, &vec![0; mode.operand_len() as usize]);
+    }
+
+    let value = self.evaluate(expression)?;
+    match mode {
+      Mode::Relative => {
+        // offset is relative to the address following the branch
+        let offset = value - (self.program_counter as i64 + 1);
+        if !(-128..=127).contains(&offset) {
+          return Err(AssembleError::BranchOutOfRange {
+            position: expression.position().clone(),
+            offset,
+          });
+        }
+        self.bytes(position, &[offset as u8])
+      }
+      mode if mode.operand_len() == 1 => {
+        let value = Self::fit(expression.position(), value, 8)?;
+        self.bytes(position, &[value as u8])
+      }
+      _ => {
+        let value = Self::fit(expression.position(), value, 16)?;
+        self.bytes(position, &(value as u16).to_le_bytes())
+      }
+    }
+  }
+
+  fn mode(&self, opcode: OpCode, operand: &Operand) -> Result<Mode, AssembleError> {
+    let mode = match operand {
+      Operand::None => match Self::encode(opcode, Mode::Implied) {
+        Some(_) => Mode::Implied,
+        None => Mode::Accumulator,
+      },
+      Operand::Accumulator => Mode::Accumulator,
+      Operand::Immediate(_) => Mode::Immediate,
+      Operand::Direct(expression) => {
+        if Self::encode(opcode, Mode::Relative).is_some() {
+          Mode::Relative
+        } else {
+          self.zero_page_or(opcode, expression, Mode::ZeroPage, Mode::Absolute)?
+        }
+      }
+      Operand::DirectX(expression) => self.zero_page_or(opcode, expression, Mode::ZeroPageX, Mode::AbsoluteX)?,
+      Operand::DirectY(expression) => self.zero_page_or(opcode, expression, Mode::ZeroPageY, Mode::AbsoluteY)?,
+      Operand::Indirect(_) => Mode::Indirect,
+      Operand::IndirectX(_) => Mode::IndirectX,
+      Operand::IndirectY(_) => Mode::IndirectY,
+    };
+
+    Ok(mode)
+  }
+
+  /// Picks the zero page form of an instruction when the operand fits in a
+  /// byte and the op code supports it. Operands that refer to labels which
+  /// are not defined yet are assumed not to fit.
+  fn zero_page_or(
+    &self,
+    opcode: OpCode,
+    expression: &Expression,
+    zero_page: Mode,
+    absolute: Mode,
+  ) -> Result<Mode, AssembleError> {
+    match self.try_evaluate(expression)? {
+      Some(value) if (0..=0xFF).contains(&value) && Self::encode(opcode, zero_page).is_some() => Ok(zero_page),
+      _ => Ok(absolute),
+    }
+  }
+
+  fn encode(opcode: OpCode, mode: Mode) -> Option<u8> {
+    (0..=u8::MAX).find(|byte| matches!(lookup(*byte), Some((op, m, _)) if op == opcode && m == mode))
+  }
+
+  /// Like [`Emitter::evaluate`], but yields `None` for forward references
+  /// while collecting labels
+  fn try_evaluate(&self, expression: &Expression) -> Result<Option<i64>, AssembleError> {
+    match self.evaluate(expression) {
+      Ok(value) => Ok(Some(value)),
+      Err(AssembleError::UndefinedLabel { .. }) if self.pass == Pass::Collect => Ok(None),
+      Err(error) => Err(error),
+    }
+  }
+
+  fn evaluate(&self, expression: &Expression) -> Result<i64, AssembleError> {
+    expression.evaluate(&|name, position| match self.symbols.get(name) {
+      Some(symbol) => Ok(symbol.value),
+      None => Err(AssembleError::UndefinedLabel {
+        position: position.clone(),
+        name: name.into(),
+      }),
+    })
+  }
+
+  /// Checks that `value` fits in `bits` bits, accepting both signed and
+  /// unsigned interpretations
+  fn fit(position: &Position, value: i64, bits: u8) -> Result<i64, AssembleError> {
+    let min = -(1 << (bits - 1));
+    let max = (1 << bits) - 1;
+    if (min..=max).contains(&value) {
+      Ok(value & max)
+    } else {
+      Err(AssembleError::ValueOutOfRange {
+        position: position.clone(),
+        value,
+        bits,
+      })
+    }
+  }
+
+  fn address(&self, position: &Position) -> Result<u16, AssembleError> {
+    u16::try_from(self.program_counter).map_err(|_| AssembleError::AddressOutOfRange {
+      position: position.clone(),
+      address: self.program_counter,
+    })
+  }
+
+  fn bytes(&mut self, position: &Position, bytes: &[u8]) -> Result<(), AssembleError> {
+    for byte in bytes {
+      let address = self.address(position)?;
+      if self.pass == Pass::Emit {
+        if self.written[address as usize] {
+          return Err(AssembleError::Overlap {
+            position: position.clone(),
+            address,
+          });
+        }
+        self.written[address as usize] = true;
+        self.program.push(address, *byte);
+        self.emitted += 1;
+      }
+      self.program_counter += 1;
+    }
+
+    Ok(())
+  }
+}
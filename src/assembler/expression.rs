@@ -0,0 +1,98 @@
+use super::{
+  literal::Literal,
+  symbol::Symbol,
+  token::{LiteralToken, Position, SymbolToken},
+};
+use crate::error::AssembleError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+  Literal {
+    token: LiteralToken,
+  },
+  Unary {
+    operator: SymbolToken,
+    operand: Box<Expression>,
+  },
+  Binary {
+    left_operand: Box<Expression>,
+    operator: SymbolToken,
+    right_operand: Box<Expression>,
+  },
+  Grouping {
+    operand: Box<Expression>,
+  },
+}
+
+impl Expression {
+  pub fn position(&self) -> &Position {
+    match self {
+      Expression::Literal { token } => &token.position,
+      Expression::Unary { operator, .. } => &operator.position,
+      Expression::Binary { left_operand, .. } => left_operand.position(),
+      Expression::Grouping { operand } => operand.position(),
+    }
+  }
+
+  /// Evaluates the expression, looking up the value of every symbol it
+  /// refers to with `symbol`
+  pub fn evaluate(
+    &self,
+    symbol: &impl Fn(&str, &Position) -> Result<i64, AssembleError>,
+  ) -> Result<i64, AssembleError> {
+    match self {
+      Expression::Literal { token } => match token {
+        LiteralToken {
+          literal: Literal::Number { lexeme },
+          ..
+        } => Ok(*lexeme),
+        LiteralToken {
+          position,
+          literal: Literal::Identifier { lexeme },
+        } => symbol(lexeme, position),
+        LiteralToken { position, literal } => Err(AssembleError::Syntax {
+          position: position.clone(),
+          message: format!("expected value but got `{}`", literal.lexeme()),
+        }),
+      },
+      Expression::Unary { operator, operand } => {
+        let operand = operand.evaluate(symbol)?;
+        match operator.symbol {
+          Symbol::Minus => Ok(operand.wrapping_neg()),
+          Symbol::LeftAngledBracket => Ok(operand & 0xFF),
+          Symbol::RightAngledBracket => Ok((operand >> 8) & 0xFF),
+          _ => Ok((operand == 0) as i64),
+        }
+      }
+      Expression::Binary {
+        left_operand,
+        operator,
+        right_operand,
+      } => {
+        let left = left_operand.evaluate(symbol)?;
+        let right = right_operand.evaluate(symbol)?;
+        match operator.symbol {
+          Symbol::Plus => Ok(left.wrapping_add(right)),
+          Symbol::Minus => Ok(left.wrapping_sub(right)),
+          Symbol::Asterisk => Ok(left.wrapping_mul(right)),
+          Symbol::ForwardSlash => match left.checked_div(right) {
+            Some(value) => Ok(value),
+            None => Err(AssembleError::Syntax {
+              position: operator.position.clone(),
+              message: "division by zero".into(),
+            }),
+          },
+          Symbol::DoubleEquals => Ok((left == right) as i64),
+          Symbol::ExclamationPointEquals => Ok((left != right) as i64),
+          Symbol::LeftAngledBracket => Ok((left < right) as i64),
+          Symbol::RightAngledBracket => Ok((left > right) as i64),
+          Symbol::LeftAngledBracketEquals => Ok((left <= right) as i64),
+          Symbol::RightAngledBracketEquals => Ok((left >= right) as i64),
+          Symbol::DoubleAmpersand => Ok((left != 0 && right != 0) as i64),
+          _ => Ok((left != 0 || right != 0) as i64),
+        }
+      }
+      Expression::Grouping { operand } => operand.evaluate(symbol),
+    }
+  }
+}
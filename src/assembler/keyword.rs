@@ -1,31 +1,65 @@
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Keyword {
   Org,
+  Byte,
+  Word,
+  Ascii,
+  Asciiz,
+  Res,
+  Align,
+  Fill,
+  Macro,
+  EndMacro,
+  If,
+  ElseIf,
+  Else,
+  EndIf,
+  IfDef,
+  IfNDef,
+  Include,
+  IncBin,
 }
 
 impl Keyword {
-  pub const AS: &'static str = "org";
+  pub const ALIGN: &'static str = "align";
+  pub const ASCII: &'static str = "ascii";
+  pub const ASCIIZ: &'static str = "asciiz";
+  pub const BYTE: &'static str = "byte";
+  pub const ELSE: &'static str = "else";
+  pub const ELSEIF: &'static str = "elseif";
+  pub const ENDIF: &'static str = "endif";
+  pub const ENDMACRO: &'static str = "endmacro";
+  pub const FILL: &'static str = "fill";
+  pub const IF: &'static str = "if";
+  pub const IFDEF: &'static str = "ifdef";
+  pub const IFNDEF: &'static str = "ifndef";
+  pub const INCBIN: &'static str = "incbin";
+  pub const INCLUDE: &'static str = "include";
+  pub const MACRO: &'static str = "macro";
+  pub const ORG: &'static str = "org";
+  pub const RES: &'static str = "res";
+  pub const WORD: &'static str = "word";
 
   pub fn lexeme(&self) -> String {
     match self {
-      Self::If => Self::IF.into(),
-      Self::Else => Self::ELSE.into(),
-      Self::For => Self::FOR.into(),
-      Self::While => Self::WHILE.into(),
-      Self::Loop => Self::LOOP.into(),
-      Self::Return => Self::RETURN.into(),
-      Self::_Self => Self::SELF.into(),
-      Self::_SelfType => Self::SELF_TYPE.into(),
-      Self::Super => Self::SUPER.into(),
-      Self::Export => Self::EXPORT.into(),
-      Self::Import => Self::IMPORT.into(),
-      Self::Public => Self::PUBLIC.into(),
-      Self::Type => Self::TYPE.into(),
-      Self::Impl => Self::IMPL.into(),
-      Self::As => Self::AS.into(),
-      Self::Trait => Self::TRAIT.into(),
-      Self::True => Self::TRUE.into(),
-      Self::False => Self::FALSE.into(),
+      Self::Org => format!(".{}", Self::ORG),
+      Self::Byte => format!(".{}", Self::BYTE),
+      Self::Word => format!(".{}", Self::WORD),
+      Self::Ascii => format!(".{}", Self::ASCII),
+      Self::Asciiz => format!(".{}", Self::ASCIIZ),
+      Self::Res => format!(".{}", Self::RES),
+      Self::Align => format!(".{}", Self::ALIGN),
+      Self::Fill => format!(".{}", Self::FILL),
+      Self::Macro => format!(".{}", Self::MACRO),
+      Self::EndMacro => format!(".{}", Self::ENDMACRO),
+      Self::If => format!(".{}", Self::IF),
+      Self::ElseIf => format!(".{}", Self::ELSEIF),
+      Self::Else => format!(".{}", Self::ELSE),
+      Self::EndIf => format!(".{}", Self::ENDIF),
+      Self::IfDef => format!(".{}", Self::IFDEF),
+      Self::IfNDef => format!(".{}", Self::IFNDEF),
+      Self::Include => format!(".{}", Self::INCLUDE),
+      Self::IncBin => format!(".{}", Self::INCBIN),
     }
   }
 }
@@ -1,30 +1,30 @@
+use std::{path::Path, str::FromStr, sync::Arc};
+
 use super::{
-  error::{error_handler::ErrorHandler, InterpreterError},
-  grammar::{
-    literal::Literal,
-    symbol::Symbol,
-    token::{LiteralToken, Position, SymbolToken, Token},
-  },
+  literal::Literal,
+  symbol::Symbol,
+  token::{LiteralToken, Position, SymbolToken, Token},
   util::character_provider::CharacterProvider,
 };
+use crate::{
+  core::{decode::lookup, instruction::OpCode},
+  error::AssembleError,
+};
 
-pub struct Lexer {
-  error_handler: Handle<ErrorHandler>,
-}
+#[derive(Default)]
+pub struct Lexer;
 
 impl Lexer {
-  pub fn new(error_handler: Handle<ErrorHandler>) -> Self {
-    Self { error_handler }
+  pub fn new() -> Self {
+    Self
   }
 
-  pub fn lex(&mut self, source: &str) -> Vec<Token> {
-    let mut characters = CharacterProvider::new(source);
+  /// Splits `source` into tokens whose positions refer to `file`
+  pub fn lex(&mut self, source: &str, file: Option<Arc<Path>>) -> Result<Vec<Token>, AssembleError> {
+    let mut characters = CharacterProvider::new(source, file);
 
     let mut tokens = Vec::default();
-    while let Some(token) = self.build_token(&mut characters) {
-      if let Token::Invalid { error, .. } = &token {
-        self.error_handler.get_mut().push(error.clone());
-      }
+    while let Some(token) = self.build_token(&mut characters)? {
       tokens.push(token);
     }
 
@@ -32,455 +32,190 @@ impl Lexer {
       position: characters.current_position(),
     });
 
-    tokens
+    Ok(tokens)
   }
 
-  fn build_token(&mut self, characters: &mut CharacterProvider) -> Option<Token> {
-    let next_character = characters.next()?;
+  /// The op code named by `lexeme`, if the assembler can encode it.
+  /// Undocumented and 65C02 mnemonics stay free for use as identifiers.
+  fn mnemonic(lexeme: &str) -> Option<OpCode> {
+    let opcode = OpCode::from_str(lexeme).ok()?;
+    (0..=u8::MAX)
+      .any(|byte| matches!(lookup(byte), Some((op, ..)) if op == opcode))
+      .then_some(opcode)
+  }
+
+  fn build_token(&mut self, characters: &mut CharacterProvider) -> Result<Option<Token>, AssembleError> {
+    let Some(next_character) = characters.next() else {
+      return Ok(None);
+    };
+    let position = characters.current_position();
     let mut lexeme = String::new();
-    match next_character {
-      letter if letter.is_ascii_alphabetic() || letter == '_' => {
-        let start_of_lexeme = characters.current_column();
 
-        // identifiers
+    let token = match next_character {
+      '\n' => Token::EndOfLine { position },
+      letter if letter.is_ascii_alphabetic() || letter == '_' => {
+        // identifiers and op codes
         lexeme.push(letter);
         lexeme.push_str(&Self::read_lexeme_while(characters, |c| c.is_ascii_alphanumeric() || c == &'_'));
 
-        return if let Some(reserved_word) =
-          Token::reserved_word(&lexeme, Position::new(characters.current_line(), start_of_lexeme))
-        {
-          Some(reserved_word)
-        } else {
-          Some(Token::Literal(LiteralToken {
-            position: Position {
-              line: characters.current_line(),
-              column: start_of_lexeme,
-            },
-            literal: Literal::Identifier { lexeme },
-          }))
+        let literal = match Self::mnemonic(&lexeme) {
+          Some(opcode) => Literal::OpCode { lexeme: opcode },
+          None => Literal::Identifier { lexeme },
         };
+
+        Token::Literal(LiteralToken { position, literal })
       }
-      digit if digit.is_ascii_digit() => {
-        let start_of_lexeme = characters.current_column();
+      '.' => {
+        // directives
+        lexeme.push_str(&Self::read_lexeme_while(characters, |c| c.is_ascii_alphanumeric() || c == &'_'));
 
-        // numbers
+        Token::reserved_word(&lexeme, position.clone()).ok_or_else(|| AssembleError::Syntax {
+          position,
+          message: format!("unknown directive `.{lexeme}`"),
+        })?
+      }
+      digit if digit.is_ascii_digit() => {
+        // decimal numbers
         lexeme.push(digit);
         lexeme.push_str(&Self::read_lexeme_while(characters, |c| c.is_ascii_digit()));
-
-        return if let Ok(lexeme) = lexeme.parse::<i64>() {
-          Some(Token::Literal(LiteralToken {
-            position: Position {
-              line: characters.current_line(),
-              column: start_of_lexeme,
-            },
-            literal: Literal::Number { lexeme },
-          }))
-        } else {
-          Some(Token::Invalid {
-            position: characters.current_position(),
-            error: InterpreterError::SyntaxError {
-              position: characters.current_position(),
-              message: "Failed to parse number".into(),
-            },
-          })
-        };
+        Self::number(&lexeme, 10, position)?
       }
-      symbol if symbol.is_ascii_punctuation() => {
-        // symbols
-        match symbol {
-          ';' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::Semicolon,
-            }))
-          }
-          ',' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::Comma,
-            }))
-          }
-          '.' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::Period,
-            }))
-          }
-          ':' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::Colon,
-            }))
-          }
-          '!' => {
-            return if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::ExclamationPointEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::ExclamationPoint,
-              }))
-            };
-          }
-          '=' => {
-            return if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::DoubleEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::Equals,
-              }))
-            };
-          }
-          '+' => {
-            return if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::PlusEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::Plus,
-              }))
-            };
-          }
-          '-' => {
-            return if Self::next_char_is(characters, '>') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::RightArrow,
-              }))
-            } else if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::MinusEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::Minus,
-              }))
-            };
-          }
-          '/' => {
-            return if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::ForwardSlashEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::ForwardSlash,
-              }))
-            };
-          }
-          '*' => {
-            return if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::AsteriskEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::Asterisk,
-              }))
-            };
-          }
-          '^' => {
-            return if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::CaretEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::Caret,
-              }))
-            };
-          }
-          '&' => {
-            return if Self::next_char_is(characters, '&') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::DoubleAmpersand,
-              }))
-            } else if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::AmpersandEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::Ampersand,
-              }))
-            };
-          }
-          '|' => {
-            return if Self::next_char_is(characters, '|') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::DoublePipe,
-              }))
-            } else if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::PipeEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::Pipe,
-              }))
-            };
-          }
-          '(' => {
-            return if Self::next_char_is(characters, ')') {
-              Some(Token::Literal(LiteralToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                literal: Literal::Void,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::LeftParenthesis,
-              }))
-            };
-          }
-          ')' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::RightParenthesis,
-            }))
-          }
-          '{' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::LeftCurlyBracket,
-            }))
-          }
-          '}' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::RightCurlyBracket,
-            }))
-          }
-          '<' => {
-            return if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::LeftAngledBracketEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::LeftAngledBracket,
-              }))
-            };
-          }
-          '>' => {
-            return if Self::next_char_is(characters, '=') {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::RightAngledBracketEquals,
-              }))
-            } else {
-              Some(Token::Symbol(SymbolToken {
-                position: Position {
-                  line: characters.current_line(),
-                  column: characters.current_column(),
-                },
-                symbol: Symbol::RightAngledBracket,
-              }))
-            };
-          }
-          '[' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::LeftSquareBracket,
-            }))
-          }
-          ']' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::RightSquareBracket,
-            }))
-          }
-          '\'' => {
-            return Some(Token::Symbol(SymbolToken {
-              position: Position {
-                line: characters.current_line(),
-                column: characters.current_column(),
-              },
-              symbol: Symbol::Apostrophe,
-            }))
+      '$' => {
+        // hexadecimal numbers
+        lexeme.push_str(&Self::read_lexeme_while(characters, |c| c.is_ascii_hexdigit()));
+        Self::number(&lexeme, 16, position)?
+      }
+      '"' => {
+        // strings
+        loop {
+          match characters.next_with_spaces() {
+            Some('"') => break,
+            Some('\\') => lexeme.push(Self::escape(characters, &position)?),
+            Some('\n') | None => Err(AssembleError::Syntax {
+              position: position.clone(),
+              message: "unterminated string".into(),
+            })?,
+            Some(character) => lexeme.push(character),
           }
-          '\"' => {
-            let start_of_lexeme = characters.current_column();
-
-            let is_end_of_string = Self::next_char_is(characters, '\"');
-            if let Some(_) = characters.peek()
-              && !is_end_of_string
-            {
-              while let Some(c) = characters.peek() {
-                if *c != '\"' {
-                  lexeme.push(characters.next_with_spaces().unwrap());
-                } else {
-                  characters.next().unwrap();
+        }
 
-                  return Some(Token::Literal(LiteralToken {
-                    position: Position {
-                      line: characters.current_line(),
-                      column: start_of_lexeme,
-                    },
-                    literal: Literal::String { lexeme },
-                  }));
-                }
-              }
-            }
+        Token::Literal(LiteralToken {
+          position,
+          literal: Literal::String { lexeme },
+        })
+      }
+      '%' => {
+        // binary numbers
+        lexeme.push_str(&Self::read_lexeme_while(characters, |c| *c == '0' || *c == '1'));
+        Self::number(&lexeme, 2, position)?
+      }
+      '\'' => {
+        // character literals
+        let character = match characters.next_with_spaces() {
+          Some('\\') => Self::escape(characters, &position)?,
+          Some(character) if character != '\n' && character != '\'' => character,
+          _ => Err(AssembleError::Syntax {
+            position: position.clone(),
+            message: "expected character".into(),
+          })?,
+        };
+        if characters.next_with_spaces() != Some('\'') {
+          Err(AssembleError::Syntax {
+            position: position.clone(),
+            message: "unterminated character literal".into(),
+          })?;
+        }
 
-            return Some(Token::Invalid {
-              position: characters.current_position(),
-              error: InterpreterError::UnterminatedString {
-                position: characters.current_position(),
-              },
-            });
-          }
-          _ => {}
+        Token::Literal(LiteralToken {
+          position,
+          literal: Literal::Number {
+            lexeme: character as i64,
+          },
+        })
+      }
+      '#' => Self::symbol(position, Symbol::Hashtag),
+      ',' => Self::symbol(position, Symbol::Comma),
+      ':' => Self::symbol(position, Symbol::Colon),
+      '(' => Self::symbol(position, Symbol::LeftParenthesis),
+      ')' => Self::symbol(position, Symbol::RightParenthesis),
+      '+' => Self::symbol(position, Symbol::Plus),
+      '-' => Self::symbol(position, Symbol::Minus),
+      '*' => Self::symbol(position, Symbol::Asterisk),
+      '/' => Self::symbol(position, Symbol::ForwardSlash),
+      '!' => {
+        if Self::next_char_is(characters, '=') {
+          Self::symbol(position, Symbol::ExclamationPointEquals)
+        } else {
+          Self::symbol(position, Symbol::ExclamationPoint)
         }
       }
-      _ => {}
-    }
+      '=' => {
+        if Self::next_char_is(characters, '=') {
+          Self::symbol(position, Symbol::DoubleEquals)
+        } else {
+          Self::symbol(position, Symbol::Equals)
+        }
+      }
+      '<' => {
+        if Self::next_char_is(characters, '=') {
+          Self::symbol(position, Symbol::LeftAngledBracketEquals)
+        } else {
+          Self::symbol(position, Symbol::LeftAngledBracket)
+        }
+      }
+      '>' => {
+        if Self::next_char_is(characters, '=') {
+          Self::symbol(position, Symbol::RightAngledBracketEquals)
+        } else {
+          Self::symbol(position, Symbol::RightAngledBracket)
+        }
+      }
+      '&' if Self::next_char_is(characters, '&') => Self::symbol(position, Symbol::DoubleAmpersand),
+      '|' if Self::next_char_is(characters, '|') => Self::symbol(position, Symbol::DoublePipe),
+      character => Err(AssembleError::UnexpectedCharacter { position, character })?,
+    };
 
-    Some(Token::Invalid {
-      position: characters.current_position(),
-      error: InterpreterError::UnknownToken {
+    Ok(Some(token))
+  }
+
+  fn symbol(position: Position, symbol: Symbol) -> Token {
+    Token::Symbol(SymbolToken { position, symbol })
+  }
+
+  fn escape(characters: &mut CharacterProvider, position: &Position) -> Result<char, AssembleError> {
+    match characters.next_with_spaces() {
+      Some('n') => Ok('\n'),
+      Some('r') => Ok('\r'),
+      Some('t') => Ok('\t'),
+      Some('0') => Ok('\0'),
+      Some(character @ ('\\' | '"' | '\'')) => Ok(character),
+      Some(character) => Err(AssembleError::Syntax {
         position: characters.current_position(),
-        token: next_character.into(),
-      },
-    })
+        message: format!("unknown escape sequence `\\{character}`"),
+      }),
+      None => Err(AssembleError::Syntax {
+        position: position.clone(),
+        message: "unterminated literal".into(),
+      }),
+    }
+  }
+
+  fn number(lexeme: &str, radix: u32, position: Position) -> Result<Token, AssembleError> {
+    match i64::from_str_radix(lexeme, radix) {
+      Ok(lexeme) => Ok(Token::Literal(LiteralToken {
+        position,
+        literal: Literal::Number { lexeme },
+      })),
+      Err(_) => Err(AssembleError::Syntax {
+        position,
+        message: "failed to parse number".into(),
+      }),
+    }
   }
 
   fn next_char_is(characters: &mut CharacterProvider, character: char) -> bool {
-    if let Some(c) = characters.peek()
-      && c == &character
-    {
-      characters.next();
+    if characters.peek() == Some(&character) {
+      characters.next_with_spaces();
       true
     } else {
       false
@@ -489,10 +224,11 @@ impl Lexer {
 
   fn read_lexeme_while(characters: &mut CharacterProvider, condition: impl Fn(&char) -> bool) -> String {
     let mut lexeme = String::new();
-    while let Some(c) = characters.peek()
-      && condition(c)
-    {
-      lexeme.push(characters.next().unwrap());
+    while let Some(c) = characters.peek() {
+      if !condition(c) {
+        break;
+      }
+      lexeme.push(characters.next_with_spaces().unwrap());
     }
     lexeme
   }
@@ -0,0 +1,198 @@
+use std::{
+  collections::HashMap,
+  fmt::{Display, Formatter},
+  path::Path,
+  sync::Arc,
+};
+
+use super::{
+  emitter::Record,
+  program::Program,
+  statement::{Directive, Statement, StatementKind},
+};
+use crate::core::decode::lookup;
+
+/// Bytes shown on each row of the listing, the length of the longest
+/// instruction
+const BYTES_PER_ROW: usize = 3;
+
+/// Human readable record of an assembly: every source line with the address
+/// and bytes it produced, followed by the symbol table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+  lines: Vec<ListingLine>,
+  symbols: Vec<(String, i64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingLine {
+  pub file: Option<Arc<Path>>,
+  pub line: u32,
+  /// Location counter at the start of the line, if anything on it was
+  /// assembled
+  pub address: Option<u16>,
+  pub bytes: Vec<u8>,
+  /// Base cycle count of the instructions on the line, without the extra
+  /// cycles for page crossings and taken branches
+  pub cycles: Option<u32>,
+  pub text: String,
+}
+
+impl Listing {
+  /// Builds the listing from the statements of an assembly and the records
+  /// the emitter kept of them. `sources` maps each file to its text, with
+  /// `root` being the file that was assembled. Lines of files without a
+  /// source, like command line definitions, are left out.
+  pub(crate) fn new<'a>(
+    statements: &[Statement],
+    records: &[Record],
+    program: &Program,
+    symbols: impl Iterator<Item = (&'a str, i64)>,
+    root: Option<Arc<Path>>,
+    sources: &HashMap<Option<Arc<Path>>, &str>,
+  ) -> Self {
+    let image = program.to_bytes();
+    let texts: HashMap<_, Vec<&str>> = sources
+      .iter()
+      .map(|(file, source)| (file.clone(), source.lines().collect()))
+      .collect();
+    let text = |file: &Option<Arc<Path>>, line: u32| texts[file].get(line as usize - 1).unwrap_or(&"").to_string();
+
+    let mut lines: Vec<ListingLine> = Vec::new();
+    // next line of each file that has not been listed yet
+    let mut next: HashMap<Option<Arc<Path>>, u32> = HashMap::new();
+    let gap = |lines: &mut Vec<ListingLine>, next: &mut HashMap<_, _>, file: &Option<Arc<Path>>, until: u32| {
+      let first = next.get(file).copied().unwrap_or(1);
+      for line in first..until {
+        lines.push(ListingLine {
+          file: file.clone(),
+          line,
+          address: None,
+          bytes: Vec::new(),
+          cycles: None,
+          text: text(file, line),
+        });
+      }
+      next.insert(file.clone(), until.max(first));
+    };
+
+    for record in records {
+      let statement = &statements[record.index];
+      let position = statement.origin();
+      if !texts.contains_key(&position.file) {
+        continue;
+      }
+
+      let bytes = &image[record.address as usize..record.address as usize + record.length];
+      // statements that do not occupy the location counter get no address
+      let address = match &statement.kind {
+        StatementKind::Assignment { .. } => None,
+        StatementKind::Directive(directive) if directive.is_conditional() => None,
+        StatementKind::Directive(Directive::Org(_) | Directive::Include(_)) => None,
+        _ => Some(record.address as u16),
+      };
+      let cycles = match statement.kind {
+        StatementKind::Instruction { .. } => bytes
+          .first()
+          .and_then(|&byte| lookup(byte))
+          .map(|(.., cycles)| cycles as u32),
+        _ => None,
+      };
+
+      match lines.last_mut() {
+        Some(last) if last.file == position.file && last.line == position.line => {
+          last.address = last.address.or(address);
+          last.bytes.extend_from_slice(bytes);
+          last.cycles = match (last.cycles, cycles) {
+            (Some(total), Some(cycles)) => Some(total + cycles),
+            (total, cycles) => total.or(cycles),
+          };
+        }
+        _ => {
+          gap(&mut lines, &mut next, &position.file, position.line);
+          lines.push(ListingLine {
+            file: position.file.clone(),
+            line: position.line,
+            address,
+            bytes: bytes.to_vec(),
+            cycles,
+            text: text(&position.file, position.line),
+          });
+          next.insert(position.file.clone(), position.line + 1);
+        }
+      }
+    }
+
+    if let Some(lines_in_root) = texts.get(&root).map(Vec::len) {
+      gap(&mut lines, &mut next, &root, lines_in_root as u32 + 1);
+    }
+
+    let mut symbols: Vec<_> = symbols.map(|(name, value)| (name.to_string(), value)).collect();
+    symbols.sort();
+
+    Self { lines, symbols }
+  }
+
+  pub fn lines(&self) -> &[ListingLine] {
+    &self.lines
+  }
+
+  /// Every label and constant with its value, sorted by name
+  pub fn symbols(&self) -> &[(String, i64)] {
+    &self.symbols
+  }
+}
+
+impl Display for Listing {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let mut file = None;
+    for line in &self.lines {
+      if line.file != file {
+        file = line.file.clone();
+        if let Some(path) = &file {
+          writeln!(f, "{}", path.display())?;
+        }
+      }
+
+      let address = line.address.map(|address| format!("{address:04X}")).unwrap_or_default();
+      let mut rows = line.bytes.chunks(BYTES_PER_ROW);
+      let bytes = rows.next().map(hex).unwrap_or_default();
+      let cycles = line.cycles.map(|cycles| cycles.to_string()).unwrap_or_default();
+      let row = format!("{:>5}  {address:<4}  {bytes:<8}  {cycles:>2}  {}", line.line, line.text);
+      writeln!(f, "{}", row.trim_end())?;
+
+      for (index, bytes) in rows.enumerate() {
+        let address = line.address.unwrap_or_default() as usize + (index + 1) * BYTES_PER_ROW;
+        writeln!(f, "{:>5}  {address:04X}  {}", "", hex(bytes))?;
+      }
+    }
+
+    if self.symbols.is_empty() {
+      return Ok(());
+    }
+
+    writeln!(f, "\nSymbols")?;
+    let width = self
+      .symbols
+      .iter()
+      .map(|(name, _)| name.len())
+      .max()
+      .unwrap_or_default();
+    for (name, value) in &self.symbols {
+      match value {
+        0..=0xFFFF => writeln!(f, "  {name:<width$}  ${value:04X}")?,
+        _ => writeln!(f, "  {name:<width$}  {value}")?,
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes
+    .iter()
+    .map(|byte| format!("{byte:02X}"))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
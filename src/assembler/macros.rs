@@ -0,0 +1,117 @@
+use std::{collections::HashSet, rc::Rc};
+
+use super::{
+  literal::Literal,
+  symbol::Symbol,
+  token::{LiteralToken, Position, SymbolToken, Token},
+};
+use crate::error::AssembleError;
+
+/// A `.macro name param, ... .endmacro` definition
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macro {
+  pub name: String,
+  pub parameters: Vec<String>,
+  pub body: Vec<Token>,
+  pub position: Position,
+}
+
+impl Macro {
+  /// Substitutes the argument tokens for the parameters and makes the labels
+  /// defined in the body unique to this expansion by appending `@suffix`
+  pub fn expand(&self, arguments: &[Vec<Token>], suffix: usize) -> Vec<Token> {
+    let locals = self.local_labels();
+
+    let mut expanded = Vec::with_capacity(self.body.len());
+    for token in &self.body {
+      let Token::Literal(LiteralToken {
+        position,
+        literal: Literal::Identifier { lexeme },
+      }) = token
+      else {
+        expanded.push(token.clone());
+        continue;
+      };
+
+      if let Some(index) = self.parameters.iter().position(|parameter| parameter == lexeme) {
+        expanded.extend(arguments[index].iter().cloned());
+      } else if locals.contains(lexeme.as_str()) {
+        expanded.push(Token::Literal(LiteralToken {
+          position: position.clone(),
+          literal: Literal::Identifier {
+            lexeme: format!("{lexeme}@{suffix}"),
+          },
+        }));
+      } else {
+        expanded.push(token.clone());
+      }
+    }
+
+    expanded
+  }
+
+  /// Names of the labels defined at the start of a line in the body
+  fn local_labels(&self) -> HashSet<&str> {
+    let mut locals = HashSet::new();
+    let mut line_start = true;
+    for (index, token) in self.body.iter().enumerate() {
+      if let (
+        true,
+        Token::Literal(LiteralToken {
+          literal: Literal::Identifier { lexeme },
+          ..
+        }),
+        Some(Token::Symbol(SymbolToken {
+          symbol: Symbol::Colon, ..
+        })),
+      ) = (line_start, token, self.body.get(index + 1))
+      {
+        locals.insert(lexeme.as_str());
+      }
+
+      line_start = matches!(token, Token::EndOfLine { .. });
+    }
+
+    locals
+  }
+}
+
+/// Where a macro was expanded. Statements produced by an expansion keep a
+/// reference to it so errors can point at both the macro body and the call
+/// site.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+  pub name: String,
+  pub position: Position,
+  pub parent: Option<Rc<Invocation>>,
+}
+
+impl Invocation {
+  /// Whether the macro `name` is this invocation or one it is nested in
+  pub fn contains(&self, name: &str) -> bool {
+    self.name == name || self.parent.as_ref().is_some_and(|parent| parent.contains(name))
+  }
+
+  /// Position of the outermost invocation, i.e. the line in the source that
+  /// led to this expansion
+  pub fn origin(&self) -> &Position {
+    match &self.parent {
+      Some(parent) => parent.origin(),
+      None => &self.position,
+    }
+  }
+
+  /// Wraps `error` in this invocation and every invocation it is nested in
+  pub fn annotate(&self, error: AssembleError) -> AssembleError {
+    let error = AssembleError::Macro {
+      name: self.name.clone(),
+      position: self.position.clone(),
+      error: Box::new(error),
+    };
+
+    match &self.parent {
+      Some(parent) => parent.annotate(error),
+      None => error,
+    }
+  }
+}
@@ -1,186 +1,888 @@
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+  rc::Rc,
+  sync::Arc,
+};
+
 use enumflags2::BitFlags;
+
 use super::{
+  conditional::{Conditional, Conditionals},
+  expression::Expression,
   keyword::Keyword,
+  lexer::Lexer,
   literal::Literal,
+  macros::{Invocation, Macro},
+  statement::{Directive, Operand, Statement, StatementKind},
   symbol::Symbol,
-  token::{KeywordToken, LiteralToken, Position, SymbolToken, Token, TokenDiscriminants},
+  token::{KeywordToken, LiteralToken, Position, SymbolToken, Token},
   util::token_provider::{Next, TokenProvider},
 };
-
-#[derive(Debug, PartialEq, Eq)]
-enum DelimiterType {
-  Paren,
-  Curly,
-  Angled,
-  Square,
+use crate::error::AssembleError;
+
+/// What the parser knows about a symbol it has seen defined
+#[derive(Debug, Clone, Copy)]
+enum Known {
+  /// A constant whose value could be evaluated
+  Value(i64),
+  /// A label or a constant that depends on one
+  Defined,
+  /// Defined in a branch of a conditional only the emitter can decide
+  Maybe,
 }
 
-#[derive(Debug, Eq)]
-struct Delimiter {
-  position: Position,
-  delimiter: DelimiterType,
+/// Turns tokens into statements, expanding macros and includes on the way.
+/// Conditionals are decided here when their condition only refers to
+/// predefined symbols and constants defined before them, so branches that are
+/// not taken can name files that do not exist or define the same macro as
+/// another branch. The remaining conditionals are left to the emitter, with
+/// every branch parsed.
+#[derive(Default)]
+pub struct Parser {
+  macros: HashMap<String, Macro>,
+  /// Macro expansion currently being parsed
+  invocation: Option<Rc<Invocation>>,
+  expansions: usize,
+  /// Directories searched for `.include` and `.incbin` files
+  include_paths: Vec<PathBuf>,
+  /// Canonical paths of the files currently being parsed
+  includes: Vec<PathBuf>,
+  /// Text of every included file, for the listing
+  sources: HashMap<Arc<Path>, String>,
+  /// Symbols defined so far, for deciding conditionals
+  symbols: HashMap<String, Known>,
+  conditionals: Conditionals,
 }
 
-impl Delimiter {
-  pub fn lexeme(&self) -> String {
-    match self.delimiter {
-      DelimiterType::Paren => "(".into(),
-      DelimiterType::Curly => "{".into(),
-      DelimiterType::Angled => "<".into(),
-      DelimiterType::Square => "[".into(),
-    }
+impl Parser {
+  pub fn new() -> Self {
+    Self::default()
   }
 
-  fn position(&self) -> &Position {
-    &self.position
+  pub fn add_include_path(&mut self, path: PathBuf) {
+    self.include_paths.push(path);
   }
-}
 
-impl PartialEq for Delimiter {
-  fn eq(&self, other: &Self) -> bool {
-    self.delimiter == other.delimiter
+  /// Text of the files included by the last call to [`Parser::parse`]
+  pub fn sources(&self) -> &HashMap<Arc<Path>, String> {
+    &self.sources
   }
-}
 
-impl TryFrom<Token> for Delimiter {
-  type Error = ();
+  /// Parses `tokens` following the `definitions` of the assembler, which are
+  /// known to conditionals
+  pub fn parse(&mut self, tokens: &[Token], definitions: &[Statement]) -> Result<Vec<Statement>, AssembleError> {
+    self.macros.clear();
+    self.sources.clear();
+    self.symbols.clear();
+    self.conditionals.clear();
+    for definition in definitions {
+      if let StatementKind::Assignment { name, value } = &definition.kind {
+        self.define(name, Some(value));
+      }
+    }
+    self.invocation = None;
+    self.expansions = 0;
+    self.includes = tokens
+      .first()
+      .and_then(|token| token.position().file.as_ref())
+      .and_then(|file| fs::canonicalize(file).ok())
+      .into_iter()
+      .collect();
+
+    let mut statements = Vec::new();
+    self.block(&mut TokenProvider::new(tokens), &mut statements)?;
+
+    self.conditionals.finish()?;
+    Ok(statements)
+  }
 
-  fn try_from(token: Token) -> Result<Self, Self::Error> {
-    let Token::Symbol(SymbolToken { symbol, .. }) = token else {
-      return Err(());
-    };
+  fn block(&mut self, tokens: &mut TokenProvider, statements: &mut Vec<Statement>) -> Result<(), AssembleError> {
+    loop {
+      match tokens.peek() {
+        Next::Token(Token::EndOfLine { .. }) => {
+          tokens.next();
+        }
+        Next::Token(_) if self.skipping() => self.skip_line(tokens, statements)?,
+        Next::Token(_) => self.line(tokens, statements)?,
+        Next::EndOfFile { .. } | Next::EndOfStream { .. } => break,
+      }
+    }
 
-    if !matches!(
-      symbol,
-      Symbol::RightParenthesis | Symbol::RightAngledBracket | Symbol::RightCurlyBracket | Symbol::RightSquareBracket
-    ) {
-      return Err(());
-    }
-
-    let delimiter = match symbol {
-      Symbol::LeftParenthesis => DelimiterType::Paren,
-      Symbol::LeftAngledBracket => DelimiterType::Angled,
-      Symbol::LeftCurlyBracket => DelimiterType::Curly,
-      Symbol::LeftSquareBracket => DelimiterType::Square,
-      Symbol::RightParenthesis => DelimiterType::Paren,
-      Symbol::RightAngledBracket => DelimiterType::Angled,
-      Symbol::RightCurlyBracket => DelimiterType::Curly,
-      Symbol::RightSquareBracket => DelimiterType::Square,
-      _ => return Err(()),
-    };
+    Ok(())
+  }
 
-    Ok(Self {
-      delimiter,
-      position: token.position().clone(),
-    })
+  fn statement(&self, position: Position, kind: StatementKind) -> Statement {
+    Statement {
+      position,
+      kind,
+      invocation: self.invocation.clone(),
+    }
   }
-}
 
-pub struct Parser {
-  delimiter_stack: Vec<Delimiter>,
-}
+  /// line := name "=" expression EOL
+  ///         | ".macro" name (parameter ("," parameter)*)? EOL line*
+  /// ".endmacro" EOL         | ".include" string EOL
+  ///         | (label ":")? (instruction | directive | macro_name arguments)?
+  /// EOL
+  fn line(&mut self, tokens: &mut TokenProvider, statements: &mut Vec<Statement>) -> Result<(), AssembleError> {
+    if let Next::Token(Token::Literal(LiteralToken {
+      position,
+      literal: Literal::Identifier { lexeme },
+    })) = tokens.peek().cloned()
+    {
+      if !self.macros.contains_key(&lexeme) {
+        tokens.next();
+        if self.match_symbols(tokens, Symbol::Equals.into()).is_some() {
+          let value = self.value(tokens)?;
+          self.define(&lexeme, Some(&value));
+          statements.push(self.statement(position, StatementKind::Assignment { name: lexeme, value }));
+          return self.end_of_line(tokens);
+        }
 
-impl Parser {
-  pub fn new(error_handler: Handle<ErrorHandler>) -> Self {
-    Self {
-      delimiter_stack: Default::default(),
+        if self.match_symbols(tokens, Symbol::Colon.into()).is_none() {
+          return Err(AssembleError::Syntax {
+            position,
+            message: format!("unknown instruction or macro `{lexeme}`"),
+          });
+        }
+        self.define(&lexeme, None);
+        statements.push(self.statement(position, StatementKind::Label { name: lexeme }));
+      }
+    }
+
+    match tokens.peek().cloned() {
+      Next::Token(Token::Literal(LiteralToken {
+        position,
+        literal: Literal::OpCode { lexeme: opcode },
+      })) => {
+        tokens.next();
+        let operand = self.operand(tokens)?;
+        statements.push(self.statement(position, StatementKind::Instruction { opcode, operand }));
+      }
+      Next::Token(Token::Literal(LiteralToken {
+        position,
+        literal: Literal::Identifier { lexeme },
+      })) => {
+        tokens.next();
+        return self.invoke(tokens, statements, position, lexeme);
+      }
+      Next::Token(Token::Keyword(KeywordToken {
+        position,
+        keyword: Keyword::Macro,
+      })) => {
+        tokens.next();
+        return self.define_macro(tokens, position);
+      }
+      Next::Token(Token::Keyword(KeywordToken {
+        position,
+        keyword: Keyword::Include,
+      })) => {
+        tokens.next();
+        let path = self.string(tokens)?;
+        self.end_of_line(tokens)?;
+        statements.push(self.statement(position.clone(), StatementKind::Directive(Directive::Include(path.clone()))));
+        return self.include(statements, position, &path);
+      }
+      Next::Token(Token::Keyword(KeywordToken { position, keyword })) => {
+        tokens.next();
+        let directive = self.directive(tokens, &position, keyword)?;
+        match directive.is_conditional() {
+          true => self.conditional(statements, position, directive)?,
+          false => statements.push(self.statement(position, StatementKind::Directive(directive))),
+        }
+      }
+      _ => (),
     }
+
+    self.end_of_line(tokens)
   }
 
-  pub fn parse(&mut self, tokens: &[Token]) -> SyntaxTree {
-    let mut tokens = TokenProvider::new(tokens);
-    let root = self.expression(&mut tokens);
+  /// Passes over a line in a branch that is not taken, only following the
+  /// conditionals on it so nested blocks are matched up correctly
+  fn skip_line(&mut self, tokens: &mut TokenProvider, statements: &mut Vec<Statement>) -> Result<(), AssembleError> {
+    let mut lookahead = tokens.clone();
+    if let Next::Token(Token::Literal(LiteralToken {
+      literal: Literal::Identifier { .. },
+      ..
+    })) = lookahead.next()
+    {
+      if self.match_symbols(&mut lookahead, Symbol::Colon.into()).is_some() {
+        *tokens = lookahead;
+      }
+    }
 
-    let next = tokens.peek().cloned();
-    match next {
-      Next::Token(t) => {
-        self.error_handler.get_mut().push(InterpreterError::ParseError {
-          position: t.position().clone(),
-          message: format!("Expected EOF but got `{}`", t),
-        });
-        SyntaxTree {
-          root,
-          eof: Token::EndOfFile {
-            position: Position::new(t.position().line, t.position().column + 1),
+    if let Next::Token(Token::Keyword(KeywordToken { position, keyword })) = tokens.peek().cloned() {
+      let directive = match keyword {
+        Keyword::If | Keyword::IfDef | Keyword::IfNDef => Some(Directive::IfDef(String::new())),
+        Keyword::Else => Some(Directive::Else),
+        Keyword::EndIf => Some(Directive::EndIf),
+        // only the condition of an `.elseif` that may be taken matters
+        Keyword::ElseIf if self.conditionals.enclosing() => {
+          tokens.next();
+          Some(self.directive(tokens, &position, keyword)?)
+        }
+        Keyword::ElseIf => Some(Directive::ElseIf(Expression::Literal {
+          token: LiteralToken {
+            position: position.clone(),
+            literal: Literal::Number { lexeme: 0 },
           },
+        })),
+        _ => None,
+      };
+
+      if let Some(directive) = directive {
+        self.conditional(statements, position, directive)?;
+      }
+    }
+
+    while !Self::at_end_of_line(tokens) {
+      tokens.next();
+    }
+    self.end_of_line(tokens)
+  }
+
+  /// Opens, continues or closes a conditional block, deciding it if the
+  /// condition is already known. Conditionals left to the emitter are added
+  /// to `statements`.
+  fn conditional(
+    &mut self,
+    statements: &mut Vec<Statement>,
+    position: Position,
+    directive: Directive,
+  ) -> Result<(), AssembleError> {
+    match directive {
+      Directive::If(_) | Directive::IfDef(_) | Directive::IfNDef(_) => {
+        let enclosing = !self.skipping();
+        let decision = match enclosing {
+          true => self.decide(&directive),
+          false => Some(false),
+        };
+        self
+          .conditionals
+          .open(Conditional::new(position.clone(), enclosing, decision));
+        if decision.is_none() {
+          statements.push(self.statement(position, StatementKind::Directive(directive)));
         }
       }
-      Next::EndOfFile { position } => SyntaxTree {
-        root,
-        eof: Token::EndOfFile { position },
-      },
-      Next::EndOfStream { position } => SyntaxTree {
-        root,
-        eof: Token::EndOfFile { position },
+      Directive::ElseIf(ref condition) => {
+        let conditional = self.conditionals.innermost(&position, &directive)?;
+        if conditional.deferred {
+          statements.push(self.statement(position, StatementKind::Directive(directive)));
+          return Ok(());
+        }
+        if !conditional.enclosing || conditional.taken {
+          conditional.else_if(false);
+          return Ok(());
+        }
+
+        let decision = self.decide(&directive);
+        let conditional = self.conditionals.innermost(&position, &directive)?;
+        match decision {
+          Some(active) => conditional.else_if(active),
+          None => {
+            // no branch was taken so far, so the rest of the block is
+            // equivalent to a new `.if`
+            conditional.deferred = true;
+            conditional.active = true;
+            let directive = Directive::If(condition.clone());
+            statements.push(self.statement(position, StatementKind::Directive(directive)));
+          }
+        }
+      }
+      Directive::Else => {
+        let conditional = self.conditionals.innermost(&position, &directive)?;
+        conditional.otherwise();
+        if conditional.deferred {
+          statements.push(self.statement(position, StatementKind::Directive(directive)));
+        }
+      }
+      _ => {
+        if self.conditionals.close(&position)?.deferred {
+          statements.push(self.statement(position, StatementKind::Directive(directive)));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Evaluates the condition of a conditional directive, or returns `None` if
+  /// it depends on values that are not known yet
+  fn decide(&self, directive: &Directive) -> Option<bool> {
+    let defined = |name: &str| match self.symbols.get(name) {
+      Some(Known::Maybe) => None,
+      Some(_) => Some(true),
+      None => Some(false),
+    };
+
+    match directive {
+      Directive::If(condition) | Directive::ElseIf(condition) => self.evaluate(condition).map(|value| value != 0),
+      Directive::IfDef(name) => defined(name),
+      Directive::IfNDef(name) => defined(name).map(|defined| !defined),
+      _ => None,
+    }
+  }
+
+  /// Evaluates `expression` if every symbol in it has a known value
+  fn evaluate(&self, expression: &Expression) -> Option<i64> {
+    expression
+      .evaluate(&|name, position| match self.symbols.get(name) {
+        Some(Known::Value(value)) => Ok(*value),
+        _ => Err(AssembleError::UndefinedLabel {
+          position: position.clone(),
+          name: name.into(),
+        }),
+      })
+      .ok()
+  }
+
+  /// Records that `name` is defined, with the value of the constant it is
+  /// assigned if that is known
+  fn define(&mut self, name: &str, value: Option<&Expression>) {
+    let known = match self.conditionals.deferred() {
+      true => Known::Maybe,
+      false => match value.and_then(|value| self.evaluate(value)) {
+        Some(value) => Known::Value(value),
+        None => Known::Defined,
       },
+    };
+    self.symbols.insert(name.into(), known);
+  }
+
+  /// Whether the current branch of a conditional is not taken
+  fn skipping(&self) -> bool {
+    !self.conditionals.active()
+  }
+
+  fn define_macro(&mut self, tokens: &mut TokenProvider, position: Position) -> Result<(), AssembleError> {
+    let name = self.identifier(tokens, "macro name")?;
+    let mut parameters = Vec::new();
+    if !Self::at_end_of_line(tokens) {
+      parameters.push(self.identifier(tokens, "parameter name")?);
+      while self.match_symbols(tokens, Symbol::Comma.into()).is_some() {
+        parameters.push(self.identifier(tokens, "parameter name")?);
+      }
     }
+    self.end_of_line(tokens)?;
+
+    let mut body = Vec::new();
+    let mut line_start = true;
+    loop {
+      match tokens.next().cloned() {
+        Next::Token(Token::Keyword(KeywordToken {
+          keyword: Keyword::EndMacro,
+          ..
+        }))
+          if line_start =>
+        {
+          break
+        }
+        Next::Token(Token::Keyword(KeywordToken {
+          position,
+          keyword: Keyword::Macro,
+        })) => {
+          return Err(AssembleError::Syntax {
+            position,
+            message: "macros cannot be defined inside other macros".into(),
+          })
+        }
+        Next::Token(token) => {
+          line_start = matches!(token, Token::EndOfLine { .. });
+          body.push(token);
+        }
+        Next::EndOfFile { .. } | Next::EndOfStream { .. } => {
+          return Err(AssembleError::Syntax {
+            position,
+            message: format!("macro `{name}` is missing `.endmacro`"),
+          })
+        }
+      }
+    }
+
+    if let Some(previous) = self.macros.get(&name) {
+      return Err(AssembleError::DuplicateLabel {
+        position,
+        name,
+        previous: previous.position.clone(),
+      });
+    }
+
+    self.macros.insert(name.clone(), Macro {
+      name,
+      parameters,
+      body,
+      position,
+    });
+
+    self.end_of_line(tokens)
   }
 
-  // TODO: fix angled bracket false positive for 1 > 3
-  fn is_rogue_delimiter(&mut self, token: &Next<&Token>) -> bool {
-    let Next::Token(token) = token.cloned() else {
-      return false;
+  fn invoke(
+    &mut self,
+    tokens: &mut TokenProvider,
+    statements: &mut Vec<Statement>,
+    position: Position,
+    name: String,
+  ) -> Result<(), AssembleError> {
+    let Some(definition) = self.macros.get(&name).cloned() else {
+      return Err(AssembleError::Syntax {
+        position,
+        message: format!("unknown instruction or macro `{name}`"),
+      });
     };
 
-    let Ok(delim) = token.try_into() else {
-      return false;
+    let arguments = self.arguments(tokens)?;
+    self.end_of_line(tokens)?;
+
+    if arguments.len() != definition.parameters.len() {
+      return Err(AssembleError::Syntax {
+        position,
+        message: format!(
+          "macro `{name}` expects {} arguments but got {}",
+          definition.parameters.len(),
+          arguments.len()
+        ),
+      });
+    }
+
+    // conditions that depend on labels are only evaluated after expansion, so
+    // a macro that invokes itself might never stop expanding
+    if self
+      .invocation
+      .as_ref()
+      .is_some_and(|invocation| invocation.contains(&name))
+    {
+      return Err(AssembleError::Syntax {
+        position,
+        message: format!("macro `{name}` invokes itself"),
+      });
+    }
+
+    let invocation = Invocation {
+      name: name.clone(),
+      position: position.clone(),
+      parent: self.invocation.clone(),
     };
 
-    !self.delimiter_stack.ends_with(&[delim])
+    self.expansions += 1;
+    let expanded = definition.expand(&arguments, self.expansions);
+
+    let parent = self.invocation.replace(Rc::new(invocation));
+    let result = self.block(&mut TokenProvider::new(&expanded), statements);
+    self.invocation = parent;
+
+    result.map_err(|error| AssembleError::Macro {
+      name,
+      position,
+      error: Box::new(error),
+    })
   }
 
-  fn match_token_types(&mut self, tokens: &mut TokenProvider, types: BitFlags<TokenDiscriminants>) -> Option<Token> {
-    let mut peeked = tokens.peek();
-    // check for rogue delimiters
-    if self.is_rogue_delimiter(&peeked) {
-      let Next::Token(Token::Symbol(SymbolToken { position, symbol })) = &peeked else {
-        return None;
-      };
+  /// Parses the statements of another source file in place of the
+  /// `.include` line
+  fn include(&mut self, statements: &mut Vec<Statement>, position: Position, path: &str) -> Result<(), AssembleError> {
+    let file = self.resolve(&position, path)?;
+    let canonical = fs::canonicalize(&file).map_err(|error| AssembleError::Read {
+      position: position.clone(),
+      path: path.into(),
+      error,
+    })?;
+    if self.includes.contains(&canonical) {
+      return Err(AssembleError::IncludeCycle {
+        position,
+        path: path.into(),
+      });
+    }
+
+    let source = fs::read_to_string(&file).map_err(|error| AssembleError::Read {
+      position: position.clone(),
+      path: path.into(),
+      error,
+    })?;
+    let file: Arc<Path> = Arc::from(file);
+    let expanded = Lexer::new().lex(&source, Some(file.clone()))?;
+    self.sources.insert(file, source);
 
-      self.error(InterpreterError::UnmatchedDelimiter {
+    self.includes.push(canonical);
+    let result = self.block(&mut TokenProvider::new(&expanded), statements);
+    self.includes.pop();
+
+    result
+  }
+
+  /// Finds `path` relative to the file containing `position`, then in each of
+  /// the include paths in the order they were added
+  fn resolve(&self, position: &Position, path: &str) -> Result<PathBuf, AssembleError> {
+    let relative = Path::new(path);
+    let directory = position
+      .file
+      .as_ref()
+      .and_then(|file| file.parent())
+      .unwrap_or(Path::new(""));
+
+    std::iter::once(directory.join(relative))
+      .chain(self.include_paths.iter().map(|directory| directory.join(relative)))
+      .find(|candidate| candidate.is_file())
+      .ok_or_else(|| AssembleError::FileNotFound {
         position: position.clone(),
-        delimiter: symbol.lexeme(),
-      });
+        path: path.into(),
+      })
+  }
+
+  /// Splits the rest of the line into comma separated token lists, ignoring
+  /// commas inside parentheses
+  fn arguments(&mut self, tokens: &mut TokenProvider) -> Result<Vec<Vec<Token>>, AssembleError> {
+    let mut arguments = Vec::new();
+    if Self::at_end_of_line(tokens) {
+      return Ok(arguments);
+    }
+
+    let mut argument = Vec::new();
+    let mut depth = 0;
+    while !Self::at_end_of_line(tokens) {
+      let Next::Token(token) = tokens.next().cloned() else {
+        break;
+      };
+
+      match &token {
+        Token::Symbol(SymbolToken {
+          symbol: Symbol::Comma,
+          position,
+        }) if depth == 0 => {
+          if argument.is_empty() {
+            return Err(AssembleError::Syntax {
+              position: position.clone(),
+              message: "expected macro argument before `,`".into(),
+            });
+          }
+          arguments.push(std::mem::take(&mut argument));
+          continue;
+        }
+        Token::Symbol(SymbolToken {
+          symbol: Symbol::LeftParenthesis,
+          ..
+        }) => depth += 1,
+        Token::Symbol(SymbolToken {
+          symbol: Symbol::RightParenthesis,
+          ..
+        }) => depth -= 1,
+        _ => (),
+      }
+
+      argument.push(token);
+    }
+
+    if argument.is_empty() {
+      let next = tokens.peek().cloned();
+      return Err(Self::expected(tokens, next, "macro argument"));
+    }
+    arguments.push(argument);
+
+    Ok(arguments)
+  }
 
-      tokens.next(); // consume the delimiter
-      peeked = tokens.peek();
+  fn identifier(&mut self, tokens: &mut TokenProvider, expected: &str) -> Result<String, AssembleError> {
+    match tokens.next().cloned() {
+      Next::Token(Token::Literal(LiteralToken {
+        literal: Literal::Identifier { lexeme },
+        ..
+      })) => Ok(lexeme),
+      next => Err(Self::expected(tokens, next, expected)),
     }
+  }
 
-    // check if no tokens
-    let Next::Token(peeked) = peeked else {
-      return None;
+  fn directive(
+    &mut self,
+    tokens: &mut TokenProvider,
+    position: &Position,
+    keyword: Keyword,
+  ) -> Result<Directive, AssembleError> {
+    let directive = match keyword {
+      Keyword::Org => Directive::Org(self.value(tokens)?),
+      Keyword::Byte => Directive::Byte(self.value_list(tokens)?),
+      Keyword::Word => Directive::Word(self.value_list(tokens)?),
+      Keyword::Ascii => Directive::Ascii(self.string(tokens)?),
+      Keyword::Asciiz => Directive::Asciiz(self.string(tokens)?),
+      Keyword::Res => Directive::Res {
+        count: self.value(tokens)?,
+        value: self.optional_value(tokens)?,
+      },
+      Keyword::Align => Directive::Align {
+        alignment: self.value(tokens)?,
+        value: self.optional_value(tokens)?,
+      },
+      Keyword::Fill => Directive::Fill {
+        count: self.value(tokens)?,
+        value: self.optional_value(tokens)?,
+      },
+      Keyword::If => Directive::If(self.value(tokens)?),
+      Keyword::ElseIf => Directive::ElseIf(self.value(tokens)?),
+      Keyword::Else => Directive::Else,
+      Keyword::EndIf => Directive::EndIf,
+      Keyword::IfDef => Directive::IfDef(self.identifier(tokens, "symbol name")?),
+      Keyword::IfNDef => Directive::IfNDef(self.identifier(tokens, "symbol name")?),
+      Keyword::IncBin => {
+        let path = self.string(tokens)?;
+        let file = self.resolve(position, &path)?;
+        let data = fs::read(file).map_err(|error| AssembleError::Read {
+          position: position.clone(),
+          path,
+          error,
+        })?;
+
+        let offset = self.optional_value(tokens)?;
+        let length = match offset {
+          Some(_) => self.optional_value(tokens)?,
+          None => None,
+        };
+        Directive::IncBin { data, offset, length }
+      }
+      Keyword::Macro | Keyword::EndMacro | Keyword::Include => {
+        return Err(AssembleError::Syntax {
+          position: position.clone(),
+          message: format!("unexpected `{}`", keyword.lexeme()),
+        })
+      }
     };
 
-    // check if matches params
-    let discrm = TokenDiscriminants::from(peeked);
-    if types.contains(discrm) {
-      Some(peeked.clone())
-    } else {
-      None
+    Ok(directive)
+  }
+
+  fn string(&mut self, tokens: &mut TokenProvider) -> Result<String, AssembleError> {
+    match tokens.next().cloned() {
+      Next::Token(Token::Literal(LiteralToken {
+        literal: Literal::String { lexeme },
+        ..
+      })) => Ok(lexeme),
+      next => Err(Self::expected(tokens, next, "string")),
+    }
+  }
+
+  /// Parses `, value` if present
+  fn optional_value(&mut self, tokens: &mut TokenProvider) -> Result<Option<Expression>, AssembleError> {
+    match self.match_symbols(tokens, Symbol::Comma.into()) {
+      Some(_) => Ok(Some(self.value(tokens)?)),
+      None => Ok(None),
+    }
+  }
+
+  fn operand(&mut self, tokens: &mut TokenProvider) -> Result<Operand, AssembleError> {
+    if Self::at_end_of_line(tokens) {
+      return Ok(Operand::None);
+    }
+
+    if let Next::Token(Token::Literal(LiteralToken {
+      literal: Literal::Identifier { lexeme },
+      ..
+    })) = tokens.peek()
+    {
+      if lexeme.eq_ignore_ascii_case("a") {
+        tokens.next();
+        return Ok(Operand::Accumulator);
+      }
+    }
+
+    if self.match_symbols(tokens, Symbol::Hashtag.into()).is_some() {
+      return Ok(Operand::Immediate(self.value(tokens)?));
+    }
+
+    if self.is_indirect(tokens) {
+      self.consume_symbol(tokens, Symbol::LeftParenthesis, "`(`")?;
+      let value = self.value(tokens)?;
+      if self.match_symbols(tokens, Symbol::Comma.into()).is_some() {
+        self.consume_register(tokens, "x")?;
+        self.consume_symbol(tokens, Symbol::RightParenthesis, "`)`")?;
+        return Ok(Operand::IndirectX(value));
+      }
+
+      self.consume_symbol(tokens, Symbol::RightParenthesis, "`)`")?;
+      if self.match_symbols(tokens, Symbol::Comma.into()).is_some() {
+        self.consume_register(tokens, "y")?;
+        return Ok(Operand::IndirectY(value));
+      }
+
+      return Ok(Operand::Indirect(value));
+    }
+
+    let value = self.value(tokens)?;
+    if self.match_symbols(tokens, Symbol::Comma.into()).is_some() {
+      return match self.register(tokens)?.as_str() {
+        "x" => Ok(Operand::DirectX(value)),
+        _ => Ok(Operand::DirectY(value)),
+      };
+    }
+
+    Ok(Operand::Direct(value))
+  }
+
+  /// Whether an operand starting with `(` is indirect rather than a
+  /// parenthesized expression, i.e. whether the matching `)` is followed by
+  /// `,` or the end of the line, or a `,` comes before it
+  fn is_indirect(&self, tokens: &TokenProvider) -> bool {
+    let mut lookahead = tokens.clone();
+    if !matches!(
+      lookahead.next(),
+      Next::Token(Token::Symbol(SymbolToken {
+        symbol: Symbol::LeftParenthesis,
+        ..
+      }))
+    ) {
+      return false;
+    }
+
+    let mut depth = 1;
+    loop {
+      match lookahead.next() {
+        Next::Token(Token::Symbol(SymbolToken { symbol, .. })) => match symbol {
+          Symbol::LeftParenthesis => depth += 1,
+          Symbol::RightParenthesis => {
+            depth -= 1;
+            if depth == 0 {
+              return matches!(
+                lookahead.peek(),
+                Next::Token(Token::EndOfLine { .. })
+                  | Next::Token(Token::Symbol(SymbolToken {
+                    symbol: Symbol::Comma,
+                    ..
+                  }))
+                  | Next::EndOfFile { .. }
+                  | Next::EndOfStream { .. }
+              );
+            }
+          }
+          Symbol::Comma if depth == 1 => return true,
+          _ => (),
+        },
+        Next::Token(Token::EndOfLine { .. }) | Next::EndOfFile { .. } | Next::EndOfStream { .. } => return false,
+        Next::Token(_) => (),
+      }
+    }
+  }
+
+  /// Parses an index register, returning it in lower case
+  fn register(&mut self, tokens: &mut TokenProvider) -> Result<String, AssembleError> {
+    match tokens.next().cloned() {
+      Next::Token(Token::Literal(LiteralToken {
+        literal: Literal::Identifier { lexeme },
+        ..
+      }))
+        if lexeme.eq_ignore_ascii_case("x") || lexeme.eq_ignore_ascii_case("y") =>
+      {
+        Ok(lexeme.to_ascii_lowercase())
+      }
+      next => Err(Self::expected(tokens, next, "`x` or `y`")),
+    }
+  }
+
+  fn consume_register(&mut self, tokens: &mut TokenProvider, register: &str) -> Result<(), AssembleError> {
+    match tokens.next().cloned() {
+      Next::Token(Token::Literal(LiteralToken {
+        literal: Literal::Identifier { lexeme },
+        ..
+      }))
+        if lexeme.eq_ignore_ascii_case(register) =>
+      {
+        Ok(())
+      }
+      next => Err(Self::expected(tokens, next, &format!("`{register}`"))),
+    }
+  }
+
+  fn value_list(&mut self, tokens: &mut TokenProvider) -> Result<Vec<Expression>, AssembleError> {
+    let mut values = vec![self.value(tokens)?];
+    while self.match_symbols(tokens, Symbol::Comma.into()).is_some() {
+      values.push(self.value(tokens)?);
     }
+    Ok(values)
+  }
+
+  /// value := ("<" | ">")? expression
+  ///
+  /// `<` and `>` select the low and high byte of everything that follows them
+  fn value(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
+    if let Some(operator) = self.match_symbols(tokens, Symbol::LeftAngledBracket | Symbol::RightAngledBracket) {
+      let operand = Box::new(self.expression(tokens)?);
+      return Ok(Expression::Unary { operator, operand });
+    }
+
+    self.expression(tokens)
   }
 
   fn match_symbols(&mut self, tokens: &mut TokenProvider, types: BitFlags<Symbol>) -> Option<SymbolToken> {
-    if let Some(Token::Symbol(symbol_token)) = self.match_token_types(tokens, TokenDiscriminants::Symbol.into()) {
+    if let Next::Token(Token::Symbol(symbol_token)) = tokens.peek() {
       if types.contains(symbol_token.symbol) {
-        if let Next::Token(Token::Symbol(symbol_token)) = tokens.next() {
-          return Some(symbol_token.clone());
-        }
+        let symbol_token = symbol_token.clone();
+        tokens.next();
+        return Some(symbol_token);
       }
     }
 
     None
   }
 
-  fn expression(&mut self, tokens: &mut TokenProvider) -> Expression {
+  fn consume_symbol(
+    &mut self,
+    tokens: &mut TokenProvider,
+    symbol: Symbol,
+    expected: &str,
+  ) -> Result<(), AssembleError> {
+    match self.match_symbols(tokens, symbol.into()) {
+      Some(_) => Ok(()),
+      None => {
+        let next = tokens.peek().cloned();
+        Err(Self::expected(tokens, next, expected))
+      }
+    }
+  }
+
+  fn at_end_of_line(tokens: &mut TokenProvider) -> bool {
+    matches!(
+      tokens.peek(),
+      Next::Token(Token::EndOfLine { .. }) | Next::EndOfFile { .. } | Next::EndOfStream { .. }
+    )
+  }
+
+  fn end_of_line(&mut self, tokens: &mut TokenProvider) -> Result<(), AssembleError> {
+    match tokens.peek().cloned() {
+      Next::Token(Token::EndOfLine { .. }) => {
+        tokens.next();
+        Ok(())
+      }
+      Next::EndOfFile { .. } | Next::EndOfStream { .. } => Ok(()),
+      next => Err(Self::expected(tokens, next, "end of line")),
+    }
+  }
+
+  fn expected(tokens: &TokenProvider, next: Next<Token>, expected: &str) -> AssembleError {
+    match next {
+      Next::Token(token) => AssembleError::Syntax {
+        position: token.position().clone(),
+        message: format!("expected {expected} but got `{token}`"),
+      },
+      Next::EndOfFile { .. } | Next::EndOfStream { .. } => {
+        let prev = tokens.previous_valid();
+        AssembleError::Syntax {
+          position: Position {
+            column: prev.position().column + prev.lexeme().len() as u32,
+            ..prev.position().clone()
+          },
+          message: format!("expected {expected} after `{prev}`"),
+        }
+      }
+    }
+  }
+
+  fn expression(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
     self.or(tokens)
   }
 
-  fn or(&mut self, tokens: &mut TokenProvider) -> Expression {
-    let mut expression = self.and(tokens);
+  fn or(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
+    let mut expression = self.and(tokens)?;
 
     while let Some(operator) = self.match_symbols(tokens, Symbol::DoublePipe.into()) {
-      let right_operand = Box::new(self.and(tokens));
+      let right_operand = Box::new(self.and(tokens)?);
       expression = Expression::Binary {
         left_operand: Box::new(expression),
         operator,
@@ -188,14 +890,14 @@ impl Parser {
       };
     }
 
-    expression
+    Ok(expression)
   }
 
-  fn and(&mut self, tokens: &mut TokenProvider) -> Expression {
-    let mut expression = self.comparison(tokens);
+  fn and(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
+    let mut expression = self.comparison(tokens)?;
 
     while let Some(operator) = self.match_symbols(tokens, Symbol::DoubleAmpersand.into()) {
-      let right_operand = Box::new(self.comparison(tokens));
+      let right_operand = Box::new(self.comparison(tokens)?);
       expression = Expression::Binary {
         left_operand: Box::new(expression),
         operator,
@@ -203,11 +905,11 @@ impl Parser {
       };
     }
 
-    expression
+    Ok(expression)
   }
 
-  fn comparison(&mut self, tokens: &mut TokenProvider) -> Expression {
-    let mut expression = self.term(tokens);
+  fn comparison(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
+    let mut expression = self.term(tokens)?;
 
     while let Some(operator) = self.match_symbols(
       tokens,
@@ -218,7 +920,7 @@ impl Parser {
         | Symbol::LeftAngledBracketEquals
         | Symbol::RightAngledBracketEquals,
     ) {
-      let right_operand = Box::new(self.term(tokens));
+      let right_operand = Box::new(self.term(tokens)?);
       expression = Expression::Binary {
         left_operand: Box::new(expression),
         operator,
@@ -226,14 +928,14 @@ impl Parser {
       };
     }
 
-    expression
+    Ok(expression)
   }
 
-  fn term(&mut self, tokens: &mut TokenProvider) -> Expression {
-    let mut expression = self.factor(tokens);
+  fn term(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
+    let mut expression = self.factor(tokens)?;
 
     while let Some(operator) = self.match_symbols(tokens, Symbol::Plus | Symbol::Minus) {
-      let right_operand = Box::new(self.factor(tokens));
+      let right_operand = Box::new(self.factor(tokens)?);
       expression = Expression::Binary {
         left_operand: Box::new(expression),
         operator,
@@ -241,14 +943,14 @@ impl Parser {
       };
     }
 
-    expression
+    Ok(expression)
   }
 
-  fn factor(&mut self, tokens: &mut TokenProvider) -> Expression {
-    let mut expression = self.unary(tokens);
+  fn factor(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
+    let mut expression = self.unary(tokens)?;
 
     while let Some(operator) = self.match_symbols(tokens, Symbol::Asterisk | Symbol::ForwardSlash) {
-      let right_operand = Box::new(self.unary(tokens));
+      let right_operand = Box::new(self.unary(tokens)?);
       expression = Expression::Binary {
         left_operand: Box::new(expression),
         operator,
@@ -256,195 +958,36 @@ impl Parser {
       };
     }
 
-    expression
+    Ok(expression)
   }
 
-  // fn power(&mut self, tokens: &mut TokenProvider) -> Expression {
-  //   let mut expression = self.unary(tokens);
-
-  //   while let Some(operator) = self.match_symbols(tokens, Symbol::Caret.into())
-  // {     let right_operand = Box::new(self.unary(tokens));
-  //     expression = Expression::Binary {
-  //       left_operand: Box::new(expression),
-  //       operator,
-  //       right_operand,
-  //     };
-  //   }
-
-  //   expression
-  // }
-
-  fn unary(&mut self, tokens: &mut TokenProvider) -> Expression {
+  fn unary(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
     if let Some(operator) = self.match_symbols(tokens, Symbol::ExclamationPoint | Symbol::Minus) {
-      let operand = Box::new(self.unary(tokens));
-      return Expression::Unary { operator, operand };
+      let operand = Box::new(self.unary(tokens)?);
+      return Ok(Expression::Unary { operator, operand });
     }
 
     self.primary(tokens)
   }
 
-  fn primary(&mut self, tokens: &mut TokenProvider) -> Expression {
+  fn primary(&mut self, tokens: &mut TokenProvider) -> Result<Expression, AssembleError> {
     let next_token = tokens.next().cloned();
     match next_token {
-      Next::Token(token) => {
-        match &token {
-          Token::Literal(token) => return Expression::Literal { token: token.clone() },
-          Token::Symbol(SymbolToken { position, symbol }) => match symbol {
-            Symbol::LeftParenthesis => {
-              self.delimiter_stack.push(Delimiter {
-                delimiter: DelimiterType::Paren,
-                position: position.clone(),
-              });
-              let operand = Box::new(self.expression(tokens));
-              let _delimiter = self.pair_delimiter(tokens, Symbol::RightParenthesis);
-              return Expression::Grouping { operand };
-            }
-            Symbol::LeftCurlyBracket => {
-              self.delimiter_stack.push(Delimiter {
-                delimiter: DelimiterType::Curly,
-                position: position.clone(),
-              });
-              let operand = Box::new(self.expression(tokens));
-              let _delimiter = self.pair_delimiter(tokens, Symbol::RightCurlyBracket);
-              self.delimiter_stack.pop();
-              return Expression::Grouping { operand };
-            }
-            _ => {}
-          },
-          _ => {}
-        }
-
-        self.error(InterpreterError::ParseError {
-          position: token.position().clone(),
-          message: format!("Expected expression but got `{}`", tokens.previous_valid()),
-        });
-
-        Expression::Literal {
-          token: LiteralToken {
-            position: token.position().clone(),
-            literal: Literal::Void,
-          },
-        }
-      }
-      Next::EndOfFile { position } => {
-        let prev = tokens.previous_valid();
-        self.error(InterpreterError::ParseError {
-          position: Position::new(prev.position().line, prev.position().column + prev.lexeme().len() as u32),
-          message: format!("Expected expression after `{}`", prev),
-        });
-
-        Expression::Literal {
-          token: LiteralToken {
-            position,
-            literal: Literal::Void,
-          },
-        }
-      }
-      Next::EndOfStream { position } => {
-        let prev = tokens.previous_valid();
-        self.error(InterpreterError::ParseError {
-          position: Position::new(prev.position().line, prev.position().column + prev.lexeme().len() as u32),
-          message: format!("Expected expression after `{}`", prev),
-        });
-
-        Expression::Literal {
-          token: LiteralToken {
-            position,
-            literal: Literal::Void,
-          },
-        }
-      }
-    }
-  }
-
-  fn pair_delimiter(&mut self, tokens: &mut TokenProvider, delimiter: Symbol) -> Token {
-    let unmatched = self.delimiter_stack.pop().expect("expected Some(delimiter)");
-    match self.check_delimiter(tokens, &delimiter) {
-      Ok(matches) => match tokens.next().cloned() {
-        Next::Token(token) => {
-          if matches {
-            token
-          } else {
-            let delimiter = Token::Symbol(SymbolToken {
-              position: token.position().clone(),
-              symbol: delimiter,
-            });
-            self.error(InterpreterError::UnmatchedDelimiter {
-              position: unmatched.position().clone(),
-              delimiter: unmatched.lexeme(),
-            });
-            delimiter
-          }
-        }
-        Next::EndOfFile { position } | Next::EndOfStream { position } => {
-          let delimiter = Token::Symbol(SymbolToken {
-            position,
-            symbol: delimiter,
-          });
-          self.error(InterpreterError::UnmatchedDelimiter {
-            position: unmatched.position().clone(),
-            delimiter: unmatched.lexeme(),
-          });
-          delimiter
-        }
-      },
-      Err(position) => {
-        let delimiter = Token::Symbol(SymbolToken {
-          position,
-          symbol: delimiter,
-        });
-        self.error(InterpreterError::UnmatchedDelimiter {
-          position: unmatched.position().clone(),
-          delimiter: unmatched.lexeme(),
-        });
-        delimiter
-      }
-    }
-  }
-
-  fn check_delimiter(&mut self, tokens: &mut TokenProvider, delimiter: &Symbol) -> Result<bool, Position> {
-    match tokens.peek() {
-      Next::Token(Token::Symbol(symbol_token)) => Ok(symbol_token.symbol == *delimiter),
-      Next::Token(token) => Err(token.position().to_owned()),
-      Next::EndOfFile { position } | Next::EndOfStream { position } => Err(position),
-    }
-  }
-
-  fn error(&mut self, error: InterpreterError) {
-    self.error_handler.get_mut().push(error.clone());
-  }
-
-  #[allow(unused)]
-  fn synchronize(&mut self, tokens: &mut TokenProvider) {
-    let mut token = tokens.next();
-    while let Next::Token(next_token) = token {
-      if matches!(
-        next_token,
-        Token::Symbol(SymbolToken {
-          symbol: Symbol::Semicolon,
-          ..
-        })
-      ) {
-        return;
-      }
-
-      if matches!(
-        next_token,
-        Token::Keyword(KeywordToken {
-          keyword: Keyword::If
-            | Keyword::For
-            | Keyword::While
-            | Keyword::Loop
-            | Keyword::Return
-            | Keyword::Type
-            | Keyword::Impl,
+      Next::Token(Token::Literal(
+        token @ LiteralToken {
+          literal: Literal::Number { .. } | Literal::Identifier { .. },
           ..
-        })
-      ) {
-        return;
+        },
+      )) => Ok(Expression::Literal { token }),
+      Next::Token(Token::Symbol(SymbolToken {
+        symbol: Symbol::LeftParenthesis,
+        ..
+      })) => {
+        let operand = Box::new(self.expression(tokens)?);
+        self.consume_symbol(tokens, Symbol::RightParenthesis, "`)`")?;
+        Ok(Expression::Grouping { operand })
       }
-
-      token = tokens.next();
+      next => Err(Self::expected(tokens, next, "expression")),
     }
   }
 }
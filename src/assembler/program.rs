@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+use crate::error::OutputError;
+
+/// Data bytes per Intel HEX and S-record line
+const BYTES_PER_RECORD: usize = 16;
+
+/// Output of the assembler: the bytes that were emitted, grouped into
+/// contiguous segments. Addresses between segments are holes which are only
+/// filled in when flattening the program into an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+  segments: Vec<Segment>,
+  fill: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+  pub origin: u16,
+  pub data: Vec<u8>,
+}
+
+impl Program {
+  pub fn new(fill: u8) -> Self {
+    Self {
+      segments: Vec::new(),
+      fill,
+    }
+  }
+
+  pub fn segments(&self) -> &[Segment] {
+    &self.segments
+  }
+
+  /// Byte that holes are filled with
+  pub fn fill(&self) -> u8 {
+    self.fill
+  }
+
+  /// Flattens the program into a 64 KiB image indexed by address, with holes
+  /// filled by [`Program::fill`]
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut image = vec![self.fill; 0x10000];
+    for segment in &self.segments {
+      let start = segment.origin as usize;
+      image[start..start + segment.data.len()].copy_from_slice(&segment.data);
+    }
+    image
+  }
+
+  /// Flattens the program into an image of `size` bytes starting at `base`,
+  /// e.g. `to_binary(0x8000, 0x8000)` for a 32 KiB ROM at `$8000-$FFFF`.
+  /// Holes are filled by [`Program::fill`]. Fails if anything was written
+  /// outside the image.
+  pub fn to_binary(&self, base: u16, size: usize) -> Result<Vec<u8>, OutputError> {
+    let end = base as usize + size;
+    if size == 0 || end > 0x10000 {
+      return Err(OutputError::InvalidImage { base, size });
+    }
+
+    for segment in &self.segments {
+      let start = segment.origin as usize;
+      let last = start + segment.data.len() - 1;
+      if start < base as usize || last >= end {
+        let address = if start < base as usize { start } else { start.max(end) };
+        return Err(OutputError::OutsideImage {
+          address: address as u16,
+          start: base,
+          end: (end - 1) as u16,
+        });
+      }
+    }
+
+    Ok(self.to_bytes()[base as usize..end].to_vec())
+  }
+
+  /// Formats the written regions as Intel HEX data records followed by an end
+  /// of file record
+  pub fn to_intel_hex(&self) -> String {
+    let mut hex = String::new();
+    for (address, data) in self.records() {
+      let mut record = vec![data.len() as u8];
+      record.extend_from_slice(&address.to_be_bytes());
+      record.push(0x00);
+      record.extend_from_slice(data);
+      let checksum = record
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        .wrapping_neg();
+      record.push(checksum);
+
+      hex.push(':');
+      push_hex(&mut hex, &record);
+    }
+    hex.push_str(":00000001FF\n");
+
+    hex
+  }
+
+  /// Formats the written regions as Motorola S-records: a header, one `S1`
+  /// record per line of data, an `S5` count and an `S9` terminator
+  pub fn to_srec(&self) -> String {
+    let mut srec = String::new();
+    let mut records = 0u16;
+    let mut push = |kind: &str, address: u16, data: &[u8]| {
+      let mut record = vec![data.len() as u8 + 3];
+      record.extend_from_slice(&address.to_be_bytes());
+      record.extend_from_slice(data);
+      let checksum = !record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+      record.push(checksum);
+
+      srec.push_str(kind);
+      push_hex(&mut srec, &record);
+    };
+
+    push("S0", 0x0000, b"HDR");
+    for (address, data) in self.records() {
+      push("S1", address, data);
+      records = records.wrapping_add(1);
+    }
+    push("S5", records, &[]);
+    push("S9", 0x0000, &[]);
+
+    srec
+  }
+
+  /// Splits the segments into chunks of at most [`BYTES_PER_RECORD`] bytes
+  fn records(&self) -> impl Iterator<Item = (u16, &[u8])> {
+    self.segments.iter().flat_map(|segment| {
+      segment
+        .data
+        .chunks(BYTES_PER_RECORD)
+        .enumerate()
+        .map(|(index, data)| (segment.origin + (index * BYTES_PER_RECORD) as u16, data))
+    })
+  }
+
+  pub(crate) fn push(&mut self, address: u16, byte: u8) {
+    match self.segments.last_mut() {
+      Some(segment) if segment.origin as usize + segment.data.len() == address as usize => segment.data.push(byte),
+      _ => self.segments.push(Segment {
+        origin: address,
+        data: vec![byte],
+      }),
+    }
+  }
+}
+
+/// Appends `bytes` as upper case hex digits and ends the line
+fn push_hex(line: &mut String, bytes: &[u8]) {
+  for byte in bytes {
+    write!(line, "{byte:02X}").unwrap();
+  }
+  line.push('\n');
+}
@@ -0,0 +1,155 @@
+use std::rc::Rc;
+
+use super::{expression::Expression, macros::Invocation, token::Position};
+use crate::{core::instruction::OpCode, error::AssembleError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+  pub position: Position,
+  pub kind: StatementKind,
+  /// Macro expansion that produced the statement, if any
+  pub invocation: Option<Rc<Invocation>>,
+}
+
+impl Statement {
+  /// Position of the source line the statement belongs to, which for macro
+  /// expansions is the line of the outermost invocation
+  pub fn origin(&self) -> &Position {
+    match &self.invocation {
+      Some(invocation) => invocation.origin(),
+      None => &self.position,
+    }
+  }
+
+  /// Adds the macro invocations that produced the statement to `error`
+  pub fn annotate(&self, error: AssembleError) -> AssembleError {
+    match &self.invocation {
+      Some(invocation) => invocation.annotate(error),
+      None => error,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatementKind {
+  Label {
+    name: String,
+  },
+  /// `NAME = value` defines a constant
+  Assignment {
+    name: String,
+    value: Expression,
+  },
+  Instruction {
+    opcode: OpCode,
+    operand: Operand,
+  },
+  Directive(Directive),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Directive {
+  /// `.org address` moves the location counter
+  Org(Expression),
+  /// `.byte value, ...`
+  Byte(Vec<Expression>),
+  /// `.word value, ...` emits little endian words
+  Word(Vec<Expression>),
+  /// `.ascii "text"`
+  Ascii(String),
+  /// `.asciiz "text"` emits the text followed by a `0x00` terminator
+  Asciiz(String),
+  /// `.res count[, value]` skips `count` bytes, leaving a hole unless a value
+  /// is given
+  Res {
+    count: Expression,
+    value: Option<Expression>,
+  },
+  /// `.align alignment[, value]` skips to the next multiple of `alignment`,
+  /// leaving a hole unless a value is given
+  Align {
+    alignment: Expression,
+    value: Option<Expression>,
+  },
+  /// `.fill count[, value]` emits `count` copies of `value`, or of the
+  /// assembler's fill byte
+  Fill {
+    count: Expression,
+    value: Option<Expression>,
+  },
+  /// `.include "file"`, whose statements follow it
+  Include(String),
+  /// `.incbin "file"[, offset[, length]]` emits the contents of a binary file
+  IncBin {
+    data: Vec<u8>,
+    offset: Option<Expression>,
+    length: Option<Expression>,
+  },
+  /// `.if condition` assembles the following lines if `condition` is not zero
+  If(Expression),
+  /// `.elseif condition`
+  ElseIf(Expression),
+  /// `.else`
+  Else,
+  /// `.endif`
+  EndIf,
+  /// `.ifdef name` assembles the following lines if `name` is defined
+  IfDef(String),
+  /// `.ifndef name` assembles the following lines if `name` is not defined
+  IfNDef(String),
+}
+
+impl Directive {
+  /// Whether the directive opens, continues or closes a conditional block
+  pub fn is_conditional(&self) -> bool {
+    matches!(
+      self,
+      Directive::If(_)
+        | Directive::ElseIf(_)
+        | Directive::Else
+        | Directive::EndIf
+        | Directive::IfDef(_)
+        | Directive::IfNDef(_)
+    )
+  }
+}
+
+/// Operand syntax as written. The addressing mode is only chosen once the
+/// value is known, e.g. `$10,x` becomes `ZeroPageX` but `$1000,x` becomes
+/// `AbsoluteX`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+  /// No operand, e.g. `rts` or `asl`
+  None,
+  /// `a`
+  Accumulator,
+  /// `#value`
+  Immediate(Expression),
+  /// `value`
+  Direct(Expression),
+  /// `value,x`
+  DirectX(Expression),
+  /// `value,y`
+  DirectY(Expression),
+  /// `(value)`
+  Indirect(Expression),
+  /// `(value,x)`
+  IndirectX(Expression),
+  /// `(value),y`
+  IndirectY(Expression),
+}
+
+impl Operand {
+  pub fn expression(&self) -> Option<&Expression> {
+    match self {
+      Operand::None | Operand::Accumulator => None,
+      Operand::Immediate(expression)
+      | Operand::Direct(expression)
+      | Operand::DirectX(expression)
+      | Operand::DirectY(expression)
+      | Operand::Indirect(expression)
+      | Operand::IndirectX(expression)
+      | Operand::IndirectY(expression) => Some(expression),
+    }
+  }
+}
@@ -0,0 +1,293 @@
+use std::{env, fs, path::PathBuf};
+
+use super::{assemble, Assembler, Segment};
+use crate::error::{AssembleError, OutputError};
+
+/// Creates an empty directory for the files of the test `name`
+fn directory(name: &str) -> PathBuf {
+  let directory = env::temp_dir().join(format!("vcpu-{}-{name}", std::process::id()));
+  let _ = fs::remove_dir_all(&directory);
+  fs::create_dir_all(&directory).unwrap();
+  directory
+}
+
+/// Assembles `source` and returns its segments as `(origin, data)` pairs
+fn segments(source: &str) -> Vec<(u16, Vec<u8>)> {
+  assemble(source)
+    .unwrap()
+    .segments()
+    .iter()
+    .map(|Segment { origin, data }| (*origin, data.clone()))
+    .collect()
+}
+
+#[test]
+fn assembles_main_asm_to_a_out() {
+  let program = assemble(include_str!("../../assets/main.asm")).unwrap();
+  assert_eq!(program.to_bytes(), include_bytes!("../../assets/a.out"));
+}
+
+#[test]
+fn resolves_forward_references() {
+  let source = "
+    .org $0200
+    jmp later
+    lda value
+later:
+    rts
+value = $1234
+";
+  assert_eq!(segments(source), [(0x0200, vec![0x4C, 0x06, 0x02, 0xAD, 0x34, 0x12, 0x60])]);
+}
+
+#[test]
+fn assembles_data_and_layout_directives() {
+  let source = r#"
+    .org $0300
+    .byte 1, $FF
+    .word $1234
+    .ascii "AB"
+    .asciiz "C"
+    .res 2
+    .align 4, $EA
+    .fill 2
+"#;
+  let mut assembler = Assembler::new();
+  assembler.set_fill(0x55);
+  let program = assembler.assemble(source).unwrap();
+
+  // `.res` leaves a hole, which splits the output
+  assert_eq!(program.segments().len(), 2);
+  assert_eq!(&program.to_bytes()[0x0300..0x030E], [
+    0x01, 0xFF, 0x34, 0x12, 0x41, 0x42, 0x43, 0x00, 0x55, 0x55, 0xEA, 0xEA, 0x55, 0x55
+  ]);
+}
+
+#[test]
+fn selects_low_and_high_bytes_of_expressions() {
+  let source = "
+address = $1234
+    .org $0200
+    lda #<address
+    ldx #>address + 1
+    .byte 1 + 2 * 3
+";
+  assert_eq!(segments(source), [(0x0200, vec![0xA9, 0x34, 0xA2, 0x12, 0x07])]);
+}
+
+#[test]
+fn rejects_values_out_of_range() {
+  assert!(matches!(
+    assemble("  lda #$100"),
+    Err(AssembleError::ValueOutOfRange {
+      value: 0x100,
+      bits: 8,
+      ..
+    })
+  ));
+  assert!(matches!(
+    assemble("  .word $10000"),
+    Err(AssembleError::ValueOutOfRange { bits: 16, .. })
+  ));
+  assert!(matches!(
+    assemble("  .org $10000"),
+    Err(AssembleError::AddressOutOfRange { address: 0x10000, .. })
+  ));
+}
+
+#[test]
+fn keeps_macro_labels_local_to_each_expansion() {
+  let source = "
+.macro wait count
+    ldx #count
+loop:
+    dex
+    bne loop
+.endmacro
+
+    .org $0200
+    wait 2
+    wait 3
+";
+  assert_eq!(segments(source), [(0x0200, vec![
+    0xA2, 0x02, 0xCA, 0xD0, 0xFD, 0xA2, 0x03, 0xCA, 0xD0, 0xFD
+  ])]);
+
+  let source = "
+.macro delay
+loop:
+    bne loop
+.endmacro
+    delay
+    jmp loop
+";
+  assert!(matches!(
+    assemble(source),
+    Err(AssembleError::UndefinedLabel { name, .. }) if name == "loop"
+  ));
+}
+
+#[test]
+fn lists_addresses_bytes_cycles_and_symbols() {
+  let source = "    .org $0200\nstart: lda #$01\n    rts\n";
+  let mut assembler = Assembler::new();
+  assembler.set_listing(true);
+  assembler.assemble(source).unwrap();
+  let listing = assembler.listing().unwrap();
+
+  let rows: Vec<_> = listing
+    .lines()
+    .iter()
+    .map(|line| (line.line, line.address, line.bytes.clone(), line.cycles))
+    .collect();
+  assert_eq!(rows, [
+    (1, None, vec![], None),
+    (2, Some(0x0200), vec![0xA9, 0x01], Some(2)),
+    (3, Some(0x0202), vec![0x60], Some(6)),
+  ]);
+  assert_eq!(listing.symbols(), [("start".to_string(), 0x0200)]);
+  assert!(listing
+    .to_string()
+    .contains("    2  0200  A9 01      2  start: lda #$01"));
+}
+
+#[test]
+fn formats_binary_intel_hex_and_s_records() {
+  let program = assemble("    .org $0200\n    .byte 1, 2\n").unwrap();
+
+  assert_eq!(program.to_binary(0x0200, 4).unwrap(), [0x01, 0x02, 0x00, 0x00]);
+  assert!(matches!(
+    program.to_binary(0x0300, 4),
+    Err(OutputError::OutsideImage { address: 0x0200, .. })
+  ));
+  assert_eq!(program.to_intel_hex(), ":020200000102F9\n:00000001FF\n");
+  assert_eq!(program.to_srec(), "S00600004844521B\nS10502000102F5\nS5030001FB\nS9030000FC\n");
+}
+
+#[test]
+fn skips_includes_in_branches_that_are_not_taken() {
+  let source = r#"
+    .org $0200
+    .if 0
+    .include "missing.asm"
+    .endif
+    nop
+"#;
+  assert_eq!(segments(source), [(0x0200, vec![0xEA])]);
+}
+
+#[test]
+fn defines_macros_only_in_the_branch_that_is_taken() {
+  let source = "
+.ifdef SERIAL
+.macro output
+    sta $6000
+.endmacro
+.else
+.macro output
+    nop
+.endmacro
+.endif
+    .org $0200
+    output
+";
+  let assemble = |definitions: &[&str]| {
+    let mut assembler = Assembler::new();
+    for definition in definitions {
+      assembler.define(definition).unwrap();
+    }
+    assembler.assemble(source).unwrap().to_bytes()[0x0200..0x0203].to_vec()
+  };
+
+  assert_eq!(assemble(&["SERIAL"]), [0x8D, 0x00, 0x60]);
+  assert_eq!(assemble(&[]), [0xEA, 0x00, 0x00]);
+}
+
+#[test]
+fn decides_conditionals_on_labels_when_assembling() {
+  let source = "
+    .org $0200
+start:
+    nop
+end:
+    .if end - start == 1
+    .byte $01
+    .elseif 1
+    .byte $02
+    .endif
+";
+  assert_eq!(segments(source), [(0x0200, vec![0xEA, 0x01])]);
+}
+
+#[test]
+fn only_reads_the_files_of_the_variant_being_built() {
+  let directory = directory("variant");
+  fs::write(directory.join("serial.asm"), "    sta $6000\n").unwrap();
+  // never assembled, so neither the syntax error nor the missing binary
+  // may be reported
+  fs::write(directory.join("broken.asm"), "    lda (\n    .incbin \"missing.bin\"\n").unwrap();
+  fs::write(
+    directory.join("main.asm"),
+    r#"
+    .org $0200
+    .ifdef SERIAL
+    .include "serial.asm"
+    .else
+    .include "broken.asm"
+    .incbin "missing.bin"
+    .endif
+"#,
+  )
+  .unwrap();
+
+  let mut assembler = Assembler::new();
+  assembler.define("SERIAL").unwrap();
+  let program = assembler.assemble_file(directory.join("main.asm")).unwrap();
+  assert_eq!(program.segments(), [Segment {
+    origin: 0x0200,
+    data: vec![0x8D, 0x00, 0x60]
+  }]);
+
+  let error = Assembler::new().assemble_file(directory.join("main.asm")).unwrap_err();
+  assert!(matches!(error, AssembleError::Syntax { .. }), "{error}");
+  fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn uses_mnemonics_the_assembler_cannot_encode_as_identifiers() {
+  let source = "
+    .org $0200
+    sax = $12
+stp: lda #sax
+    jmp stp
+";
+  assert_eq!(segments(source), [(0x0200, vec![0xA9, 0x12, 0x4C, 0x00, 0x02])]);
+}
+
+#[test]
+fn uses_65c02_mnemonics_as_identifiers() {
+  let source = "
+    .org $0200
+    phx = $34
+bra:
+stz:
+    lda phx
+    bne bra
+    jmp stz
+";
+  assert_eq!(segments(source), [(0x0200, vec![0xA5, 0x34, 0xD0, 0xFC, 0x4C, 0x00, 0x02])]);
+}
+
+#[test]
+fn rejects_misplaced_conditional_directives_in_both_passes() {
+  let error = |source: &str| assemble(source).unwrap_err().to_string();
+
+  // decided while parsing
+  assert!(error("    .if 1\n    .else\n    .else\n    .endif\n").contains("`.else` after `.else`"));
+  assert!(error("    .endif\n").contains("`.endif` without `.if`"));
+  assert!(error("    .if 1\n    nop\n").contains("conditional is missing `.endif`"));
+
+  // left to the emitter, as the condition refers to a label
+  let deferred = "    .org $0200\nstart:\n    .if start\n    .else\n    .elseif 1\n    .endif\n";
+  assert!(error(deferred).contains("`.elseif` after `.else`"));
+}
@@ -1,36 +1,44 @@
-use std::fmt::{Display, Formatter};
-
-use strum::EnumDiscriminants;
+use std::{
+  fmt::{Display, Formatter},
+  path::Path,
+  sync::Arc,
+};
 
 use super::{keyword::Keyword, literal::Literal, symbol::Symbol};
 
-#[derive(Debug, Clone, PartialEq, EnumDiscriminants)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
   Symbol(SymbolToken),
+  Keyword(KeywordToken),
   Literal(LiteralToken),
-  EndOfFile {
-    position: Position,
-  },
-  Invalid {
-    position: Position,
-  },
+  EndOfLine { position: Position },
+  EndOfFile { position: Position },
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Position {
   pub line: u32,
   pub column: u32,
+  /// Source file, if the source was read from one
+  pub file: Option<Arc<Path>>,
 }
 
 impl Position {
   pub fn new(line: u32, column: u32) -> Self {
-    Self { line, column }
+    Self {
+      line,
+      column,
+      file: None,
+    }
   }
 }
 
 impl Display for Position {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    write!(f, "({}, {})", self.line, self.column)
+    match &self.file {
+      Some(file) => write!(f, "{}:{}:{}", file.display(), self.line, self.column),
+      None => write!(f, "({}, {})", self.line, self.column),
+    }
   }
 }
 
@@ -76,8 +84,8 @@ impl Token {
       Token::Symbol(SymbolToken { position, .. }) => position,
       Token::Keyword(KeywordToken { position, .. }) => position,
       Token::Literal(LiteralToken { position, .. }) => position,
+      Token::EndOfLine { position, .. } => position,
       Token::EndOfFile { position, .. } => position,
-      Token::Invalid { position, .. } => position,
     }
   }
 
@@ -86,76 +94,37 @@ impl Token {
       Token::Symbol(SymbolToken { symbol, .. }) => symbol.lexeme(),
       Token::Keyword(KeywordToken { keyword, .. }) => keyword.lexeme(),
       Token::Literal(LiteralToken { literal, .. }) => literal.lexeme(),
+      Token::EndOfLine { .. } => "[EOL]".into(),
       Token::EndOfFile { .. } => "[EOF]".into(),
-      Token::Invalid { .. } => "[INV]".into(),
     }
   }
 
+  /// Looks up the directive named by `value`, which is the lexeme following the
+  /// leading `.`
   pub fn reserved_word(value: &str, position: Position) -> Option<Self> {
-    match value {
-      // Keywords
-      Keyword::IF => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::If,
-      })),
-      Keyword::ELSE => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Else,
-      })),
-      Keyword::FOR => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::For,
-      })),
-      Keyword::WHILE => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::While,
-      })),
-      Keyword::LOOP => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Loop,
-      })),
-      Keyword::RETURN => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Return,
-      })),
-      Keyword::SELF => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::_Self,
-      })),
-      Keyword::SUPER => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Super,
-      })),
-      Keyword::IMPORT => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Import,
-      })),
-      Keyword::EXPORT => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Export,
-      })),
-      Keyword::PUBLIC => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Public,
-      })),
-      Keyword::TYPE => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Type,
-      })),
-      Keyword::IMPL => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Impl,
-      })),
-      Keyword::AS => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::As,
-      })),
-      Keyword::TRAIT => Some(Token::Keyword(KeywordToken {
-        position,
-        keyword: Keyword::Trait,
-      })),
-      _ => None,
-    }
+    let keyword = match value.to_ascii_lowercase().as_str() {
+      Keyword::ORG => Keyword::Org,
+      Keyword::BYTE => Keyword::Byte,
+      Keyword::WORD => Keyword::Word,
+      Keyword::ASCII => Keyword::Ascii,
+      Keyword::ASCIIZ => Keyword::Asciiz,
+      Keyword::RES => Keyword::Res,
+      Keyword::ALIGN => Keyword::Align,
+      Keyword::FILL => Keyword::Fill,
+      Keyword::MACRO => Keyword::Macro,
+      Keyword::ENDMACRO => Keyword::EndMacro,
+      Keyword::IF => Keyword::If,
+      Keyword::ELSEIF => Keyword::ElseIf,
+      Keyword::ELSE => Keyword::Else,
+      Keyword::ENDIF => Keyword::EndIf,
+      Keyword::IFDEF => Keyword::IfDef,
+      Keyword::IFNDEF => Keyword::IfNDef,
+      Keyword::INCLUDE => Keyword::Include,
+      Keyword::INCBIN => Keyword::IncBin,
+      _ => return None,
+    };
+
+    Some(Token::Keyword(KeywordToken { position, keyword }))
   }
 }
 
@@ -1,4 +1,4 @@
-use std::{iter::Peekable, str::Chars};
+use std::{iter::Peekable, path::Path, str::Chars, sync::Arc};
 
 use crate::assembler::token::Position;
 
@@ -6,77 +6,59 @@ pub struct CharacterProvider<'a> {
   chars: Peekable<Chars<'a>>,
   line: u32,
   column: u32,
+  pending_newline: bool,
+  file: Option<Arc<Path>>,
 }
 
 impl<'a> CharacterProvider<'a> {
-  pub fn new(source: &'a str) -> Self {
+  pub fn new(source: &'a str, file: Option<Arc<Path>>) -> Self {
     Self {
       chars: source.chars().peekable(),
       line: 1,
       column: 0,
+      pending_newline: false,
+      file,
     }
   }
 
-  // pub fn current_location(&self) -> String {
-  //     "NUL".into()
-  // }
-
   pub fn current_position(&self) -> Position {
     Position {
       line: self.line,
       column: self.column,
+      file: self.file.clone(),
     }
   }
 
-  pub fn current_line(&self) -> u32 {
-    self.line
-  }
-
-  pub fn current_column(&self) -> u32 {
-    self.column
-  }
-
   pub fn peek(&mut self) -> Option<&char> {
     self.chars.peek()
   }
 
-  fn filtered_comment(&mut self, ch: &mut char) -> Option<bool> {
-    if let Some(peek) = self.chars.peek() {
-      if *peek == '/' {
-        while *ch != '\n' {
-          self.column += 1;
-          *ch = self.chars.next()?;
-        }
-        self.line += 1;
-        self.column = 0;
+  /// Returns the next character verbatim, including whitespace and `;`
+  pub fn next_with_spaces(&mut self) -> Option<char> {
+    let ch = self.chars.next()?;
 
-        return Some(true);
-      }
+    // the line is only advanced once the newline itself has been handed out so
+    // it keeps the position of the line it terminates
+    if self.pending_newline {
+      self.line += 1;
+      self.column = 0;
+      self.pending_newline = false;
     }
 
-    Some(false)
+    self.column += 1;
+    if ch == '\n' {
+      self.pending_newline = true;
+    }
+
+    Some(ch)
   }
 
-  pub fn next_with_spaces(&mut self) -> Option<char> {
-    loop {
-      let mut ch = self.chars.next()?;
-      self.column += 1;
-      match ch {
-        whitespace if whitespace.is_whitespace() => {
-          if ch == '\n' {
-            self.line += 1;
-            self.column = 0;
-          } else {
-            break Some(ch);
-          }
-        }
-        '/' => {
-          if !self.filtered_comment(&mut ch)? {
-            break Some(ch);
-          }
-        }
-        _ => break Some(ch),
+  fn skip_comment(&mut self) {
+    while let Some(ch) = self.chars.peek() {
+      if *ch == '\n' {
+        break;
       }
+      self.next_with_spaces();
     }
   }
 }
@@ -84,22 +66,15 @@ impl<'a> CharacterProvider<'a> {
 impl Iterator for CharacterProvider<'_> {
   type Item = char;
 
+  /// Skips whitespace and `;` comments, but not newlines since statements are
+  /// terminated by them
   fn next(&mut self) -> Option<Self::Item> {
     loop {
-      let mut ch = self.chars.next()?;
-      self.column += 1;
+      let ch = self.next_with_spaces()?;
       match ch {
-        whitespace if whitespace.is_whitespace() => {
-          if ch == '\n' {
-            self.line += 1;
-            self.column = 0;
-          }
-        }
-        '/' => {
-          if !self.filtered_comment(&mut ch)? {
-            break Some(ch);
-          }
-        }
+        '\n' => break Some(ch),
+        whitespace if whitespace.is_whitespace() => (),
+        ';' => self.skip_comment(),
         _ => break Some(ch),
       }
     }
@@ -23,11 +23,11 @@ impl<T: Clone> Next<&T> {
   }
 }
 
+#[derive(Clone)]
 pub struct TokenProvider<'a> {
   previous_valid_token: Token,
   tokens: Peekable<Iter<'a, Token>>,
-  last_line: u32,
-  last_column: u32,
+  last_position: Position,
 }
 
 impl<'a> TokenProvider<'a> {
@@ -37,8 +37,7 @@ impl<'a> TokenProvider<'a> {
         position: Position::default(),
       },
       tokens: tokens.iter().peekable(),
-      last_line: 0,
-      last_column: 0,
+      last_position: Position::default(),
     }
   }
 
@@ -46,8 +45,7 @@ impl<'a> TokenProvider<'a> {
     match self.tokens.peek() {
       Some(token) => match token {
         Token::EndOfFile { position } => {
-          self.last_line = position.line;
-          self.last_column = position.column;
+          self.last_position = position.clone();
           Next::EndOfFile {
             position: position.clone(),
           }
@@ -55,7 +53,7 @@ impl<'a> TokenProvider<'a> {
         &t => Next::Token(t),
       },
       None => Next::EndOfStream {
-        position: Position::new(self.last_line, self.last_column),
+        position: self.last_position.clone(),
       },
     }
   }
@@ -67,31 +65,20 @@ impl<'a> TokenProvider<'a> {
   pub fn next(&mut self) -> Next<&Token> {
     match self.tokens.next() {
       Some(token) => match token {
-        Token::EndOfFile { position } => Next::EndOfFile {
-          position: position.clone(),
-        },
+        Token::EndOfFile { position } => {
+          self.last_position = position.clone();
+          Next::EndOfFile {
+            position: position.clone(),
+          }
+        }
         t => {
           self.previous_valid_token = t.clone();
           Next::Token(t)
         }
       },
       None => Next::EndOfStream {
-        position: Position::new(self.last_line, self.last_column),
+        position: self.last_position.clone(),
       },
     }
   }
 }
-
-// impl<'a> Iterator for TokenProvider<'a> {
-//     type Item = &'a Token;
-
-//     fn next(&mut self) -> Option<Self::Item> {
-//         match self.tokens.next() {
-//             Some(token) => match token {
-//                 &Token::EndOfFile { .. } => None,
-//                 t => Some(t),
-//             },
-//             None => None,
-//         }
-//     }
-// }
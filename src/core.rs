@@ -1,7 +1,18 @@
 pub mod bus;
 pub mod cpu;
-mod decode;
+pub(crate) mod decode;
+#[cfg(test)]
+mod dormann;
 mod execute;
+#[cfg(test)]
+mod fixtures;
 pub mod instruction;
+pub mod nestest;
+pub mod observer;
 pub mod ram;
 pub mod ram_file;
+pub mod scheduler;
+#[cfg(test)]
+mod single_step;
+pub mod step;
+pub mod variant;
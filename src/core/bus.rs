@@ -1,4 +1,4 @@
-use std::ops::RangeInclusive;
+use std::{cell::RefCell, collections::HashSet, ops::RangeInclusive, rc::Rc};
 
 // TODO: Try invert the dependency on bus so Cpu and Ram hold channels to/from
 // Bus
@@ -6,13 +6,44 @@ use std::ops::RangeInclusive;
 #[derive(Default)]
 pub struct Bus {
   items: Vec<Box<dyn BusItem>>,
+  lines: Rc<RefCell<Lines>>,
+}
+
+/// State of the interrupt lines shared between a bus and its devices
+#[derive(Debug, Default)]
+struct Lines {
+  /// Handles currently asserting IRQ
+  irq: HashSet<usize>,
+  /// Set by an NMI pulse until the CPU sees it
+  nmi: bool,
+  next_id: usize,
 }
 
 impl Bus {
   pub fn new() -> Self {
-    let items = Default::default();
+    Self::default()
+  }
+
+  /// Creates a handle a device can use to drive the interrupt lines
+  pub fn interrupt_line(&self) -> InterruptLine {
+    let mut lines = self.lines.borrow_mut();
+    let id = lines.next_id;
+    lines.next_id += 1;
+
+    InterruptLine {
+      id,
+      lines: self.lines.clone(),
+    }
+  }
 
-    Self { items }
+  /// Whether any device asserts IRQ
+  pub fn irq(&self) -> bool {
+    !self.lines.borrow().irq.is_empty()
+  }
+
+  /// Whether a device pulsed NMI since the last call
+  pub fn take_nmi(&mut self) -> bool {
+    std::mem::take(&mut self.lines.borrow_mut().nmi)
   }
 
   pub fn read(&mut self, address: u16, read_only: bool) -> u8 {
@@ -33,15 +64,117 @@ impl Bus {
     }
   }
 
+  /// Whether any connected item responds to `address`
+  pub fn is_mapped(&self, address: u16) -> bool {
+    self.items.iter().any(|item| item.address_range().contains(&address))
+  }
+
   pub fn connect(&mut self, item: impl BusItem + 'static) {
     self.items.push(Box::new(item));
   }
+
+  /// Advances every connected item by `cycles` CPU cycles
+  pub fn tick(&mut self, cycles: u32) {
+    for item in self.items.iter_mut() {
+      item.tick(cycles);
+    }
+  }
 }
 
 pub trait BusItem {
   fn address_range(&self) -> &RangeInclusive<u16>;
 
+  /// Reads the byte at `address`, which is the full bus address rather than
+  /// an offset into the device. When `read_only` is set the read comes
+  /// from a debugger or similar rather than the CPU, and must not have side
+  /// effects like clearing a status register or popping a FIFO.
   fn read(&mut self, address: u16, read_only: bool) -> u8;
 
   fn write(&mut self, address: u16, value: u8);
+
+  /// Advances the device by `cycles` CPU cycles. Timers, serial ports and the
+  /// like override this to stay in step with the processor.
+  fn tick(&mut self, _cycles: u32) {}
+}
+
+/// Lets a device drive the interrupt lines of the bus it was created from.
+/// IRQ is wired-OR: it stays asserted while any handle asserts it. Dropping a
+/// handle releases its IRQ.
+#[derive(Debug)]
+pub struct InterruptLine {
+  id: usize,
+  lines: Rc<RefCell<Lines>>,
+}
+
+impl InterruptLine {
+  pub fn assert_irq(&self) {
+    self.lines.borrow_mut().irq.insert(self.id);
+  }
+
+  pub fn release_irq(&self) {
+    self.lines.borrow_mut().irq.remove(&self.id);
+  }
+
+  /// Whether this handle is asserting IRQ
+  pub fn is_irq_asserted(&self) -> bool {
+    self.lines.borrow().irq.contains(&self.id)
+  }
+
+  /// Signals an NMI, which the CPU takes at its next instruction boundary
+  pub fn pulse_nmi(&self) {
+    self.lines.borrow_mut().nmi = true;
+  }
+}
+
+impl Drop for InterruptLine {
+  fn drop(&mut self) {
+    self.release_irq();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::{
+    cpu::Cpu,
+    ram::Ram,
+    step::{Interrupt, StepEvent},
+  };
+
+  #[test]
+  fn irq_stays_asserted_while_any_device_asserts_it() {
+    let bus = Bus::new();
+    let (timer, serial) = (bus.interrupt_line(), bus.interrupt_line());
+    timer.assert_irq();
+    serial.assert_irq();
+    assert!(bus.irq());
+
+    timer.release_irq();
+    assert!(!timer.is_irq_asserted());
+    assert!(bus.irq());
+
+    // dropping a handle releases its IRQ
+    drop(serial);
+    assert!(!bus.irq());
+  }
+
+  #[test]
+  fn nmi_pulse_is_serviced_once() {
+    let mut ram = Ram::new(0x0000..=0xFFFF);
+    ram.write_all(0xFFFC, &[0x00, 0x02]);
+    ram.write_all(0xFFFA, &[0x00, 0x03]);
+    let mut cpu = Cpu::new(1.0);
+    cpu.connect(ram);
+    cpu.reset();
+
+    let line = cpu.interrupt_line();
+    line.pulse_nmi();
+    let events: Vec<_> = (0..4).map(|_| cpu.step_instruction().unwrap().event).collect();
+    let nmis = events
+      .iter()
+      .filter(|event| matches!(event, StepEvent::Interrupt(Interrupt::Nmi)))
+      .count();
+    assert!(matches!(events[0], StepEvent::Interrupt(Interrupt::Nmi)));
+    assert_eq!(nmis, 1);
+  }
 }
@@ -1,12 +1,15 @@
-use std::{
-  fmt::{Debug, Formatter},
-  time::Duration,
-};
+use std::fmt::{Debug, Formatter};
 
-use enumflags2::{bitflags, BitFlag, BitFlags};
+use enumflags2::{bitflags, BitFlags};
 
 use crate::{
-  core::bus::{Bus, BusItem},
+  core::{
+    bus::{Bus, BusItem, InterruptLine},
+    instruction::OpCode,
+    observer::{describe, CpuObserver, NoopObserver},
+    step::{Interrupt, StepEvent},
+    variant::CpuVariant,
+  },
   error::CpuError,
 };
 
@@ -24,8 +27,9 @@ pub enum StatusFlags {
   Negative = 1 << 7,
 }
 
-pub struct Cpu {
+pub struct Cpu<O: CpuObserver = NoopObserver> {
   data_bus: Bus,
+  observer: O,
 
   pub(crate) a: u8,
   pub(crate) x: u8,
@@ -35,31 +39,66 @@ pub struct Cpu {
   pub(crate) status: BitFlags<StatusFlags>,
 
   clock_speed: f64,
-  clock: u64,
-  cycles: u8,
+  pub(crate) clock: u64,
+  pub(crate) cycles: u8,
+  pub(crate) variant: CpuVariant,
+  /// Whether ADC and SBC honor the decimal flag
+  pub(crate) decimal_mode: bool,
+  /// Whether undocumented op codes execute instead of being rejected
+  pub(crate) undocumented_opcodes: bool,
+  /// Set by `JAM` and `STP`, after which only a reset gets the CPU going
+  /// again
+  pub(crate) jammed: bool,
+  /// Set by `WAI` until the next interrupt
+  pub(crate) waiting: bool,
+  /// Level of the IRQ line, serviced at every instruction boundary while
+  /// asserted and not masked
+  irq_line: bool,
+  /// Level of the NMI line, only its rising edge matters
+  nmi_line: bool,
+  /// Set on a rising edge of the NMI line until the NMI is serviced
+  nmi_pending: bool,
+  /// I flag from before a `CLI`, `SEI` or `PLP`. They change it on their last
+  /// cycle, after interrupts were polled, so the boundary right after them
+  /// still masks IRQs by the old flag.
+  polled_mask: Option<bool>,
+  /// Whether every bus cycle of an instruction reaches the bus as it happens
+  cycle_stepped: bool,
+  /// Cycles the devices on the bus were already advanced by while a cycle
+  /// stepped instruction ran
+  ticked: u8,
+}
+
+/// Snapshot of the registers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+  pub a: u8,
+  pub x: u8,
+  pub y: u8,
+  pub stack_ptr: u8,
+  pub program_counter: u16,
+  pub status: u8,
 }
 
-impl Debug for Cpu {
+impl<O: CpuObserver> Debug for Cpu<O> {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    write!(
-      f,
-      "a: {:#04X} ({}) | x: {:#04X} ({}) | y: {:#04X} ({}) | stack_ptr: {:#04X} | prog_counter: {:#06X} | cycles: {}",
-      self.a, self.a, self.x, self.x, self.y, self.y, self.stack_ptr, self.program_counter, self.cycles
-    )
+    f.write_str(&describe(&self.registers(), self.cycles))
   }
 }
 
 impl Cpu {
-  pub const INTERRUPT_ADDRESS: u16 = 0xFFFE;
-  pub const RESET_ADDRESS: u16 = 0xFFFC;
-  pub const STACK_POINTER_BASE_ADDRESS: u16 = 0x0100;
-
+  /// Creates an NMOS 6502
   pub fn new(clock_speed: f64) -> Self {
+    Self::with_variant(clock_speed, CpuVariant::default())
+  }
+
+  pub fn with_variant(clock_speed: f64, variant: CpuVariant) -> Self {
     let data_bus = Bus::new();
     let status = Default::default();
 
     Self {
       data_bus,
+      observer: NoopObserver,
       a: 0,
       x: 0,
       y: 0,
@@ -69,30 +108,180 @@ impl Cpu {
       clock_speed,
       clock: 0,
       cycles: 0,
+      variant,
+      decimal_mode: variant.has_decimal_mode(),
+      undocumented_opcodes: false,
+      jammed: false,
+      waiting: false,
+      irq_line: false,
+      nmi_line: false,
+      nmi_pending: false,
+      polled_mask: None,
+      cycle_stepped: false,
+      ticked: 0,
     }
   }
+}
+
+impl<O: CpuObserver> Cpu<O> {
+  pub const INTERRUPT_ADDRESS: u16 = 0xFFFE;
+  pub const NMI_ADDRESS: u16 = 0xFFFA;
+  pub const RESET_ADDRESS: u16 = 0xFFFC;
+  pub const STACK_POINTER_BASE_ADDRESS: u16 = 0x0100;
+
+  /// Replaces the observer that is told about everything the CPU does, e.g.
+  /// with a [`super::observer::HumanTracer`]
+  pub fn with_observer<P: CpuObserver>(self, observer: P) -> Cpu<P> {
+    Cpu {
+      data_bus: self.data_bus,
+      observer,
+      a: self.a,
+      x: self.x,
+      y: self.y,
+      stack_ptr: self.stack_ptr,
+      program_counter: self.program_counter,
+      status: self.status,
+      clock_speed: self.clock_speed,
+      clock: self.clock,
+      cycles: self.cycles,
+      variant: self.variant,
+      decimal_mode: self.decimal_mode,
+      undocumented_opcodes: self.undocumented_opcodes,
+      jammed: self.jammed,
+      waiting: self.waiting,
+      irq_line: self.irq_line,
+      nmi_line: self.nmi_line,
+      nmi_pending: self.nmi_pending,
+      polled_mask: self.polled_mask,
+      cycle_stepped: self.cycle_stepped,
+      ticked: self.ticked,
+    }
+  }
+
+  pub fn observer(&self) -> &O {
+    &self.observer
+  }
+
+  pub fn observer_mut(&mut self) -> &mut O {
+    &mut self.observer
+  }
 
   pub fn connect(&mut self, item: impl BusItem + 'static) {
     self.data_bus.connect(item)
   }
 
-  pub fn clock(&mut self) -> Result<(), CpuError> {
-    if self.cycles == 0 {
-      let state_before = format!("{self:?}");
-      let opcode = self.fetch()?;
-      let instruction = self.decode(opcode)?;
-      let instr_string = format!("  => {instruction:?}");
-      self.cycles += self.execute(instruction)?;
-      println!(
-        "[{}]\nBefore: [{state_before}]\n{instr_string}\nAfter: [{self:?}]\n",
-        self.clock
-      );
+  /// Runs one cycle and returns what started on it, if anything. A whole
+  /// instruction or interrupt sequence runs on its first cycle and the
+  /// following ones only count it down.
+  pub(crate) fn cycle(&mut self) -> Result<Option<StepEvent>, CpuError> {
+    let irq = self.poll_interrupts();
+    // `WAI` ends on any interrupt, even a masked IRQ, which then does not get
+    // serviced
+    if self.waiting && (self.nmi_pending || irq) {
+      self.waiting = false;
     }
 
+    let masked = match self.cycles {
+      0 => self.polled_mask.take(),
+      _ => None,
+    }
+    .unwrap_or(self.is_flag(StatusFlags::Interrupt));
+
+    let event = match self.cycles {
+      _ if self.jammed || self.waiting => None,
+      0 if self.nmi_pending => {
+        self.nmi_pending = false;
+        self.service_interrupt(Interrupt::Nmi);
+        Some(StepEvent::Interrupt(Interrupt::Nmi))
+      }
+      0 if irq && !masked => {
+        self.service_interrupt(Interrupt::Irq);
+        Some(StepEvent::Interrupt(Interrupt::Irq))
+      }
+      0 => {
+        let registers = self.registers();
+        let opcode = self.fetch()?;
+        self
+          .observer
+          .fetch(registers.program_counter, opcode, &registers, self.clock);
+        let instruction = self.decode(opcode)?;
+        self.observer.decoded(&instruction);
+        let mask = self.is_flag(StatusFlags::Interrupt);
+        self.execute(instruction)?;
+        if matches!(instruction.opcode, OpCode::CLI | OpCode::SEI | OpCode::PLP) {
+          self.polled_mask = Some(mask);
+        }
+        let registers = self.registers();
+        self.observer.executed(&instruction, &registers, self.cycles);
+        Some(StepEvent::Instruction(instruction))
+      }
+      _ => None,
+    };
+
+    self.elapse();
+    self.cycles = self.cycles.saturating_sub(1);
+
+    Ok(event)
+  }
+
+  /// Counts a cycle and lets the devices on the bus advance by it, unless
+  /// they already were during a cycle stepped instruction
+  fn elapse(&mut self) {
     self.clock += 1;
-    self.cycles -= 1;
+    match self.ticked {
+      0 => self.data_bus.tick(1),
+      _ => self.ticked -= 1,
+    }
+  }
 
-    Ok(())
+  /// Reads `address` as the next bus cycle of the running instruction
+  pub(crate) fn read_cycle(&mut self, address: u16) -> u8 {
+    let value = self.read(address);
+    self.observer.read(address, value);
+    self.bus_cycle();
+    value
+  }
+
+  /// Writes `address` as the next bus cycle of the running instruction
+  pub(crate) fn write_cycle(&mut self, address: u16, value: u8) {
+    self.write(address, value);
+    self.observer.write(address, value);
+    self.bus_cycle();
+  }
+
+  /// A bus cycle that reads `address` and ignores the value, like the read of
+  /// the unfixed address when indexing crosses a page. Only reaches the bus
+  /// in cycle stepped mode.
+  pub(crate) fn dummy_read(&mut self, address: u16) {
+    if self.cycle_stepped {
+      let value = self.read(address);
+      self.observer.read(address, value);
+    }
+    self.bus_cycle();
+  }
+
+  /// A bus cycle that writes `value` to `address` before the real write, like
+  /// the first write of a read-modify-write instruction. Only reaches the bus
+  /// in cycle stepped mode.
+  pub(crate) fn dummy_write(&mut self, address: u16, value: u8) {
+    if self.cycle_stepped {
+      self.write(address, value);
+      self.observer.write(address, value);
+    }
+    self.bus_cycle();
+  }
+
+  /// A cycle the 65C02 spends without a bus access the NMOS 6502 would make
+  pub(crate) fn idle_cycle(&mut self) {
+    self.bus_cycle();
+  }
+
+  fn bus_cycle(&mut self) {
+    self.cycles += 1;
+    if self.cycle_stepped {
+      self.data_bus.tick(1);
+      self.ticked += 1;
+    }
   }
 
   pub fn read(&mut self, address: u16) -> u8 {
@@ -103,6 +292,87 @@ impl Cpu {
     self.data_bus.write(address, value)
   }
 
+  /// Reads `address` for inspection, e.g. by a debugger, without the device
+  /// side effects of a real bus read
+  pub fn peek(&mut self, address: u16) -> u8 {
+    self.data_bus.read(address, true)
+  }
+
+  pub fn variant(&self) -> CpuVariant {
+    self.variant
+  }
+
+  /// Frequency in Hz the CPU runs at under a [`super::scheduler::Scheduler`]
+  pub fn clock_speed(&self) -> f64 {
+    self.clock_speed
+  }
+
+  /// Enables or disables BCD arithmetic. The Ricoh 2A03 in the NES lacks it,
+  /// so ADC and SBC stay binary there even with the decimal flag set.
+  pub fn set_decimal_mode(&mut self, enabled: bool) {
+    self.decimal_mode = enabled;
+  }
+
+  /// Enables or disables the undocumented NMOS op codes such as `LAX` and
+  /// `DCP`. Disabled by default, in which case they are reported as
+  /// [`CpuError::InvalidOpCode`]. Has no effect on the 65C02 variants, which
+  /// execute undefined op codes as `NOP`s.
+  pub fn set_undocumented_opcodes(&mut self, enabled: bool) {
+    self.undocumented_opcodes = enabled;
+  }
+
+  pub fn registers(&self) -> Registers {
+    Registers {
+      a: self.a,
+      x: self.x,
+      y: self.y,
+      stack_ptr: self.stack_ptr,
+      program_counter: self.program_counter,
+      status: self.status.bits(),
+    }
+  }
+
+  pub fn set_registers(&mut self, registers: Registers) {
+    self.a = registers.a;
+    self.x = registers.x;
+    self.y = registers.y;
+    self.stack_ptr = registers.stack_ptr;
+    self.program_counter = registers.program_counter;
+    self.status = BitFlags::from_bits_truncate(registers.status);
+  }
+
+  /// Cycles run since the last reset
+  pub fn total_cycles(&self) -> u64 {
+    self.clock
+  }
+
+  /// Enables or disables cycle stepped mode. Instructions always take the
+  /// cycles of the real CPU, but by default only their logical reads and
+  /// writes reach the bus and devices advance once per [`Cpu::step_cycle`]. In
+  /// cycle stepped mode every bus cycle is performed, including dummy reads
+  /// on page crossings and the double write of read-modify-write
+  /// instructions, and devices advance after each of them. An instruction
+  /// still runs on the first call to [`Cpu::step_cycle`] and the remaining
+  /// calls only count its cycles.
+  pub fn set_cycle_stepped(&mut self, enabled: bool) {
+    self.cycle_stepped = enabled;
+  }
+
+  /// Whether a `JAM` or `STP` instruction has stopped the CPU
+  pub fn is_jammed(&self) -> bool {
+    self.jammed
+  }
+
+  /// Whether a `WAI` instruction is waiting for an interrupt
+  pub fn is_waiting(&self) -> bool {
+    self.waiting
+  }
+
+  /// Whether any device on the bus responds to `address`
+  pub fn is_mapped(&self, address: u16) -> bool {
+    self.data_bus.is_mapped(address)
+  }
+
   pub fn is_flag(&self, flag: StatusFlags) -> bool {
     self.status.contains(flag)
   }
@@ -111,91 +381,318 @@ impl Cpu {
     self.status.set(flag, set);
   }
 
+  /// Loads the reset vector. Like IRQ and NMI the sequence takes 7 cycles,
+  /// with the writes to the stack turned into reads.
   pub fn reset(&mut self) {
+    self.observer.reset();
     self.a = 0;
     self.x = 0;
     self.y = 0;
     self.stack_ptr = 0xFD;
-    self.status = StatusFlags::empty() | StatusFlags::Unused;
+    self.status = StatusFlags::Unused | StatusFlags::Interrupt;
+    self.jammed = false;
+    self.waiting = false;
+    self.nmi_pending = false;
+    self.polled_mask = None;
+    self.ticked = 0;
 
     let lo = self.read(Self::RESET_ADDRESS);
     let hi = self.read(Self::RESET_ADDRESS + 1);
     self.program_counter = u16::from_le_bytes([lo, hi]);
 
     self.clock = 0;
-    self.cycles = 8;
+    self.cycles = 7;
   }
 
-  pub fn interrupt(&mut self) {
-    if !self.is_flag(StatusFlags::Interrupt) {
-      let [lo, hi] = self.program_counter.to_le_bytes();
-      self.push(hi);
-      self.push(lo);
+  /// Creates a handle a device can use to raise interrupts, see
+  /// [`Bus::interrupt_line`]
+  pub fn interrupt_line(&self) -> InterruptLine {
+    self.data_bus.interrupt_line()
+  }
 
-      self.set_flag(StatusFlags::Break, false);
-      self.set_flag(StatusFlags::Unused, true);
-      self.set_flag(StatusFlags::Interrupt, true);
-      self.push(self.status.bits());
+  /// Latches NMI pulses from devices and returns the combined level of the
+  /// IRQ line
+  fn poll_interrupts(&mut self) -> bool {
+    if self.data_bus.take_nmi() {
+      self.nmi_pending = true;
+    }
+    self.irq_line || self.data_bus.irq()
+  }
 
-      let lo = self.read(Self::INTERRUPT_ADDRESS);
-      let hi = self.read(Self::INTERRUPT_ADDRESS + 1);
-      self.program_counter = u16::from_le_bytes([lo, hi]);
+  /// Sets the level of the IRQ line. While it is asserted and interrupts are
+  /// not masked, an IRQ is taken at every instruction boundary, so devices
+  /// have to release the line once they have been serviced.
+  pub fn set_irq_line(&mut self, asserted: bool) {
+    self.irq_line = asserted;
+  }
 
-      self.cycles = 7;
+  /// Sets the level of the NMI line. The NMI is edge triggered: it is taken
+  /// once at the next instruction boundary after the line becomes asserted.
+  pub fn set_nmi_line(&mut self, asserted: bool) {
+    if asserted && !self.nmi_line {
+      self.nmi_pending = true;
     }
+    self.nmi_line = asserted;
+  }
+
+  /// Pulses the NMI line, taking an NMI at the next instruction boundary
+  pub fn trigger_nmi(&mut self) {
+    self.nmi_pending = true;
   }
 
+  /// Takes an IRQ right away unless IRQs are masked. Only call it between
+  /// instructions; devices should drive the IRQ line instead.
+  #[deprecated(note = "use `set_irq_line` or an `InterruptLine`, sampled at instruction boundaries")]
+  pub fn interrupt(&mut self) {
+    if !self.is_flag(StatusFlags::Interrupt) {
+      self.service_interrupt(Interrupt::Irq);
+    }
+  }
+
+  /// Takes an NMI right away. Only call it between instructions; devices
+  /// should pulse the NMI line instead.
+  #[deprecated(note = "use `trigger_nmi`, `set_nmi_line` or an `InterruptLine`")]
   pub fn non_maskable_interrupt(&mut self) {
+    self.service_interrupt(Interrupt::Nmi);
+  }
+
+  /// Takes an IRQ or NMI at an instruction boundary. The op code that would
+  /// have run is read twice and dropped before the interrupt sequence.
+  fn service_interrupt(&mut self, interrupt: Interrupt) {
+    self.observer.interrupt(interrupt);
+    self.dummy_read(self.program_counter);
+    self.dummy_read(self.program_counter);
+    let vector = match interrupt {
+      Interrupt::Irq => Self::INTERRUPT_ADDRESS,
+      Interrupt::Nmi => Self::NMI_ADDRESS,
+    };
+    self.enter_interrupt(vector, false);
+  }
+
+  /// Pushes the program counter and status, masks IRQs and continues at the
+  /// address stored in `vector`. `brk` sets the B flag in the pushed status
+  /// so handlers can tell `BRK` from an IRQ.
+  pub(crate) fn enter_interrupt(&mut self, vector: u16, brk: bool) {
     let [lo, hi] = self.program_counter.to_le_bytes();
     self.push(hi);
     self.push(lo);
 
-    self.set_flag(StatusFlags::Break, false);
-    self.set_flag(StatusFlags::Unused, true);
+    let mut status = self.status | StatusFlags::Unused;
+    status.set(StatusFlags::Break, brk);
+    self.push(status.bits());
+
     self.set_flag(StatusFlags::Interrupt, true);
-    self.push(self.status.bits());
+    if self.variant.is_cmos() {
+      self.set_flag(StatusFlags::Decimal, false);
+    }
 
-    let lo = self.read(Self::INTERRUPT_ADDRESS);
-    let hi = self.read(Self::INTERRUPT_ADDRESS + 1);
+    let lo = self.read_cycle(vector);
+    let hi = self.read_cycle(vector.wrapping_add(1));
     self.program_counter = u16::from_le_bytes([lo, hi]);
-
-    self.cycles = 8;
   }
 
   pub fn fetch(&mut self) -> Result<u8, CpuError> {
-    let value = self.read(self.program_counter);
-    self.program_counter += 1;
+    let value = self.read_cycle(self.program_counter);
+    self.program_counter = self.program_counter.wrapping_add(1);
     Ok(value)
   }
 
   pub fn pop(&mut self) -> u8 {
-    self.stack_ptr += 1;
-    self.read(Self::STACK_POINTER_BASE_ADDRESS + self.stack_ptr as u16)
+    self.stack_ptr = self.stack_ptr.wrapping_add(1);
+    self.read_cycle(self.stack_address())
   }
 
   pub fn push(&mut self, value: u8) {
-    self.write(Self::STACK_POINTER_BASE_ADDRESS + self.stack_ptr as u16, value);
-    self.stack_ptr -= 1;
+    self.write_cycle(self.stack_address(), value);
+    self.stack_ptr = self.stack_ptr.wrapping_sub(1);
+  }
+
+  /// Address the stack pointer points at
+  pub(crate) fn stack_address(&self) -> u16 {
+    Self::STACK_POINTER_BASE_ADDRESS + self.stack_ptr as u16
   }
 }
 
-impl Iterator for Cpu {
+/// Runs an instruction per item as fast as possible. A
+/// [`super::scheduler::Scheduler`] runs the CPU at its clock speed instead.
+impl<O: CpuObserver> Iterator for Cpu<O> {
   type Item = ();
 
   fn next(&mut self) -> Option<Self::Item> {
-    loop {
-      if let Err(error) = self.clock() {
-        eprintln!("{error}");
-        return None;
-      };
+    if let Err(error) = self.step_instruction() {
+      eprintln!("{error}");
+      return None;
+    }
+
+    Some(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    cell::{Cell, RefCell},
+    ops::RangeInclusive,
+    rc::Rc,
+  };
+
+  use super::*;
+
+  #[derive(Debug, PartialEq)]
+  enum Access {
+    Read(u16),
+    Write(u16, u8),
+  }
+
+  /// What a [`Probe`] saw of the bus
+  #[derive(Default)]
+  struct Record {
+    ticks: Cell<u32>,
+    accesses: RefCell<Vec<Access>>,
+  }
+
+  /// Memory that records its accesses and the cycles it was ticked by
+  struct Probe {
+    range: RangeInclusive<u16>,
+    memory: Vec<u8>,
+    record: Rc<Record>,
+  }
+
+  impl BusItem for Probe {
+    fn address_range(&self) -> &RangeInclusive<u16> {
+      &self.range
+    }
+
+    fn read(&mut self, address: u16, _read_only: bool) -> u8 {
+      self.record.accesses.borrow_mut().push(Access::Read(address));
+      self.memory[address as usize]
+    }
 
-      std::thread::sleep(Duration::from_secs_f64(1.0 / self.clock_speed));
+    fn write(&mut self, address: u16, value: u8) {
+      self.record.accesses.borrow_mut().push(Access::Write(address, value));
+      self.memory[address as usize] = value;
+    }
 
-      if self.cycles == 0 {
-        break;
+    fn tick(&mut self, cycles: u32) {
+      self.record.ticks.set(self.record.ticks.get() + cycles);
+    }
+  }
+
+  /// A reset CPU with `program` at `$0200` and the record of its device
+  fn probe(program: &[u8], cycle_stepped: bool) -> (Cpu, Rc<Record>) {
+    let record = Rc::new(Record::default());
+    let mut memory = vec![0xEA; 0x10000];
+    memory[0x0200..0x0200 + program.len()].copy_from_slice(program);
+    memory[0xFFFC..0xFFFE].copy_from_slice(&[0x00, 0x02]);
+
+    let mut cpu = Cpu::new(1.0);
+    cpu.set_cycle_stepped(cycle_stepped);
+    cpu.connect(Probe {
+      range: 0x0000..=0xFFFF,
+      memory,
+      record: record.clone(),
+    });
+    cpu.reset();
+    (cpu, record)
+  }
+
+  /// The bus accesses of the next instruction
+  fn accesses(cpu: &mut Cpu, record: &Record) -> Vec<Access> {
+    cpu.step_instruction().unwrap();
+    record.accesses.take()
+  }
+
+  #[test]
+  fn devices_tick_once_per_cycle() {
+    // LDA $1234; INC $10; LDA $12FF,X
+    let program = [0xAD, 0x34, 0x12, 0xE6, 0x10, 0xBD, 0xFF, 0x12];
+    for cycle_stepped in [false, true] {
+      let (mut cpu, record) = probe(&program, cycle_stepped);
+      for cycles in [4, 5, 4] {
+        let before = record.ticks.get();
+        let step = cpu.step_instruction().unwrap();
+        assert_eq!(step.cycles, cycles);
+        assert_eq!(record.ticks.get() as u64, cpu.total_cycles());
+        if before > 0 {
+          assert_eq!(record.ticks.get() - before, cycles as u32);
+        }
       }
     }
+  }
 
-    Some(())
+  #[test]
+  fn cycle_stepped_devices_tick_as_the_instruction_runs() {
+    // INC $10
+    let (mut cpu, record) = probe(&[0xE6, 0x10], false);
+    cpu.run_cycles(7).unwrap();
+    for cycle in 1..=5 {
+      cpu.step_cycle().unwrap();
+      assert_eq!(record.ticks.get(), 7 + cycle);
+    }
+
+    // every bus cycle of the instruction ticks the devices when it runs, and
+    // the cycles counted down after it do not tick them again
+    let (mut cpu, record) = probe(&[0xE6, 0x10], true);
+    cpu.run_cycles(7).unwrap();
+    cpu.step_cycle().unwrap();
+    assert_eq!(record.ticks.get(), 7 + 5);
+    cpu.run_cycles(4).unwrap();
+    assert_eq!(record.ticks.get(), 7 + 5);
+    // the `NOP` that follows
+    cpu.step_cycle().unwrap();
+    assert_eq!(record.ticks.get(), 7 + 5 + 2);
+  }
+
+  #[test]
+  fn read_modify_writes_write_twice_when_cycle_stepped() {
+    use Access::*;
+
+    // INC $1234
+    let (mut cpu, record) = probe(&[0xEE, 0x34, 0x12], true);
+    record.accesses.take();
+    assert_eq!(accesses(&mut cpu, &record), [
+      Read(0x0200),
+      Read(0x0201),
+      Read(0x0202),
+      Read(0x1234),
+      Write(0x1234, 0xEA),
+      Write(0x1234, 0xEB),
+    ]);
+
+    let (mut cpu, record) = probe(&[0xEE, 0x34, 0x12], false);
+    record.accesses.take();
+    assert_eq!(accesses(&mut cpu, &record), [
+      Read(0x0200),
+      Read(0x0201),
+      Read(0x0202),
+      Read(0x1234),
+      Write(0x1234, 0xEB),
+    ]);
+  }
+
+  #[test]
+  fn page_crossing_reads_read_the_unfixed_address_when_cycle_stepped() {
+    use Access::*;
+
+    // LDX #$01; LDA $12FF,X
+    let program = [0xA2, 0x01, 0xBD, 0xFF, 0x12];
+    let (mut cpu, record) = probe(&program, true);
+    accesses(&mut cpu, &record);
+    assert_eq!(accesses(&mut cpu, &record), [
+      Read(0x0202),
+      Read(0x0203),
+      Read(0x0204),
+      Read(0x1200),
+      Read(0x1300),
+    ]);
+
+    let (mut cpu, record) = probe(&program, false);
+    accesses(&mut cpu, &record);
+    assert_eq!(accesses(&mut cpu, &record), [
+      Read(0x0202),
+      Read(0x0203),
+      Read(0x0204),
+      Read(0x1300),
+    ]);
   }
 }
@@ -1,201 +1,529 @@
 use crate::{
   core::{
     cpu::Cpu,
-    instruction::{AddressingMode, Instruction, OpCode},
+    instruction::{AddressingMode, AddressingModeDiscriminants as Mode, Instruction, OpCode},
+    observer::CpuObserver,
+    variant::CpuVariant,
   },
   error::CpuError,
 };
 
-impl Cpu {
+impl<O: CpuObserver> Cpu<O> {
   pub(crate) fn decode(&mut self, opcode: u8) -> Result<Instruction, CpuError> {
-    let (opcode, mode, cycles) = match opcode {
-      // ADC
-      0x69 => (OpCode::ADC, AddressingMode::immediate(self), 2),
-      0x65 => (OpCode::ADC, AddressingMode::zero_page(self), 3),
-      0x75 => (OpCode::ADC, AddressingMode::zero_page_x(self), 4),
-      0x6D => (OpCode::ADC, AddressingMode::absolute(self), 4),
-      0x7D => (OpCode::ADC, AddressingMode::absolute_x(self), 4),
-      0x79 => (OpCode::ADC, AddressingMode::absolute_y(self), 4),
-      0x61 => (OpCode::ADC, AddressingMode::indirect_x(self), 6),
-      0x71 => (OpCode::ADC, AddressingMode::indirect_y(self), 5),
-      // AND
-      0x29 => (OpCode::AND, AddressingMode::immediate(self), 2),
-      0x25 => (OpCode::AND, AddressingMode::zero_page(self), 3),
-      0x35 => (OpCode::AND, AddressingMode::zero_page_x(self), 4),
-      0x2D => (OpCode::AND, AddressingMode::absolute(self), 4),
-      0x3D => (OpCode::AND, AddressingMode::absolute_x(self), 4),
-      0x39 => (OpCode::AND, AddressingMode::absolute_y(self), 4),
-      0x21 => (OpCode::AND, AddressingMode::indirect_x(self), 6),
-      0x31 => (OpCode::AND, AddressingMode::indirect_y(self), 5),
-      // ASL
-      0x0A => (OpCode::ASL, AddressingMode::accumulator(self), 2),
-      0x06 => (OpCode::ASL, AddressingMode::zero_page(self), 5),
-      0x16 => (OpCode::ASL, AddressingMode::zero_page_x(self), 6),
-      0x0E => (OpCode::ASL, AddressingMode::absolute(self), 6),
-      0x1E => (OpCode::ASL, AddressingMode::absolute_x(self), 7),
-      // BIT
-      0x24 => (OpCode::BIT, AddressingMode::zero_page(self), 3),
-      0x2C => (OpCode::BIT, AddressingMode::absolute(self), 4),
-      // BRANCH
-      0x10 => (OpCode::BPL, AddressingMode::relative(self), 2),
-      0x30 => (OpCode::BMI, AddressingMode::relative(self), 2),
-      0x50 => (OpCode::BVC, AddressingMode::relative(self), 2),
-      0x70 => (OpCode::BVS, AddressingMode::relative(self), 2),
-      0x90 => (OpCode::BCC, AddressingMode::relative(self), 2),
-      0xB0 => (OpCode::BCS, AddressingMode::relative(self), 2),
-      0xD0 => (OpCode::BNE, AddressingMode::relative(self), 2),
-      0xF0 => (OpCode::BEQ, AddressingMode::relative(self), 2),
-      // BREAK
-      0x00 => (OpCode::BRK, AddressingMode::implied(self), 7),
-      // CMP
-      0xC9 => (OpCode::CMP, AddressingMode::immediate(self), 2),
-      0xC5 => (OpCode::CMP, AddressingMode::zero_page(self), 3),
-      0xD5 => (OpCode::CMP, AddressingMode::zero_page_x(self), 4),
-      0xCD => (OpCode::CMP, AddressingMode::absolute(self), 4),
-      0xDD => (OpCode::CMP, AddressingMode::absolute_x(self), 4),
-      0xD9 => (OpCode::CMP, AddressingMode::absolute_y(self), 4),
-      0xC1 => (OpCode::CMP, AddressingMode::indirect_x(self), 6),
-      0xD1 => (OpCode::CMP, AddressingMode::indirect_y(self), 5),
-      // CPX
-      0xE0 => (OpCode::CPX, AddressingMode::immediate(self), 2),
-      0xE4 => (OpCode::CPX, AddressingMode::zero_page(self), 3),
-      0xEC => (OpCode::CPX, AddressingMode::absolute(self), 4),
-      // CPY
-      0xC0 => (OpCode::CPY, AddressingMode::immediate(self), 2),
-      0xC4 => (OpCode::CPY, AddressingMode::zero_page(self), 3),
-      0xCC => (OpCode::CPY, AddressingMode::absolute(self), 4),
-      // DEC
-      0xC6 => (OpCode::DEC, AddressingMode::zero_page(self), 5),
-      0xD6 => (OpCode::DEC, AddressingMode::zero_page_x(self), 6),
-      0xCE => (OpCode::DEC, AddressingMode::absolute(self), 6),
-      0xDE => (OpCode::DEC, AddressingMode::absolute_x(self), 7),
-      // EOR
-      0x49 => (OpCode::EOR, AddressingMode::immediate(self), 2),
-      0x45 => (OpCode::EOR, AddressingMode::zero_page(self), 3),
-      0x55 => (OpCode::EOR, AddressingMode::zero_page_x(self), 4),
-      0x4D => (OpCode::EOR, AddressingMode::absolute(self), 4),
-      0x5D => (OpCode::EOR, AddressingMode::absolute_x(self), 4),
-      0x59 => (OpCode::EOR, AddressingMode::absolute_y(self), 4),
-      0x41 => (OpCode::EOR, AddressingMode::indirect_x(self), 6),
-      0x51 => (OpCode::EOR, AddressingMode::indirect_y(self), 5),
-      // FLAGS
-      0x18 => (OpCode::CLC, AddressingMode::implied(self), 2),
-      0x38 => (OpCode::SEC, AddressingMode::implied(self), 2),
-      0x58 => (OpCode::CLI, AddressingMode::implied(self), 2),
-      0x78 => (OpCode::SEI, AddressingMode::implied(self), 2),
-      0xB8 => (OpCode::CLV, AddressingMode::implied(self), 2),
-      0xD8 => (OpCode::CLD, AddressingMode::implied(self), 2),
-      0xF8 => (OpCode::SED, AddressingMode::implied(self), 2),
-      // INC
-      0xE6 => (OpCode::INC, AddressingMode::zero_page(self), 5),
-      0xF6 => (OpCode::INC, AddressingMode::zero_page_x(self), 6),
-      0xEE => (OpCode::INC, AddressingMode::absolute(self), 6),
-      0xFE => (OpCode::INC, AddressingMode::absolute_x(self), 7),
-      // JMP
-      0x4C => (OpCode::JMP, AddressingMode::absolute(self), 3),
-      0x6C => (OpCode::JMP, AddressingMode::indirect(self), 5),
-      // JSR
-      0x20 => (OpCode::JSR, AddressingMode::absolute(self), 6),
-      // LDA
-      0xA9 => (OpCode::LDA, AddressingMode::immediate(self), 2),
-      0xA5 => (OpCode::LDA, AddressingMode::zero_page(self), 3),
-      0xB5 => (OpCode::LDA, AddressingMode::zero_page_x(self), 4),
-      0xAD => (OpCode::LDA, AddressingMode::absolute(self), 4),
-      0xBD => (OpCode::LDA, AddressingMode::absolute_x(self), 4),
-      0xB9 => (OpCode::LDA, AddressingMode::absolute_y(self), 4),
-      0xA1 => (OpCode::LDA, AddressingMode::indirect_x(self), 6),
-      0xB1 => (OpCode::LDA, AddressingMode::indirect_y(self), 5),
-      // LDX
-      0xA2 => (OpCode::LDX, AddressingMode::immediate(self), 2),
-      0xA6 => (OpCode::LDX, AddressingMode::zero_page(self), 3),
-      0xB6 => (OpCode::LDX, AddressingMode::zero_page_y(self), 4),
-      0xAE => (OpCode::LDX, AddressingMode::absolute(self), 4),
-      0xBE => (OpCode::LDX, AddressingMode::absolute_y(self), 4),
-      // LDY
-      0xA0 => (OpCode::LDY, AddressingMode::immediate(self), 2),
-      0xA4 => (OpCode::LDY, AddressingMode::zero_page(self), 3),
-      0xB4 => (OpCode::LDY, AddressingMode::zero_page_x(self), 4),
-      0xAC => (OpCode::LDY, AddressingMode::absolute(self), 4),
-      0xBC => (OpCode::LDY, AddressingMode::absolute_x(self), 4),
-      // LSR
-      0x4A => (OpCode::LSR, AddressingMode::accumulator(self), 2),
-      0x46 => (OpCode::LSR, AddressingMode::zero_page(self), 5),
-      0x56 => (OpCode::LSR, AddressingMode::zero_page_x(self), 6),
-      0x4E => (OpCode::LSR, AddressingMode::absolute(self), 6),
-      0x5E => (OpCode::LSR, AddressingMode::absolute_x(self), 7),
-      // NOP
-      0xEA => (OpCode::NOP, AddressingMode::implied(self), 2),
-      // ORA
-      0x09 => (OpCode::ORA, AddressingMode::immediate(self), 2),
-      0x05 => (OpCode::ORA, AddressingMode::zero_page(self), 3),
-      0x15 => (OpCode::ORA, AddressingMode::zero_page_x(self), 4),
-      0x0D => (OpCode::ORA, AddressingMode::absolute(self), 4),
-      0x1D => (OpCode::ORA, AddressingMode::absolute_x(self), 4),
-      0x19 => (OpCode::ORA, AddressingMode::absolute_y(self), 4),
-      0x01 => (OpCode::ORA, AddressingMode::indirect_x(self), 6),
-      0x11 => (OpCode::ORA, AddressingMode::indirect_y(self), 5),
-      // REGISTER
-      0xAA => (OpCode::TAX, AddressingMode::implied(self), 2),
-      0x8A => (OpCode::TXA, AddressingMode::implied(self), 2),
-      0xCA => (OpCode::DEX, AddressingMode::implied(self), 2),
-      0xE8 => (OpCode::INX, AddressingMode::implied(self), 2),
-      0xA8 => (OpCode::TAY, AddressingMode::implied(self), 2),
-      0x98 => (OpCode::TYA, AddressingMode::implied(self), 2),
-      0x88 => (OpCode::DEY, AddressingMode::implied(self), 2),
-      0xC8 => (OpCode::INY, AddressingMode::implied(self), 2),
-      // ROL
-      0x2A => (OpCode::ROL, AddressingMode::accumulator(self), 2),
-      0x26 => (OpCode::ROL, AddressingMode::zero_page(self), 5),
-      0x36 => (OpCode::ROL, AddressingMode::zero_page_x(self), 6),
-      0x2E => (OpCode::ROL, AddressingMode::absolute(self), 6),
-      0x3E => (OpCode::ROL, AddressingMode::absolute_x(self), 7),
-      // ROR
-      0x6A => (OpCode::ROR, AddressingMode::accumulator(self), 2),
-      0x66 => (OpCode::ROR, AddressingMode::zero_page(self), 5),
-      0x76 => (OpCode::ROR, AddressingMode::zero_page_x(self), 6),
-      0x6E => (OpCode::ROR, AddressingMode::absolute(self), 6),
-      0x7E => (OpCode::ROR, AddressingMode::absolute_x(self), 7),
-      // RTI
-      0x40 => (OpCode::RTI, AddressingMode::implied(self), 6),
-      // RTS
-      0x60 => (OpCode::RTS, AddressingMode::implied(self), 6),
-      // SBC
-      0xE9 => (OpCode::SBC, AddressingMode::immediate(self), 2),
-      0xE5 => (OpCode::SBC, AddressingMode::zero_page(self), 3),
-      0xF5 => (OpCode::SBC, AddressingMode::zero_page_x(self), 4),
-      0xED => (OpCode::SBC, AddressingMode::absolute(self), 4),
-      0xFD => (OpCode::SBC, AddressingMode::absolute_x(self), 4),
-      0xF9 => (OpCode::SBC, AddressingMode::absolute_y(self), 4),
-      0xE1 => (OpCode::SBC, AddressingMode::indirect_x(self), 6),
-      0xF1 => (OpCode::SBC, AddressingMode::indirect_y(self), 5),
-      // STA
-      0x85 => (OpCode::STA, AddressingMode::zero_page(self), 3),
-      0x95 => (OpCode::STA, AddressingMode::zero_page_x(self), 4),
-      0x8D => (OpCode::STA, AddressingMode::absolute(self), 4),
-      0x9D => (OpCode::STA, AddressingMode::absolute_x(self), 5),
-      0x99 => (OpCode::STA, AddressingMode::absolute_y(self), 5),
-      0x81 => (OpCode::STA, AddressingMode::indirect_x(self), 6),
-      0x91 => (OpCode::STA, AddressingMode::indirect_y(self), 6),
-      // STACK
-      0x9A => (OpCode::TXS, AddressingMode::implied(self), 2),
-      0xBA => (OpCode::TSX, AddressingMode::implied(self), 2),
-      0x48 => (OpCode::PHA, AddressingMode::implied(self), 3),
-      0x68 => (OpCode::PLA, AddressingMode::implied(self), 4),
-      0x08 => (OpCode::PHP, AddressingMode::implied(self), 3),
-      0x28 => (OpCode::PLP, AddressingMode::implied(self), 4),
-      // STX
-      0x86 => (OpCode::STX, AddressingMode::zero_page(self), 3),
-      0x96 => (OpCode::STX, AddressingMode::zero_page_y(self), 4),
-      0x8E => (OpCode::STX, AddressingMode::absolute(self), 4),
-      // STY
-      0x84 => (OpCode::STY, AddressingMode::zero_page(self), 3),
-      0x94 => (OpCode::STY, AddressingMode::zero_page_x(self), 4),
-      0x8C => (OpCode::STY, AddressingMode::absolute(self), 4),
-      _ => Err(CpuError::InvalidOpCode(opcode))?,
-    };
-
-    let mode = mode?;
+    let (opcode, mode, cycles) = self.lookup(opcode)?;
+
+    let mode = match mode {
+      _ if opcode == OpCode::JSR => self.jump_to_subroutine(),
+      Mode::Accumulator => AddressingMode::accumulator(self),
+      Mode::Implied => AddressingMode::implied(self),
+      Mode::Immediate => AddressingMode::immediate(self),
+      Mode::Relative => AddressingMode::relative(self),
+      Mode::ZeroPage => AddressingMode::zero_page(self),
+      Mode::ZeroPageX => AddressingMode::zero_page_x(self),
+      Mode::ZeroPageY => AddressingMode::zero_page_y(self),
+      Mode::Absolute => AddressingMode::absolute(self),
+      Mode::AbsoluteX => AddressingMode::absolute_x(self),
+      Mode::AbsoluteY => AddressingMode::absolute_y(self),
+      Mode::Indirect => AddressingMode::indirect(self),
+      Mode::IndirectX => AddressingMode::indirect_x(self),
+      Mode::IndirectY => AddressingMode::indirect_y(self),
+      Mode::ZeroPageIndirect => AddressingMode::zero_page_indirect(self),
+      Mode::AbsoluteIndexedIndirect => AddressingMode::absolute_indexed_indirect(self),
+      Mode::ZeroPageRelative => AddressingMode::zero_page_relative(self),
+    }?;
+
+    Ok(Instruction { opcode, mode, cycles })
+  }
+
+  /// Decodes the instruction at `address` without changing any CPU state.
+  /// Bytes are read with `read_only` set, so devices must not trigger side
+  /// effects such as acknowledging an interrupt.
+  pub fn peek_instruction(&mut self, address: u16) -> Result<Instruction, CpuError> {
+    let opcode = self.peek(address);
+    let (opcode, mode, cycles) = self.lookup(opcode)?;
+
+    let operand: Vec<u8> = (1..=mode.operand_len())
+      .map(|offset| self.peek(address.wrapping_add(offset)))
+      .collect();
+    let mode = AddressingMode::from_operand(mode, &operand);
 
     Ok(Instruction { opcode, mode, cycles })
   }
+
+  fn lookup(&self, opcode: u8) -> Result<(OpCode, Mode, u8), CpuError> {
+    lookup_variant(self.variant, self.undocumented_opcodes, opcode).ok_or(CpuError::InvalidOpCode(opcode))
+  }
+}
+
+/// Looks `opcode` up in the tables of `variant`. NMOS variants fall back to
+/// the undocumented table if `undocumented` is set, while every op code the
+/// 65C02 does not define is a `NOP`.
+pub(crate) fn lookup_variant(variant: CpuVariant, undocumented: bool, opcode: u8) -> Option<(OpCode, Mode, u8)> {
+  match variant.is_cmos() {
+    true => lookup_cmos(variant, opcode)
+      .or_else(|| lookup(opcode))
+      .or_else(|| Some(lookup_cmos_nop(opcode))),
+    false => lookup(opcode).or_else(|| lookup_undocumented(opcode).filter(|_| undocumented)),
+  }
+}
+
+/// Op code table shared by the decoder and the assembler. Returns the
+/// instruction, its addressing mode and its base cycle count.
+pub(crate) fn lookup(opcode: u8) -> Option<(OpCode, Mode, u8)> {
+  match opcode {
+    // ADC
+    0x69 => Some((OpCode::ADC, Mode::Immediate, 2)),
+    0x65 => Some((OpCode::ADC, Mode::ZeroPage, 3)),
+    0x75 => Some((OpCode::ADC, Mode::ZeroPageX, 4)),
+    0x6D => Some((OpCode::ADC, Mode::Absolute, 4)),
+    0x7D => Some((OpCode::ADC, Mode::AbsoluteX, 4)),
+    0x79 => Some((OpCode::ADC, Mode::AbsoluteY, 4)),
+    0x61 => Some((OpCode::ADC, Mode::IndirectX, 6)),
+    0x71 => Some((OpCode::ADC, Mode::IndirectY, 5)),
+    // AND
+    0x29 => Some((OpCode::AND, Mode::Immediate, 2)),
+    0x25 => Some((OpCode::AND, Mode::ZeroPage, 3)),
+    0x35 => Some((OpCode::AND, Mode::ZeroPageX, 4)),
+    0x2D => Some((OpCode::AND, Mode::Absolute, 4)),
+    0x3D => Some((OpCode::AND, Mode::AbsoluteX, 4)),
+    0x39 => Some((OpCode::AND, Mode::AbsoluteY, 4)),
+    0x21 => Some((OpCode::AND, Mode::IndirectX, 6)),
+    0x31 => Some((OpCode::AND, Mode::IndirectY, 5)),
+    // ASL
+    0x0A => Some((OpCode::ASL, Mode::Accumulator, 2)),
+    0x06 => Some((OpCode::ASL, Mode::ZeroPage, 5)),
+    0x16 => Some((OpCode::ASL, Mode::ZeroPageX, 6)),
+    0x0E => Some((OpCode::ASL, Mode::Absolute, 6)),
+    0x1E => Some((OpCode::ASL, Mode::AbsoluteX, 7)),
+    // BIT
+    0x24 => Some((OpCode::BIT, Mode::ZeroPage, 3)),
+    0x2C => Some((OpCode::BIT, Mode::Absolute, 4)),
+    // BRANCH
+    0x10 => Some((OpCode::BPL, Mode::Relative, 2)),
+    0x30 => Some((OpCode::BMI, Mode::Relative, 2)),
+    0x50 => Some((OpCode::BVC, Mode::Relative, 2)),
+    0x70 => Some((OpCode::BVS, Mode::Relative, 2)),
+    0x90 => Some((OpCode::BCC, Mode::Relative, 2)),
+    0xB0 => Some((OpCode::BCS, Mode::Relative, 2)),
+    0xD0 => Some((OpCode::BNE, Mode::Relative, 2)),
+    0xF0 => Some((OpCode::BEQ, Mode::Relative, 2)),
+    // BREAK
+    0x00 => Some((OpCode::BRK, Mode::Implied, 7)),
+    // CMP
+    0xC9 => Some((OpCode::CMP, Mode::Immediate, 2)),
+    0xC5 => Some((OpCode::CMP, Mode::ZeroPage, 3)),
+    0xD5 => Some((OpCode::CMP, Mode::ZeroPageX, 4)),
+    0xCD => Some((OpCode::CMP, Mode::Absolute, 4)),
+    0xDD => Some((OpCode::CMP, Mode::AbsoluteX, 4)),
+    0xD9 => Some((OpCode::CMP, Mode::AbsoluteY, 4)),
+    0xC1 => Some((OpCode::CMP, Mode::IndirectX, 6)),
+    0xD1 => Some((OpCode::CMP, Mode::IndirectY, 5)),
+    // CPX
+    0xE0 => Some((OpCode::CPX, Mode::Immediate, 2)),
+    0xE4 => Some((OpCode::CPX, Mode::ZeroPage, 3)),
+    0xEC => Some((OpCode::CPX, Mode::Absolute, 4)),
+    // CPY
+    0xC0 => Some((OpCode::CPY, Mode::Immediate, 2)),
+    0xC4 => Some((OpCode::CPY, Mode::ZeroPage, 3)),
+    0xCC => Some((OpCode::CPY, Mode::Absolute, 4)),
+    // DEC
+    0xC6 => Some((OpCode::DEC, Mode::ZeroPage, 5)),
+    0xD6 => Some((OpCode::DEC, Mode::ZeroPageX, 6)),
+    0xCE => Some((OpCode::DEC, Mode::Absolute, 6)),
+    0xDE => Some((OpCode::DEC, Mode::AbsoluteX, 7)),
+    // EOR
+    0x49 => Some((OpCode::EOR, Mode::Immediate, 2)),
+    0x45 => Some((OpCode::EOR, Mode::ZeroPage, 3)),
+    0x55 => Some((OpCode::EOR, Mode::ZeroPageX, 4)),
+    0x4D => Some((OpCode::EOR, Mode::Absolute, 4)),
+    0x5D => Some((OpCode::EOR, Mode::AbsoluteX, 4)),
+    0x59 => Some((OpCode::EOR, Mode::AbsoluteY, 4)),
+    0x41 => Some((OpCode::EOR, Mode::IndirectX, 6)),
+    0x51 => Some((OpCode::EOR, Mode::IndirectY, 5)),
+    // FLAGS
+    0x18 => Some((OpCode::CLC, Mode::Implied, 2)),
+    0x38 => Some((OpCode::SEC, Mode::Implied, 2)),
+    0x58 => Some((OpCode::CLI, Mode::Implied, 2)),
+    0x78 => Some((OpCode::SEI, Mode::Implied, 2)),
+    0xB8 => Some((OpCode::CLV, Mode::Implied, 2)),
+    0xD8 => Some((OpCode::CLD, Mode::Implied, 2)),
+    0xF8 => Some((OpCode::SED, Mode::Implied, 2)),
+    // INC
+    0xE6 => Some((OpCode::INC, Mode::ZeroPage, 5)),
+    0xF6 => Some((OpCode::INC, Mode::ZeroPageX, 6)),
+    0xEE => Some((OpCode::INC, Mode::Absolute, 6)),
+    0xFE => Some((OpCode::INC, Mode::AbsoluteX, 7)),
+    // JMP
+    0x4C => Some((OpCode::JMP, Mode::Absolute, 3)),
+    0x6C => Some((OpCode::JMP, Mode::Indirect, 5)),
+    // JSR
+    0x20 => Some((OpCode::JSR, Mode::Absolute, 6)),
+    // LDA
+    0xA9 => Some((OpCode::LDA, Mode::Immediate, 2)),
+    0xA5 => Some((OpCode::LDA, Mode::ZeroPage, 3)),
+    0xB5 => Some((OpCode::LDA, Mode::ZeroPageX, 4)),
+    0xAD => Some((OpCode::LDA, Mode::Absolute, 4)),
+    0xBD => Some((OpCode::LDA, Mode::AbsoluteX, 4)),
+    0xB9 => Some((OpCode::LDA, Mode::AbsoluteY, 4)),
+    0xA1 => Some((OpCode::LDA, Mode::IndirectX, 6)),
+    0xB1 => Some((OpCode::LDA, Mode::IndirectY, 5)),
+    // LDX
+    0xA2 => Some((OpCode::LDX, Mode::Immediate, 2)),
+    0xA6 => Some((OpCode::LDX, Mode::ZeroPage, 3)),
+    0xB6 => Some((OpCode::LDX, Mode::ZeroPageY, 4)),
+    0xAE => Some((OpCode::LDX, Mode::Absolute, 4)),
+    0xBE => Some((OpCode::LDX, Mode::AbsoluteY, 4)),
+    // LDY
+    0xA0 => Some((OpCode::LDY, Mode::Immediate, 2)),
+    0xA4 => Some((OpCode::LDY, Mode::ZeroPage, 3)),
+    0xB4 => Some((OpCode::LDY, Mode::ZeroPageX, 4)),
+    0xAC => Some((OpCode::LDY, Mode::Absolute, 4)),
+    0xBC => Some((OpCode::LDY, Mode::AbsoluteX, 4)),
+    // LSR
+    0x4A => Some((OpCode::LSR, Mode::Accumulator, 2)),
+    0x46 => Some((OpCode::LSR, Mode::ZeroPage, 5)),
+    0x56 => Some((OpCode::LSR, Mode::ZeroPageX, 6)),
+    0x4E => Some((OpCode::LSR, Mode::Absolute, 6)),
+    0x5E => Some((OpCode::LSR, Mode::AbsoluteX, 7)),
+    // NOP
+    0xEA => Some((OpCode::NOP, Mode::Implied, 2)),
+    // ORA
+    0x09 => Some((OpCode::ORA, Mode::Immediate, 2)),
+    0x05 => Some((OpCode::ORA, Mode::ZeroPage, 3)),
+    0x15 => Some((OpCode::ORA, Mode::ZeroPageX, 4)),
+    0x0D => Some((OpCode::ORA, Mode::Absolute, 4)),
+    0x1D => Some((OpCode::ORA, Mode::AbsoluteX, 4)),
+    0x19 => Some((OpCode::ORA, Mode::AbsoluteY, 4)),
+    0x01 => Some((OpCode::ORA, Mode::IndirectX, 6)),
+    0x11 => Some((OpCode::ORA, Mode::IndirectY, 5)),
+    // REGISTER
+    0xAA => Some((OpCode::TAX, Mode::Implied, 2)),
+    0x8A => Some((OpCode::TXA, Mode::Implied, 2)),
+    0xCA => Some((OpCode::DEX, Mode::Implied, 2)),
+    0xE8 => Some((OpCode::INX, Mode::Implied, 2)),
+    0xA8 => Some((OpCode::TAY, Mode::Implied, 2)),
+    0x98 => Some((OpCode::TYA, Mode::Implied, 2)),
+    0x88 => Some((OpCode::DEY, Mode::Implied, 2)),
+    0xC8 => Some((OpCode::INY, Mode::Implied, 2)),
+    // ROL
+    0x2A => Some((OpCode::ROL, Mode::Accumulator, 2)),
+    0x26 => Some((OpCode::ROL, Mode::ZeroPage, 5)),
+    0x36 => Some((OpCode::ROL, Mode::ZeroPageX, 6)),
+    0x2E => Some((OpCode::ROL, Mode::Absolute, 6)),
+    0x3E => Some((OpCode::ROL, Mode::AbsoluteX, 7)),
+    // ROR
+    0x6A => Some((OpCode::ROR, Mode::Accumulator, 2)),
+    0x66 => Some((OpCode::ROR, Mode::ZeroPage, 5)),
+    0x76 => Some((OpCode::ROR, Mode::ZeroPageX, 6)),
+    0x6E => Some((OpCode::ROR, Mode::Absolute, 6)),
+    0x7E => Some((OpCode::ROR, Mode::AbsoluteX, 7)),
+    // RTI
+    0x40 => Some((OpCode::RTI, Mode::Implied, 6)),
+    // RTS
+    0x60 => Some((OpCode::RTS, Mode::Implied, 6)),
+    // SBC
+    0xE9 => Some((OpCode::SBC, Mode::Immediate, 2)),
+    0xE5 => Some((OpCode::SBC, Mode::ZeroPage, 3)),
+    0xF5 => Some((OpCode::SBC, Mode::ZeroPageX, 4)),
+    0xED => Some((OpCode::SBC, Mode::Absolute, 4)),
+    0xFD => Some((OpCode::SBC, Mode::AbsoluteX, 4)),
+    0xF9 => Some((OpCode::SBC, Mode::AbsoluteY, 4)),
+    0xE1 => Some((OpCode::SBC, Mode::IndirectX, 6)),
+    0xF1 => Some((OpCode::SBC, Mode::IndirectY, 5)),
+    // STA
+    0x85 => Some((OpCode::STA, Mode::ZeroPage, 3)),
+    0x95 => Some((OpCode::STA, Mode::ZeroPageX, 4)),
+    0x8D => Some((OpCode::STA, Mode::Absolute, 4)),
+    0x9D => Some((OpCode::STA, Mode::AbsoluteX, 5)),
+    0x99 => Some((OpCode::STA, Mode::AbsoluteY, 5)),
+    0x81 => Some((OpCode::STA, Mode::IndirectX, 6)),
+    0x91 => Some((OpCode::STA, Mode::IndirectY, 6)),
+    // STACK
+    0x9A => Some((OpCode::TXS, Mode::Implied, 2)),
+    0xBA => Some((OpCode::TSX, Mode::Implied, 2)),
+    0x48 => Some((OpCode::PHA, Mode::Implied, 3)),
+    0x68 => Some((OpCode::PLA, Mode::Implied, 4)),
+    0x08 => Some((OpCode::PHP, Mode::Implied, 3)),
+    0x28 => Some((OpCode::PLP, Mode::Implied, 4)),
+    // STX
+    0x86 => Some((OpCode::STX, Mode::ZeroPage, 3)),
+    0x96 => Some((OpCode::STX, Mode::ZeroPageY, 4)),
+    0x8E => Some((OpCode::STX, Mode::Absolute, 4)),
+    // STY
+    0x84 => Some((OpCode::STY, Mode::ZeroPage, 3)),
+    0x94 => Some((OpCode::STY, Mode::ZeroPageX, 4)),
+    0x8C => Some((OpCode::STY, Mode::Absolute, 4)),
+    _ => None,
+  }
+}
+
+/// Op codes the NMOS 6502 executes although they were never documented
+pub(crate) fn lookup_undocumented(opcode: u8) -> Option<(OpCode, Mode, u8)> {
+  match opcode {
+    // ALR
+    0x4B => Some((OpCode::ALR, Mode::Immediate, 2)),
+    // ANC
+    0x0B | 0x2B => Some((OpCode::ANC, Mode::Immediate, 2)),
+    // ANE
+    0x8B => Some((OpCode::ANE, Mode::Immediate, 2)),
+    // ARR
+    0x6B => Some((OpCode::ARR, Mode::Immediate, 2)),
+    // DCP
+    0xC7 => Some((OpCode::DCP, Mode::ZeroPage, 5)),
+    0xD7 => Some((OpCode::DCP, Mode::ZeroPageX, 6)),
+    0xCF => Some((OpCode::DCP, Mode::Absolute, 6)),
+    0xDF => Some((OpCode::DCP, Mode::AbsoluteX, 7)),
+    0xDB => Some((OpCode::DCP, Mode::AbsoluteY, 7)),
+    0xC3 => Some((OpCode::DCP, Mode::IndirectX, 8)),
+    0xD3 => Some((OpCode::DCP, Mode::IndirectY, 8)),
+    // ISC
+    0xE7 => Some((OpCode::ISC, Mode::ZeroPage, 5)),
+    0xF7 => Some((OpCode::ISC, Mode::ZeroPageX, 6)),
+    0xEF => Some((OpCode::ISC, Mode::Absolute, 6)),
+    0xFF => Some((OpCode::ISC, Mode::AbsoluteX, 7)),
+    0xFB => Some((OpCode::ISC, Mode::AbsoluteY, 7)),
+    0xE3 => Some((OpCode::ISC, Mode::IndirectX, 8)),
+    0xF3 => Some((OpCode::ISC, Mode::IndirectY, 8)),
+    // JAM
+    0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xB2 | 0xD2 | 0xF2 => {
+      Some((OpCode::JAM, Mode::Implied, 2))
+    }
+    // LAS
+    0xBB => Some((OpCode::LAS, Mode::AbsoluteY, 4)),
+    // LAX
+    0xA7 => Some((OpCode::LAX, Mode::ZeroPage, 3)),
+    0xB7 => Some((OpCode::LAX, Mode::ZeroPageY, 4)),
+    0xAF => Some((OpCode::LAX, Mode::Absolute, 4)),
+    0xBF => Some((OpCode::LAX, Mode::AbsoluteY, 4)),
+    0xA3 => Some((OpCode::LAX, Mode::IndirectX, 6)),
+    0xB3 => Some((OpCode::LAX, Mode::IndirectY, 5)),
+    // LXA
+    0xAB => Some((OpCode::LXA, Mode::Immediate, 2)),
+    // NOP
+    0x1A | 0x3A | 0x5A | 0x7A | 0xDA | 0xFA => Some((OpCode::NOP, Mode::Implied, 2)),
+    0x80 | 0x82 | 0x89 | 0xC2 | 0xE2 => Some((OpCode::NOP, Mode::Immediate, 2)),
+    0x04 | 0x44 | 0x64 => Some((OpCode::NOP, Mode::ZeroPage, 3)),
+    0x14 | 0x34 | 0x54 | 0x74 | 0xD4 | 0xF4 => Some((OpCode::NOP, Mode::ZeroPageX, 4)),
+    0x0C => Some((OpCode::NOP, Mode::Absolute, 4)),
+    0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC => Some((OpCode::NOP, Mode::AbsoluteX, 4)),
+    // RLA
+    0x27 => Some((OpCode::RLA, Mode::ZeroPage, 5)),
+    0x37 => Some((OpCode::RLA, Mode::ZeroPageX, 6)),
+    0x2F => Some((OpCode::RLA, Mode::Absolute, 6)),
+    0x3F => Some((OpCode::RLA, Mode::AbsoluteX, 7)),
+    0x3B => Some((OpCode::RLA, Mode::AbsoluteY, 7)),
+    0x23 => Some((OpCode::RLA, Mode::IndirectX, 8)),
+    0x33 => Some((OpCode::RLA, Mode::IndirectY, 8)),
+    // RRA
+    0x67 => Some((OpCode::RRA, Mode::ZeroPage, 5)),
+    0x77 => Some((OpCode::RRA, Mode::ZeroPageX, 6)),
+    0x6F => Some((OpCode::RRA, Mode::Absolute, 6)),
+    0x7F => Some((OpCode::RRA, Mode::AbsoluteX, 7)),
+    0x7B => Some((OpCode::RRA, Mode::AbsoluteY, 7)),
+    0x63 => Some((OpCode::RRA, Mode::IndirectX, 8)),
+    0x73 => Some((OpCode::RRA, Mode::IndirectY, 8)),
+    // SAX
+    0x87 => Some((OpCode::SAX, Mode::ZeroPage, 3)),
+    0x97 => Some((OpCode::SAX, Mode::ZeroPageY, 4)),
+    0x8F => Some((OpCode::SAX, Mode::Absolute, 4)),
+    0x83 => Some((OpCode::SAX, Mode::IndirectX, 6)),
+    // SBC
+    0xEB => Some((OpCode::SBC, Mode::Immediate, 2)),
+    // SBX
+    0xCB => Some((OpCode::SBX, Mode::Immediate, 2)),
+    // SHA
+    0x9F => Some((OpCode::SHA, Mode::AbsoluteY, 5)),
+    0x93 => Some((OpCode::SHA, Mode::IndirectY, 6)),
+    // SHX
+    0x9E => Some((OpCode::SHX, Mode::AbsoluteY, 5)),
+    // SHY
+    0x9C => Some((OpCode::SHY, Mode::AbsoluteX, 5)),
+    // SLO
+    0x07 => Some((OpCode::SLO, Mode::ZeroPage, 5)),
+    0x17 => Some((OpCode::SLO, Mode::ZeroPageX, 6)),
+    0x0F => Some((OpCode::SLO, Mode::Absolute, 6)),
+    0x1F => Some((OpCode::SLO, Mode::AbsoluteX, 7)),
+    0x1B => Some((OpCode::SLO, Mode::AbsoluteY, 7)),
+    0x03 => Some((OpCode::SLO, Mode::IndirectX, 8)),
+    0x13 => Some((OpCode::SLO, Mode::IndirectY, 8)),
+    // SRE
+    0x47 => Some((OpCode::SRE, Mode::ZeroPage, 5)),
+    0x57 => Some((OpCode::SRE, Mode::ZeroPageX, 6)),
+    0x4F => Some((OpCode::SRE, Mode::Absolute, 6)),
+    0x5F => Some((OpCode::SRE, Mode::AbsoluteX, 7)),
+    0x5B => Some((OpCode::SRE, Mode::AbsoluteY, 7)),
+    0x43 => Some((OpCode::SRE, Mode::IndirectX, 8)),
+    0x53 => Some((OpCode::SRE, Mode::IndirectY, 8)),
+    // TAS
+    0x9B => Some((OpCode::TAS, Mode::AbsoluteY, 5)),
+    _ => None,
+  }
+}
+
+/// Op codes the 65C02 adds or changes compared to the NMOS 6502
+pub(crate) fn lookup_cmos(variant: CpuVariant, opcode: u8) -> Option<(OpCode, Mode, u8)> {
+  match opcode {
+    // (zp)
+    0x12 => Some((OpCode::ORA, Mode::ZeroPageIndirect, 5)),
+    0x32 => Some((OpCode::AND, Mode::ZeroPageIndirect, 5)),
+    0x52 => Some((OpCode::EOR, Mode::ZeroPageIndirect, 5)),
+    0x72 => Some((OpCode::ADC, Mode::ZeroPageIndirect, 5)),
+    0x92 => Some((OpCode::STA, Mode::ZeroPageIndirect, 5)),
+    0xB2 => Some((OpCode::LDA, Mode::ZeroPageIndirect, 5)),
+    0xD2 => Some((OpCode::CMP, Mode::ZeroPageIndirect, 5)),
+    0xF2 => Some((OpCode::SBC, Mode::ZeroPageIndirect, 5)),
+    // BIT
+    0x89 => Some((OpCode::BIT, Mode::Immediate, 2)),
+    0x34 => Some((OpCode::BIT, Mode::ZeroPageX, 4)),
+    0x3C => Some((OpCode::BIT, Mode::AbsoluteX, 4)),
+    // BRA
+    0x80 => Some((OpCode::BRA, Mode::Relative, 3)),
+    // DEC, INC
+    0x3A => Some((OpCode::DEC, Mode::Accumulator, 2)),
+    0x1A => Some((OpCode::INC, Mode::Accumulator, 2)),
+    // ASL, LSR, ROL, ROR only take the fix-up cycle when crossing a page
+    0x1E => Some((OpCode::ASL, Mode::AbsoluteX, 6)),
+    0x5E => Some((OpCode::LSR, Mode::AbsoluteX, 6)),
+    0x3E => Some((OpCode::ROL, Mode::AbsoluteX, 6)),
+    0x7E => Some((OpCode::ROR, Mode::AbsoluteX, 6)),
+    // JMP
+    0x6C => Some((OpCode::JMP, Mode::Indirect, 6)),
+    0x7C => Some((OpCode::JMP, Mode::AbsoluteIndexedIndirect, 6)),
+    // STACK
+    0xDA => Some((OpCode::PHX, Mode::Implied, 3)),
+    0x5A => Some((OpCode::PHY, Mode::Implied, 3)),
+    0xFA => Some((OpCode::PLX, Mode::Implied, 4)),
+    0x7A => Some((OpCode::PLY, Mode::Implied, 4)),
+    // STZ
+    0x64 => Some((OpCode::STZ, Mode::ZeroPage, 3)),
+    0x74 => Some((OpCode::STZ, Mode::ZeroPageX, 4)),
+    0x9C => Some((OpCode::STZ, Mode::Absolute, 4)),
+    0x9E => Some((OpCode::STZ, Mode::AbsoluteX, 5)),
+    // TRB, TSB
+    0x14 => Some((OpCode::TRB, Mode::ZeroPage, 5)),
+    0x1C => Some((OpCode::TRB, Mode::Absolute, 6)),
+    0x04 => Some((OpCode::TSB, Mode::ZeroPage, 5)),
+    0x0C => Some((OpCode::TSB, Mode::Absolute, 6)),
+    // BBR, BBS
+    0x0F if variant.has_bit_instructions() => Some((OpCode::BBR0, Mode::ZeroPageRelative, 5)),
+    0x1F if variant.has_bit_instructions() => Some((OpCode::BBR1, Mode::ZeroPageRelative, 5)),
+    0x2F if variant.has_bit_instructions() => Some((OpCode::BBR2, Mode::ZeroPageRelative, 5)),
+    0x3F if variant.has_bit_instructions() => Some((OpCode::BBR3, Mode::ZeroPageRelative, 5)),
+    0x4F if variant.has_bit_instructions() => Some((OpCode::BBR4, Mode::ZeroPageRelative, 5)),
+    0x5F if variant.has_bit_instructions() => Some((OpCode::BBR5, Mode::ZeroPageRelative, 5)),
+    0x6F if variant.has_bit_instructions() => Some((OpCode::BBR6, Mode::ZeroPageRelative, 5)),
+    0x7F if variant.has_bit_instructions() => Some((OpCode::BBR7, Mode::ZeroPageRelative, 5)),
+    0x8F if variant.has_bit_instructions() => Some((OpCode::BBS0, Mode::ZeroPageRelative, 5)),
+    0x9F if variant.has_bit_instructions() => Some((OpCode::BBS1, Mode::ZeroPageRelative, 5)),
+    0xAF if variant.has_bit_instructions() => Some((OpCode::BBS2, Mode::ZeroPageRelative, 5)),
+    0xBF if variant.has_bit_instructions() => Some((OpCode::BBS3, Mode::ZeroPageRelative, 5)),
+    0xCF if variant.has_bit_instructions() => Some((OpCode::BBS4, Mode::ZeroPageRelative, 5)),
+    0xDF if variant.has_bit_instructions() => Some((OpCode::BBS5, Mode::ZeroPageRelative, 5)),
+    0xEF if variant.has_bit_instructions() => Some((OpCode::BBS6, Mode::ZeroPageRelative, 5)),
+    0xFF if variant.has_bit_instructions() => Some((OpCode::BBS7, Mode::ZeroPageRelative, 5)),
+    // RMB, SMB
+    0x07 if variant.has_bit_instructions() => Some((OpCode::RMB0, Mode::ZeroPage, 5)),
+    0x17 if variant.has_bit_instructions() => Some((OpCode::RMB1, Mode::ZeroPage, 5)),
+    0x27 if variant.has_bit_instructions() => Some((OpCode::RMB2, Mode::ZeroPage, 5)),
+    0x37 if variant.has_bit_instructions() => Some((OpCode::RMB3, Mode::ZeroPage, 5)),
+    0x47 if variant.has_bit_instructions() => Some((OpCode::RMB4, Mode::ZeroPage, 5)),
+    0x57 if variant.has_bit_instructions() => Some((OpCode::RMB5, Mode::ZeroPage, 5)),
+    0x67 if variant.has_bit_instructions() => Some((OpCode::RMB6, Mode::ZeroPage, 5)),
+    0x77 if variant.has_bit_instructions() => Some((OpCode::RMB7, Mode::ZeroPage, 5)),
+    0x87 if variant.has_bit_instructions() => Some((OpCode::SMB0, Mode::ZeroPage, 5)),
+    0x97 if variant.has_bit_instructions() => Some((OpCode::SMB1, Mode::ZeroPage, 5)),
+    0xA7 if variant.has_bit_instructions() => Some((OpCode::SMB2, Mode::ZeroPage, 5)),
+    0xB7 if variant.has_bit_instructions() => Some((OpCode::SMB3, Mode::ZeroPage, 5)),
+    0xC7 if variant.has_bit_instructions() => Some((OpCode::SMB4, Mode::ZeroPage, 5)),
+    0xD7 if variant.has_bit_instructions() => Some((OpCode::SMB5, Mode::ZeroPage, 5)),
+    0xE7 if variant.has_bit_instructions() => Some((OpCode::SMB6, Mode::ZeroPage, 5)),
+    0xF7 if variant.has_bit_instructions() => Some((OpCode::SMB7, Mode::ZeroPage, 5)),
+    // WAI, STP
+    0xCB if variant.has_wait_and_stop() => Some((OpCode::WAI, Mode::Implied, 3)),
+    0xDB if variant.has_wait_and_stop() => Some((OpCode::STP, Mode::Implied, 3)),
+    _ => None,
+  }
+}
+
+/// Size and timing of the `NOP` that an op code the 65C02 does not define
+/// executes as
+pub(crate) fn lookup_cmos_nop(opcode: u8) -> (OpCode, Mode, u8) {
+  match opcode {
+    0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xC2 | 0xE2 => (OpCode::NOP, Mode::Immediate, 2),
+    0x44 => (OpCode::NOP, Mode::ZeroPage, 3),
+    0x54 | 0xD4 | 0xF4 => (OpCode::NOP, Mode::ZeroPageX, 4),
+    0x5C => (OpCode::NOP, Mode::Absolute, 8),
+    0xDC | 0xFC => (OpCode::NOP, Mode::Absolute, 4),
+    _ => (OpCode::NOP, Mode::Implied, 1),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{cell::RefCell, ops::RangeInclusive, rc::Rc};
+
+  use crate::core::{
+    bus::BusItem,
+    cpu::Cpu,
+    instruction::{AddressingMode, Instruction, OpCode},
+  };
+
+  /// Memory that records every read along with its `read_only` flag
+  struct Recorder {
+    range: RangeInclusive<u16>,
+    memory: Vec<u8>,
+    reads: Rc<RefCell<Vec<(u16, bool)>>>,
+  }
+
+  impl BusItem for Recorder {
+    fn address_range(&self) -> &RangeInclusive<u16> {
+      &self.range
+    }
+
+    fn read(&mut self, address: u16, read_only: bool) -> u8 {
+      self.reads.borrow_mut().push((address, read_only));
+      self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+      self.memory[address as usize] = value;
+    }
+  }
+
+  #[test]
+  fn peeking_leaves_the_cpu_and_devices_alone() {
+    let reads = Rc::new(RefCell::new(Vec::new()));
+    let mut memory = vec![0xEA; 0x10000];
+    // LDA $1234 at the reset vector target
+    memory[0x0200..0x0203].copy_from_slice(&[0xAD, 0x34, 0x12]);
+    memory[0xFFFC..0xFFFE].copy_from_slice(&[0x00, 0x02]);
+
+    let mut cpu = Cpu::new(1.0);
+    cpu.connect(Recorder {
+      range: 0x0000..=0xFFFF,
+      memory,
+      reads: reads.clone(),
+    });
+    cpu.reset();
+    cpu.step_instruction().unwrap();
+    let registers = cpu.registers();
+    let cycles = cpu.total_cycles();
+    reads.borrow_mut().clear();
+
+    assert_eq!(cpu.peek_instruction(0x0200).unwrap(), Instruction {
+      opcode: OpCode::LDA,
+      mode: AddressingMode::Absolute(0x1234),
+      cycles: 4,
+    });
+    assert_eq!(cpu.peek(0x1234), 0xEA);
+
+    assert_eq!(cpu.registers(), registers);
+    assert_eq!(cpu.total_cycles(), cycles);
+    assert_eq!(*reads.borrow(), [
+      (0x0200, true),
+      (0x0201, true),
+      (0x0202, true),
+      (0x1234, true)
+    ]);
+  }
 }
@@ -0,0 +1,183 @@
+//! Runs Klaus Dormann's functional and decimal tests from
+//! https://github.com/Klaus2m5/6502_65C02_functional_tests against the NMOS
+//! 6502. The binaries are not bundled: put `6502_functional_test.bin` and
+//! `6502_decimal_test.bin` in `tests/dormann`, or point `DORMANN_TESTS` at
+//! the directory holding them, then run `cargo test dormann -- --ignored`.
+//!
+//! The functional test ends in a trap, an instruction that jumps or branches
+//! to itself, and where the CPU got stuck tells whether it passed. The
+//! decimal test ends with `STP`, which the NMOS 6502 does not have, and keeps
+//! its result in memory.
+
+use std::{
+  collections::VecDeque,
+  fmt::{Display, Formatter},
+  fs,
+};
+
+use crate::{
+  core::{
+    cpu::{Cpu, Registers},
+    fixtures,
+    ram::Ram,
+  },
+  disassembler::disassemble,
+  error::CpuError,
+};
+
+const DEFAULT_DIRECTORY: &str = "tests/dormann";
+
+/// Instructions kept for the report of where a test got stuck
+const HISTORY: usize = 20;
+
+/// Gives up on a test that has not trapped after this many instructions
+const LIMIT: u64 = 200_000_000;
+
+/// Where the functional test traps once every test passed, as assembled with
+/// its default configuration
+const FUNCTIONAL_SUCCESS: u16 = 0x3469;
+
+/// Where the decimal test keeps its result, `0` when every test passed
+const DECIMAL_ERROR: u16 = 0x000B;
+
+/// `STP` on the 65C02, which ends the decimal test
+const STOP: u8 = 0xDB;
+
+/// Where a test got stuck, with the instructions that led there
+struct Trap {
+  address: u16,
+  /// Whether the test ended with `STP` rather than a trap
+  stopped: bool,
+  instructions: u64,
+  history: VecDeque<Executed>,
+}
+
+/// An instruction and the registers before it ran
+struct Executed {
+  bytes: [u8; 3],
+  registers: Registers,
+}
+
+impl Display for Trap {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      f,
+      "{} at ${:04X} after {} instructions, last executed:",
+      if self.stopped { "stopped" } else { "trapped" },
+      self.address,
+      self.instructions
+    )?;
+    for executed in &self.history {
+      writeln!(f, "  {executed}")?;
+    }
+    Ok(())
+  }
+}
+
+impl Display for Executed {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let Registers {
+      a,
+      x,
+      y,
+      stack_ptr,
+      program_counter,
+      status,
+    } = self.registers;
+    let text = disassemble(&self.bytes, program_counter)
+      .next()
+      .map(|disassembled| disassembled.to_string())
+      .unwrap_or_default();
+    write!(
+      f,
+      "{program_counter:04X}  {text:<14}  A:{a:02X} X:{x:02X} Y:{y:02X} P:{status:02X} SP:{stack_ptr:02X}"
+    )
+  }
+}
+
+#[test]
+#[ignore = "needs the Dormann test binaries"]
+fn functional() {
+  let image = binary("6502_functional_test.bin");
+
+  let mut cpu = machine(&image, 0x0000);
+  let trap = run(&mut cpu, 0x0400).unwrap();
+  assert!(
+    !trap.stopped && trap.address == FUNCTIONAL_SUCCESS,
+    "expected a trap at ${FUNCTIONAL_SUCCESS:04X}, {trap}"
+  );
+}
+
+#[test]
+#[ignore = "needs the Dormann test binaries"]
+fn decimal() {
+  let image = binary("6502_decimal_test.bin");
+
+  // the test is assembled to run from `$0200`, but may come as a full image
+  let origin = if image.len() == 0x10000 { 0x0000 } else { 0x0200 };
+  let mut cpu = machine(&image, origin);
+  let trap = run(&mut cpu, 0x0200).unwrap();
+  assert!(trap.stopped, "expected `STP`, {trap}");
+  let error = cpu.peek(DECIMAL_ERROR);
+  assert!(error == 0x00, "ERROR is ${error:02X}, {trap}");
+}
+
+/// Reads a test binary, panicking if it is not present
+fn binary(name: &str) -> Vec<u8> {
+  let path = fixtures::directory("DORMANN_TESTS", DEFAULT_DIRECTORY).join(name);
+  fs::read(&path).unwrap_or_else(|error| panic!("`{}`: {error}", path.display()))
+}
+
+/// An NMOS 6502 with 64 KiB of RAM holding `image` at `origin`
+fn machine(image: &[u8], origin: u16) -> Cpu {
+  let mut ram = Ram::new(0x0000..=0xFFFF);
+  ram.write_all(origin, image);
+
+  let mut cpu = Cpu::new(1.0);
+  cpu.connect(ram);
+  cpu
+}
+
+/// Runs from `entry` until an instruction jumps or branches to itself, or
+/// until `STP`, which is not run
+fn run(cpu: &mut Cpu, entry: u16) -> Result<Trap, CpuError> {
+  cpu.reset();
+  cpu.set_registers(Registers {
+    program_counter: entry,
+    ..cpu.registers()
+  });
+  let mut history = VecDeque::with_capacity(HISTORY);
+
+  for instructions in 1..=LIMIT {
+    let registers = cpu.registers();
+    let address = registers.program_counter;
+    if cpu.peek(address) == STOP {
+      return Ok(Trap {
+        address,
+        stopped: true,
+        instructions: instructions - 1,
+        history,
+      });
+    }
+    if history.len() == HISTORY {
+      history.pop_front();
+    }
+    history.push_back(Executed {
+      bytes: [0, 1, 2].map(|offset| cpu.peek(address.wrapping_add(offset))),
+      registers,
+    });
+
+    cpu.step_instruction()?;
+
+    if cpu.registers().program_counter == address {
+      return Ok(Trap {
+        address,
+        stopped: false,
+        instructions,
+        history,
+      });
+    }
+  }
+
+  Err(CpuError::Other(format!("no trap after {LIMIT} instructions")))
+}
@@ -1,32 +1,130 @@
+use enumflags2::BitFlags;
+
 use crate::{
   core::{
     cpu::{Cpu, StatusFlags},
     instruction::{AddressingMode, Instruction, OpCode},
+    observer::CpuObserver,
   },
   error::CpuError,
 };
 
-impl Cpu {
-  /// Returns how many cycles needed for completion of instruction
-  pub(crate) fn execute(&mut self, instruction: Instruction) -> Result<u8, CpuError> {
-    let Instruction {
-      opcode,
-      mode,
-      mut cycles,
-    } = instruction;
+/// Bits of the accumulator that `ANE` and `LXA` treat as set. The real value
+/// varies between chips and with temperature; `$EE` is the common choice of
+/// emulators and test suites.
+const UNSTABLE_MAGIC: u8 = 0xEE;
+
+/// How an instruction accesses the memory its addressing mode points at
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+  Read,
+  Write,
+  Modify,
+  Other,
+}
+
+impl Access {
+  fn of(opcode: OpCode) -> Self {
+    match opcode {
+      OpCode::ADC
+      | OpCode::AND
+      | OpCode::BIT
+      | OpCode::CMP
+      | OpCode::CPX
+      | OpCode::CPY
+      | OpCode::EOR
+      | OpCode::LDA
+      | OpCode::LDX
+      | OpCode::LDY
+      | OpCode::NOP
+      | OpCode::ORA
+      | OpCode::SBC
+      | OpCode::ALR
+      | OpCode::ANC
+      | OpCode::ANE
+      | OpCode::ARR
+      | OpCode::LAS
+      | OpCode::LAX
+      | OpCode::LXA
+      | OpCode::SBX => Self::Read,
+      OpCode::STA
+      | OpCode::STX
+      | OpCode::STY
+      | OpCode::SAX
+      | OpCode::SHA
+      | OpCode::SHX
+      | OpCode::SHY
+      | OpCode::TAS
+      | OpCode::STZ => Self::Write,
+      OpCode::ASL
+      | OpCode::DEC
+      | OpCode::INC
+      | OpCode::LSR
+      | OpCode::ROL
+      | OpCode::ROR
+      | OpCode::DCP
+      | OpCode::ISC
+      | OpCode::RLA
+      | OpCode::RRA
+      | OpCode::SLO
+      | OpCode::SRE
+      | OpCode::TRB
+      | OpCode::TSB => Self::Modify,
+      OpCode::BBR0
+      | OpCode::BBR1
+      | OpCode::BBR2
+      | OpCode::BBR3
+      | OpCode::BBR4
+      | OpCode::BBR5
+      | OpCode::BBR6
+      | OpCode::BBR7
+      | OpCode::BBS0
+      | OpCode::BBS1
+      | OpCode::BBS2
+      | OpCode::BBS3
+      | OpCode::BBS4
+      | OpCode::BBS5
+      | OpCode::BBS6
+      | OpCode::BBS7 => Self::Read,
+      OpCode::RMB0
+      | OpCode::RMB1
+      | OpCode::RMB2
+      | OpCode::RMB3
+      | OpCode::RMB4
+      | OpCode::RMB5
+      | OpCode::RMB6
+      | OpCode::RMB7
+      | OpCode::SMB0
+      | OpCode::SMB1
+      | OpCode::SMB2
+      | OpCode::SMB3
+      | OpCode::SMB4
+      | OpCode::SMB5
+      | OpCode::SMB6
+      | OpCode::SMB7 => Self::Modify,
+      _ => Self::Other,
+    }
+  }
+}
+
+impl<O: CpuObserver> Cpu<O> {
+  /// Runs `instruction` once its op code and operand have been fetched. Each
+  /// bus cycle it takes is counted in `cycles` as it happens.
+  pub(crate) fn execute(&mut self, instruction: Instruction) -> Result<(), CpuError> {
+    let Instruction { opcode, mode, cycles } = instruction;
+    // undefined 65C02 op codes that take a single cycle do nothing but fetch
+    if cycles == 1 {
+      return Ok(());
+    }
 
-    let (data, address) = self.interpret_payload(&mode, &mut cycles);
+    let access = Access::of(opcode);
+    let address = self.effective_address(&mode, self.indexing(opcode, access));
+    let data = self.load(&mode, address, access);
 
     match opcode {
       OpCode::ADC => {
-        let input_carry = self.is_flag(StatusFlags::Carry);
-        let (result, carry) = self.a.carrying_add(data, input_carry);
-        let overflow = input_carry != carry; // https://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
-        self.set_flag(StatusFlags::Overflow, overflow);
-        self.set_flag(StatusFlags::Carry, carry);
-        self.set_flag(StatusFlags::Zero, result == 0x00);
-        self.set_flag(StatusFlags::Negative, (result & 0x80) == 0x80);
-        self.a = result;
+        self.add_with_carry(data);
+        self.decimal_penalty();
       }
       OpCode::AND => {
         self.a &= data;
@@ -34,78 +132,60 @@ impl Cpu {
         self.set_flag(StatusFlags::Negative, (self.a & 0x80) == 0x80);
       }
       OpCode::ASL => {
-        let shifted = (data as u16) << 1;
-        self.set_flag(StatusFlags::Carry, (shifted & 0xFF00) > 0);
-        self.set_flag(StatusFlags::Zero, (shifted & 0xFF00) > 0);
-        self.set_flag(StatusFlags::Negative, (shifted & 0x0080) == 0x0080);
-        if let AddressingMode::Implied | AddressingMode::Accumulator = mode {
-          self.a = shifted as u8;
-        } else {
-          self.write(address, shifted as u8);
-        }
+        let result = self.shift_left(data);
+        self.store(&mode, address, result);
       }
       OpCode::BCC => {
         if !self.is_flag(StatusFlags::Carry) {
-          cycles += 1;
-          let (addr, overflow) = self.program_counter.overflowing_add(address);
-          if overflow {
-            cycles += 1;
-          }
-          self.program_counter = addr;
+          self.branch(address);
         }
       }
       OpCode::BCS => {
         if self.is_flag(StatusFlags::Carry) {
-          self.branch(address, &mut cycles);
+          self.branch(address);
         }
       }
       OpCode::BEQ => {
         if self.is_flag(StatusFlags::Zero) {
-          self.branch(address, &mut cycles);
+          self.branch(address);
         }
       }
       OpCode::BIT => {
-        let temp = self.a & data;
-        self.set_flag(StatusFlags::Overflow, temp == 0x00);
-        self.set_flag(StatusFlags::Zero, data & (1 << 7) == (1 << 7));
-        self.set_flag(StatusFlags::Negative, data & (1 << 6) == (1 << 6));
+        self.set_flag(StatusFlags::Zero, (self.a & data) == 0x00);
+        // the 65C02 immediate form only has a result to set Z from
+        if !matches!(mode, AddressingMode::Immediate(_)) {
+          self.set_flag(StatusFlags::Negative, (data & 0x80) == 0x80);
+          self.set_flag(StatusFlags::Overflow, (data & 0x40) == 0x40);
+        }
       }
       OpCode::BMI => {
         if self.is_flag(StatusFlags::Negative) {
-          self.branch(address, &mut cycles);
+          self.branch(address);
         }
       }
       OpCode::BNE => {
         if !self.is_flag(StatusFlags::Zero) {
-          self.branch(address, &mut cycles);
+          self.branch(address);
         }
       }
       OpCode::BPL => {
         if !self.is_flag(StatusFlags::Negative) {
-          self.branch(address, &mut cycles);
+          self.branch(address);
         }
       }
       OpCode::BRK => {
-        self.set_flag(StatusFlags::Interrupt, true);
-        let [lo, hi] = self.program_counter.to_le_bytes();
-        self.push(hi);
-        self.push(lo);
-        self.set_flag(StatusFlags::Break, true);
-        self.push(self.status.bits());
-        self.set_flag(StatusFlags::Break, false);
-
-        let lo = self.read(Self::INTERRUPT_ADDRESS);
-        let hi = self.read(Self::INTERRUPT_ADDRESS + 1);
-        self.program_counter = u16::from_le_bytes([lo, hi]);
+        // the byte after `BRK` is skipped, so the return address is `BRK + 2`
+        self.program_counter = self.program_counter.wrapping_add(1);
+        self.enter_interrupt(Self::INTERRUPT_ADDRESS, true);
       }
       OpCode::BVC => {
         if !self.is_flag(StatusFlags::Overflow) {
-          self.branch(address, &mut cycles);
+          self.branch(address);
         }
       }
       OpCode::BVS => {
         if self.is_flag(StatusFlags::Overflow) {
-          self.branch(address, &mut cycles);
+          self.branch(address);
         }
       }
       OpCode::CLC => {
@@ -121,38 +201,26 @@ impl Cpu {
         self.set_flag(StatusFlags::Overflow, false);
       }
       OpCode::CMP => {
-        let temp = self.a - data;
-        self.set_flag(StatusFlags::Carry, self.a >= data);
-        self.set_flag(StatusFlags::Zero, temp == 0x00);
-        self.set_flag(StatusFlags::Negative, (temp & 0x80) == 0x80);
+        self.compare(self.a, data);
       }
       OpCode::CPX => {
-        let temp = self.x - data;
-        self.set_flag(StatusFlags::Carry, self.a >= data);
-        self.set_flag(StatusFlags::Zero, temp == 0x00);
-        self.set_flag(StatusFlags::Negative, (temp & 0x80) == 0x80);
+        self.compare(self.x, data);
       }
       OpCode::CPY => {
-        let temp = self.y - data;
-        self.set_flag(StatusFlags::Carry, self.a >= data);
-        self.set_flag(StatusFlags::Zero, temp == 0x00);
-        self.set_flag(StatusFlags::Negative, (temp & 0x80) == 0x80);
+        self.compare(self.y, data);
       }
       OpCode::DEC => {
-        let temp = data - 1;
-        self.write(address, temp);
-        self.set_flag(StatusFlags::Zero, temp == 0x00);
-        self.set_flag(StatusFlags::Negative, (temp & 0x80) == 0x80);
+        let result = data.wrapping_sub(1);
+        self.store(&mode, address, result);
+        self.set_flags_from(result);
       }
       OpCode::DEX => {
-        self.x -= 1;
-        self.set_flag(StatusFlags::Zero, self.x == 0x00);
-        self.set_flag(StatusFlags::Negative, (self.x & 0x80) == 0x80);
+        self.x = self.x.wrapping_sub(1);
+        self.set_flags_from(self.x);
       }
       OpCode::DEY => {
-        self.y -= 1;
-        self.set_flag(StatusFlags::Zero, self.y == 0x00);
-        self.set_flag(StatusFlags::Negative, (self.y & 0x80) == 0x80);
+        self.y = self.y.wrapping_sub(1);
+        self.set_flags_from(self.y);
       }
       OpCode::EOR => {
         self.a ^= data;
@@ -160,29 +228,23 @@ impl Cpu {
         self.set_flag(StatusFlags::Negative, (self.a & 0x80) == 0x80);
       }
       OpCode::INC => {
-        let temp = data + 1;
-        self.write(address, temp);
-        self.set_flag(StatusFlags::Zero, temp == 0x00);
-        self.set_flag(StatusFlags::Negative, (temp & 0x80) == 0x80);
+        let result = data.wrapping_add(1);
+        self.store(&mode, address, result);
+        self.set_flags_from(result);
       }
       OpCode::INX => {
-        self.x += 1;
-        self.set_flag(StatusFlags::Zero, self.x == 0x00);
-        self.set_flag(StatusFlags::Negative, (self.x & 0x80) == 0x80);
+        self.x = self.x.wrapping_add(1);
+        self.set_flags_from(self.x);
       }
       OpCode::INY => {
-        self.y += 1;
-        self.set_flag(StatusFlags::Zero, self.y == 0x00);
-        self.set_flag(StatusFlags::Negative, (self.y & 0x80) == 0x80);
+        self.y = self.y.wrapping_add(1);
+        self.set_flags_from(self.y);
       }
       OpCode::JMP => {
         self.program_counter = address;
       }
       OpCode::JSR => {
-        // self.program_counter -= 1;
-        let [lo, hi] = self.program_counter.to_le_bytes();
-        self.push(hi);
-        self.push(lo);
+        // the return address was pushed while the operand was fetched
         self.program_counter = address;
       }
       OpCode::LDA => {
@@ -201,15 +263,8 @@ impl Cpu {
         self.set_flag(StatusFlags::Negative, (self.y & 0x80) == 0x80);
       }
       OpCode::LSR => {
-        self.set_flag(StatusFlags::Carry, (data & 0x01) == 0x01);
-        let temp = data >> 1;
-        self.set_flag(StatusFlags::Zero, temp == 0x00);
-        self.set_flag(StatusFlags::Negative, (temp & 0x80) == 0x80);
-        if let AddressingMode::Implied | AddressingMode::Accumulator = mode {
-          self.a = temp;
-        } else {
-          self.write(address, temp);
-        }
+        let result = self.shift_right(data);
+        self.store(&mode, address, result);
       }
       OpCode::NOP => (),
       OpCode::ORA => {
@@ -222,68 +277,47 @@ impl Cpu {
       }
       OpCode::PHP => {
         self.push((self.status | StatusFlags::Unused | StatusFlags::Break).bits());
-        self.set_flag(StatusFlags::Break, false);
-        self.set_flag(StatusFlags::Unused, false);
       }
       OpCode::PLA => {
+        self.dummy_read(self.stack_address());
         self.a = self.pop();
         self.set_flag(StatusFlags::Zero, self.a == 0x00);
         self.set_flag(StatusFlags::Negative, (self.a & 0x80) == 0x80);
       }
       OpCode::PLP => {
-        self.status = self.pop().try_into().map_err(|e| CpuError::Other(format!("{e}")))?;
-        self.set_flag(StatusFlags::Unused, true);
+        self.dummy_read(self.stack_address());
+        let status = self.pop();
+        self.pull_status(status);
       }
       OpCode::ROL => {
-        let carry = self.is_flag(StatusFlags::Carry) as u8;
-        let new_carry = (data & 0x80) == 0x80;
-        let temp = (data << 1) | carry;
-        self.set_flag(StatusFlags::Carry, new_carry);
-        self.set_flag(StatusFlags::Zero, temp == 0x00);
-        self.set_flag(StatusFlags::Negative, (temp & 0x80) == 0x80);
-        if let AddressingMode::Implied | AddressingMode::Accumulator = mode {
-          self.a = temp;
-        } else {
-          self.write(address, temp);
-        }
+        let result = self.rotate_left(data);
+        self.store(&mode, address, result);
       }
       OpCode::ROR => {
-        let carry = self.is_flag(StatusFlags::Carry) as u8;
-        let new_carry = (data & 0x01) == 0x01;
-        let temp = (carry << 7) | (data >> 1);
-        self.set_flag(StatusFlags::Carry, new_carry);
-        self.set_flag(StatusFlags::Zero, temp == 0x00);
-        self.set_flag(StatusFlags::Negative, (temp & 0x80) == 0x80);
-        if let AddressingMode::Implied | AddressingMode::Accumulator = mode {
-          self.a = temp;
-        } else {
-          self.write(address, temp);
-        }
+        let result = self.rotate_right(data);
+        self.store(&mode, address, result);
       }
       OpCode::RTI => {
-        self.status = self.pop().try_into().map_err(|e| CpuError::Other(format!("{e}")))?;
-        self.status.toggle(StatusFlags::Break);
-        self.status.toggle(StatusFlags::Unused);
+        self.dummy_read(self.stack_address());
+        let status = self.pop();
+        self.pull_status(status);
 
         let lo = self.pop();
         let hi = self.pop();
         self.program_counter = u16::from_le_bytes([lo, hi]);
       }
       OpCode::RTS => {
+        self.dummy_read(self.stack_address());
         let lo = self.pop();
         let hi = self.pop();
+        // `JSR` pushed the address of its last byte
         self.program_counter = u16::from_le_bytes([lo, hi]);
+        self.dummy_read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
       }
       OpCode::SBC => {
-        let input_data = !data;
-        let input_carry = self.is_flag(StatusFlags::Carry);
-        let (result, carry) = self.a.carrying_add(input_data, input_carry);
-        let overflow = input_carry != carry; // https://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
-        self.set_flag(StatusFlags::Overflow, overflow);
-        self.set_flag(StatusFlags::Carry, carry);
-        self.set_flag(StatusFlags::Zero, result == 0x00);
-        self.set_flag(StatusFlags::Negative, (result & 0x80) == 0x80);
-        self.a = result;
+        self.subtract_with_borrow(data);
+        self.decimal_penalty();
       }
       OpCode::SEC => {
         self.set_flag(StatusFlags::Carry, true);
@@ -295,13 +329,13 @@ impl Cpu {
         self.set_flag(StatusFlags::Interrupt, true);
       }
       OpCode::STA => {
-        self.write(address, self.a);
+        self.write_cycle(address, self.a);
       }
       OpCode::STX => {
-        self.write(address, self.x);
+        self.write_cycle(address, self.x);
       }
       OpCode::STY => {
-        self.write(address, self.y);
+        self.write_cycle(address, self.y);
       }
       OpCode::TAX => {
         self.x = self.a;
@@ -331,87 +365,823 @@ impl Cpu {
         self.set_flag(StatusFlags::Zero, self.a == 0x00);
         self.set_flag(StatusFlags::Negative, (self.a & 0x80) == 0x80);
       }
+      OpCode::ALR => {
+        self.a = self.shift_right(self.a & data);
+      }
+      OpCode::ANC => {
+        self.a &= data;
+        self.set_flags_from(self.a);
+        self.set_flag(StatusFlags::Carry, (self.a & 0x80) == 0x80);
+      }
+      OpCode::ANE => {
+        self.a = (self.a | UNSTABLE_MAGIC) & self.x & data;
+        self.set_flags_from(self.a);
+      }
+      OpCode::ARR => {
+        self.and_rotate_right(data);
+      }
+      OpCode::DCP => {
+        let result = data.wrapping_sub(1);
+        self.write_cycle(address, result);
+        self.compare(self.a, result);
+      }
+      OpCode::ISC => {
+        let result = data.wrapping_add(1);
+        self.write_cycle(address, result);
+        self.subtract_with_borrow(result);
+      }
+      OpCode::JAM => {
+        self.jammed = true;
+      }
+      OpCode::LAS => {
+        let value = data & self.stack_ptr;
+        self.a = value;
+        self.x = value;
+        self.stack_ptr = value;
+        self.set_flags_from(value);
+      }
+      OpCode::LAX => {
+        self.a = data;
+        self.x = data;
+        self.set_flags_from(data);
+      }
+      OpCode::LXA => {
+        let value = (self.a | UNSTABLE_MAGIC) & data;
+        self.a = value;
+        self.x = value;
+        self.set_flags_from(value);
+      }
+      OpCode::RLA => {
+        let result = self.rotate_left(data);
+        self.write_cycle(address, result);
+        self.a &= result;
+        self.set_flags_from(self.a);
+      }
+      OpCode::RRA => {
+        let result = self.rotate_right(data);
+        self.write_cycle(address, result);
+        self.add_with_carry(result);
+      }
+      OpCode::SAX => {
+        self.write_cycle(address, self.a & self.x);
+      }
+      OpCode::SBX => {
+        let value = self.a & self.x;
+        self.set_flag(StatusFlags::Carry, value >= data);
+        self.x = value.wrapping_sub(data);
+        self.set_flags_from(self.x);
+      }
+      OpCode::SHA => {
+        self.store_high_and(address, self.y, self.a & self.x);
+      }
+      OpCode::SHX => {
+        self.store_high_and(address, self.y, self.x);
+      }
+      OpCode::SHY => {
+        self.store_high_and(address, self.x, self.y);
+      }
+      OpCode::TAS => {
+        self.stack_ptr = self.a & self.x;
+        self.store_high_and(address, self.y, self.stack_ptr);
+      }
+      OpCode::SLO => {
+        let result = self.shift_left(data);
+        self.write_cycle(address, result);
+        self.a |= result;
+        self.set_flags_from(self.a);
+      }
+      OpCode::SRE => {
+        let result = self.shift_right(data);
+        self.write_cycle(address, result);
+        self.a ^= result;
+        self.set_flags_from(self.a);
+      }
+      OpCode::BRA => {
+        self.branch(address);
+      }
+      OpCode::PHX => {
+        self.push(self.x);
+      }
+      OpCode::PHY => {
+        self.push(self.y);
+      }
+      OpCode::PLX => {
+        self.dummy_read(self.stack_address());
+        self.x = self.pop();
+        self.set_flags_from(self.x);
+      }
+      OpCode::PLY => {
+        self.dummy_read(self.stack_address());
+        self.y = self.pop();
+        self.set_flags_from(self.y);
+      }
+      OpCode::STZ => {
+        self.write_cycle(address, 0x00);
+      }
+      OpCode::TRB => {
+        self.set_flag(StatusFlags::Zero, (self.a & data) == 0x00);
+        self.write_cycle(address, data & !self.a);
+      }
+      OpCode::TSB => {
+        self.set_flag(StatusFlags::Zero, (self.a & data) == 0x00);
+        self.write_cycle(address, data | self.a);
+      }
+      OpCode::BBR0
+      | OpCode::BBR1
+      | OpCode::BBR2
+      | OpCode::BBR3
+      | OpCode::BBR4
+      | OpCode::BBR5
+      | OpCode::BBR6
+      | OpCode::BBR7 => {
+        self.branch_on_bit(&mode, data, opcode, false);
+      }
+      OpCode::BBS0
+      | OpCode::BBS1
+      | OpCode::BBS2
+      | OpCode::BBS3
+      | OpCode::BBS4
+      | OpCode::BBS5
+      | OpCode::BBS6
+      | OpCode::BBS7 => {
+        self.branch_on_bit(&mode, data, opcode, true);
+      }
+      OpCode::RMB0
+      | OpCode::RMB1
+      | OpCode::RMB2
+      | OpCode::RMB3
+      | OpCode::RMB4
+      | OpCode::RMB5
+      | OpCode::RMB6
+      | OpCode::RMB7 => {
+        self.write_cycle(address, data & !(1 << opcode.bit().unwrap_or_default()));
+      }
+      OpCode::SMB0
+      | OpCode::SMB1
+      | OpCode::SMB2
+      | OpCode::SMB3
+      | OpCode::SMB4
+      | OpCode::SMB5
+      | OpCode::SMB6
+      | OpCode::SMB7 => {
+        self.write_cycle(address, data | (1 << opcode.bit().unwrap_or_default()));
+      }
+      OpCode::WAI => {
+        self.waiting = true;
+      }
+      OpCode::STP => {
+        self.jammed = true;
+      }
+    }
+
+    // the 65C02 takes longer than its bus accesses for some instructions
+    while self.cycles < cycles {
+      self.idle_cycle();
     }
 
-    Ok(cycles)
+    Ok(())
   }
 
-  fn interpret_payload(&mut self, mode: &AddressingMode, cycles: &mut u8) -> (u8, u16) {
-    let address = match mode {
-      AddressingMode::Accumulator => self.a as u16,
-      AddressingMode::Implied => {
-        return (self.a, 0);
+  /// Fetches the target of a `JSR`. The return address is pushed between
+  /// fetching its low and high byte, so the decoder leaves the operand to
+  /// this instead of fetching it like for other absolute instructions.
+  pub(crate) fn jump_to_subroutine(&mut self) -> Result<AddressingMode, CpuError> {
+    let lo = self.fetch()?;
+    self.dummy_read(self.stack_address());
+    let [pcl, pch] = self.program_counter.to_le_bytes();
+    self.push(pch);
+    self.push(pcl);
+    let hi = self.fetch()?;
+
+    Ok(AddressingMode::Absolute(u16::from_le_bytes([lo, hi])))
+  }
+
+  /// Computes the address `mode` points at, performing the bus cycles that
+  /// takes. Relative modes yield the sign extended offset.
+  fn effective_address(&mut self, mode: &AddressingMode, access: Access) -> u16 {
+    match *mode {
+      AddressingMode::Accumulator | AddressingMode::Implied => {
+        // the byte after the op code is read and dropped
+        self.dummy_read(self.program_counter);
+        0
       }
-      AddressingMode::Immediate(value) => {
-        return (*value, self.program_counter - 1);
+      AddressingMode::Immediate(_) => self.program_counter.wrapping_sub(1),
+      AddressingMode::Relative(offset) => offset as i8 as u16,
+      AddressingMode::ZeroPage(address) => address as u16,
+      AddressingMode::ZeroPageX(address) => {
+        self.dummy_read(address as u16);
+        address.wrapping_add(self.x) as u16
       }
-      AddressingMode::Relative(addr) => {
-        let mut address = *addr as u16;
-        if (address & 0x80) == 0x80 {
-          // 7th bit is 1, so this is supposed to be signed. set the top bits to all 1s to
-          // enable 2s compliment arithmetic
-          address |= 0xFF00;
-        }
-        address
-      }
-      AddressingMode::ZeroPage(addr) => *addr as u16 & 0x00FF,
-      AddressingMode::ZeroPageX(addr) => (addr + self.x as u16) & 0x00FF,
-      AddressingMode::ZeroPageY(addr) => (addr + self.y as u16) & 0x00FF,
-      AddressingMode::Absolute(addr) => *addr,
-      AddressingMode::AbsoluteX(addr) => {
-        let (addr, overflow) = addr.overflowing_add(self.x as u16);
-        if overflow {
-          *cycles += 1;
-        }
-        addr
-      }
-      AddressingMode::AbsoluteY(addr) => {
-        let (addr, overflow) = addr.overflowing_add(self.y as u16);
-        if overflow {
-          *cycles += 1;
-        }
-        addr
+      AddressingMode::ZeroPageY(address) => {
+        self.dummy_read(address as u16);
+        address.wrapping_add(self.y) as u16
       }
+      AddressingMode::Absolute(address) => address,
+      AddressingMode::AbsoluteX(address) => self.indexed(address, self.x, access),
+      AddressingMode::AbsoluteY(address) => self.indexed(address, self.y, access),
       AddressingMode::Indirect(ptr) => {
-        if (ptr & 0x00FF) == 0x00FF {
-          // simulate hardware bug
-          let lo = self.read(*ptr);
-          let hi = self.read(ptr & 0xFF00);
-          u16::from_le_bytes([lo, hi])
-        } else {
-          // behave normally
-          let lo = self.read(*ptr);
-          let hi = self.read(ptr + 1);
-          u16::from_le_bytes([lo, hi])
-        }
+        // the NMOS 6502 does not carry into the high byte of the pointer
+        let next = match self.variant.is_cmos() {
+          true => ptr.wrapping_add(1),
+          false => (ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF),
+        };
+        let lo = self.read_cycle(ptr);
+        let hi = self.read_cycle(next);
+        u16::from_le_bytes([lo, hi])
       }
       AddressingMode::IndirectX(ptr) => {
-        let lo = self.read((ptr + self.x as u16) & 0x00FF);
-        let hi = self.read((ptr + self.x as u16 + 1) & 0x00FF);
-        u16::from_le_bytes([lo, hi])
+        self.dummy_read(ptr as u16);
+        self.read_pointer(ptr.wrapping_add(self.x))
       }
       AddressingMode::IndirectY(ptr) => {
-        let lo = self.read((ptr) & 0x00FF);
-        let hi = self.read((ptr + 1) & 0x00FF);
-        let (addr, overflow) = u16::from_le_bytes([lo, hi]).overflowing_add(self.y as u16);
-        if overflow {
-          *cycles += 1;
+        let base = self.read_pointer(ptr);
+        self.indexed(base, self.y, access)
+      }
+      AddressingMode::ZeroPageIndirect(ptr) => self.read_pointer(ptr),
+      AddressingMode::AbsoluteIndexedIndirect(ptr) => {
+        self.dummy_read(self.program_counter.wrapping_sub(1));
+        let ptr = ptr.wrapping_add(self.x as u16);
+        let lo = self.read_cycle(ptr);
+        let hi = self.read_cycle(ptr.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+      }
+      AddressingMode::ZeroPageRelative(address, _) => address as u16,
+    }
+  }
+
+  /// Access that decides whether indexing spends its fix-up cycle without
+  /// crossing a page. The 65C02 skips it for shifts and rotates, which then
+  /// only take the extra cycle like reads do.
+  fn indexing(&self, opcode: OpCode, access: Access) -> Access {
+    match opcode {
+      OpCode::ASL | OpCode::LSR | OpCode::ROL | OpCode::ROR if self.variant.is_cmos() => Access::Read,
+      _ => access,
+    }
+  }
+
+  /// Reads a little endian pointer from the zero page, wrapping around within
+  /// it
+  fn read_pointer(&mut self, ptr: u8) -> u16 {
+    let lo = self.read_cycle(ptr as u16);
+    let hi = self.read_cycle(ptr.wrapping_add(1) as u16);
+    u16::from_le_bytes([lo, hi])
+  }
+
+  /// Adds `index` to `base`. The address is formed before the carry into the
+  /// high byte, so it is read once without the carry when indexing crosses a
+  /// page. Writes always spend that cycle.
+  fn indexed(&mut self, base: u16, index: u8, access: Access) -> u16 {
+    let address = base.wrapping_add(index as u16);
+    if (base ^ address) & 0xFF00 != 0 || access != Access::Read {
+      self.dummy_read((base & 0xFF00) | (address & 0x00FF));
+    }
+    address
+  }
+
+  /// Reads the value an instruction operates on. Read-modify-write
+  /// instructions write the unmodified value back before the result, while
+  /// the 65C02 reads it a second time instead.
+  fn load(&mut self, mode: &AddressingMode, address: u16, access: Access) -> u8 {
+    match (mode, access) {
+      (AddressingMode::Immediate(value), _) => *value,
+      (AddressingMode::Accumulator | AddressingMode::Implied, _) => self.a,
+      (_, Access::Read) => self.read_cycle(address),
+      (_, Access::Modify) => {
+        let data = self.read_cycle(address);
+        match self.variant.is_cmos() {
+          true => self.dummy_read(address),
+          false => self.dummy_write(address, data),
         }
-        addr
+        data
+      }
+      (_, Access::Write | Access::Other) => 0,
+    }
+  }
+
+  /// `A + data + C`, in BCD when the decimal flag is set and decimal mode is
+  /// enabled. Decimal results follow the NMOS 6502: Z reflects the binary
+  /// sum, while N and V are taken from the sum after only the low nibble was
+  /// adjusted.
+  fn add_with_carry(&mut self, data: u8) {
+    let carry = self.is_flag(StatusFlags::Carry) as u16;
+    let binary = self.a as u16 + data as u16 + carry;
+
+    if !(self.decimal_mode && self.is_flag(StatusFlags::Decimal)) {
+      self.set_flag(StatusFlags::Carry, binary > 0xFF);
+      self.set_flag(StatusFlags::Overflow, Self::overflowed(self.a, data, binary as u8));
+      self.set_flags_from(binary as u8);
+      self.a = binary as u8;
+      return;
+    }
+
+    let mut low = (self.a & 0x0F) as u16 + (data & 0x0F) as u16 + carry;
+    if low >= 0x0A {
+      low = ((low + 0x06) & 0x0F) + 0x10;
+    }
+    let mut result = (self.a & 0xF0) as u16 + (data & 0xF0) as u16 + low;
+
+    self.set_flag(StatusFlags::Zero, binary as u8 == 0x00);
+    self.set_flag(StatusFlags::Negative, (result & 0x80) == 0x80);
+    self.set_flag(StatusFlags::Overflow, Self::overflowed(self.a, data, result as u8));
+    if result >= 0xA0 {
+      result += 0x60;
+    }
+    self.set_flag(StatusFlags::Carry, result > 0xFF);
+    self.a = result as u8;
+
+    // the 65C02 spends an extra cycle to make N and Z valid
+    if self.variant.is_cmos() {
+      self.set_flags_from(self.a);
+    }
+  }
+
+  /// `A - data - !C`, in BCD when the decimal flag is set and decimal mode is
+  /// enabled. On the NMOS 6502 every flag reflects the binary difference even
+  /// in decimal mode.
+  fn subtract_with_borrow(&mut self, data: u8) {
+    let borrow = !self.is_flag(StatusFlags::Carry) as i16;
+    let binary = self.a as i16 - data as i16 - borrow;
+
+    self.set_flag(StatusFlags::Carry, binary >= 0);
+    self.set_flag(StatusFlags::Overflow, Self::overflowed(self.a, !data, binary as u8));
+    self.set_flags_from(binary as u8);
+
+    if !(self.decimal_mode && self.is_flag(StatusFlags::Decimal)) {
+      self.a = binary as u8;
+      return;
+    }
+
+    let mut low = (self.a & 0x0F) as i16 - (data & 0x0F) as i16 - borrow;
+    if self.variant.is_cmos() {
+      let mut result = binary;
+      if result < 0 {
+        result -= 0x60;
       }
+      if low < 0 {
+        result -= 0x06;
+      }
+      self.a = result as u8;
+      self.set_flags_from(self.a);
+      return;
+    }
+
+    if low < 0 {
+      low = ((low - 0x06) & 0x0F) - 0x10;
+    }
+    let mut result = (self.a & 0xF0) as i16 - (data & 0xF0) as i16 + low;
+    if result < 0 {
+      result -= 0x60;
+    }
+    self.a = result as u8;
+  }
+
+  /// Spends the extra cycle the 65C02 takes for ADC and SBC in decimal mode
+  fn decimal_penalty(&mut self) {
+    if self.variant.is_cmos() && self.decimal_mode && self.is_flag(StatusFlags::Decimal) {
+      self.idle_cycle();
+    }
+  }
+
+  /// Whether adding `left` and `right` into `result` overflowed as signed
+  /// numbers, i.e. both inputs have the same sign and the result does not.
+  /// See https://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
+  fn overflowed(left: u8, right: u8, result: u8) -> bool {
+    (!(left ^ right) & (left ^ result) & 0x80) == 0x80
+  }
+
+  /// `A & data` rotated right, with C and V taken from bits 6 and 5 of the
+  /// result. In decimal mode the NMOS 6502 also adjusts each nibble like a
+  /// BCD addition would.
+  fn and_rotate_right(&mut self, data: u8) {
+    let value = self.a & data;
+    let carry = self.is_flag(StatusFlags::Carry) as u8;
+    let mut result = (value >> 1) | (carry << 7);
+    self.set_flags_from(result);
+
+    if !(self.decimal_mode && self.is_flag(StatusFlags::Decimal)) {
+      self.set_flag(StatusFlags::Carry, (result & 0x40) == 0x40);
+      self.set_flag(StatusFlags::Overflow, ((result >> 6) ^ (result >> 5)) & 0x01 == 0x01);
+      self.a = result;
+      return;
+    }
+
+    self.set_flag(StatusFlags::Overflow, ((value ^ result) & 0x40) == 0x40);
+    if (value & 0x0F) + (value & 0x01) > 0x05 {
+      result = (result & 0xF0) | (result.wrapping_add(0x06) & 0x0F);
+    }
+    let high = value >> 4;
+    let carry = high + (high & 0x01) > 0x05;
+    self.set_flag(StatusFlags::Carry, carry);
+    if carry {
+      result = result.wrapping_add(0x60);
+    }
+    self.a = result;
+  }
+
+  fn compare(&mut self, register: u8, data: u8) {
+    self.set_flag(StatusFlags::Carry, register >= data);
+    self.set_flags_from(register.wrapping_sub(data));
+  }
+
+  fn shift_left(&mut self, value: u8) -> u8 {
+    let result = value << 1;
+    self.set_flag(StatusFlags::Carry, (value & 0x80) == 0x80);
+    self.set_flags_from(result);
+    result
+  }
+
+  fn shift_right(&mut self, value: u8) -> u8 {
+    let result = value >> 1;
+    self.set_flag(StatusFlags::Carry, (value & 0x01) == 0x01);
+    self.set_flags_from(result);
+    result
+  }
+
+  fn rotate_left(&mut self, value: u8) -> u8 {
+    let result = (value << 1) | self.is_flag(StatusFlags::Carry) as u8;
+    self.set_flag(StatusFlags::Carry, (value & 0x80) == 0x80);
+    self.set_flags_from(result);
+    result
+  }
+
+  fn rotate_right(&mut self, value: u8) -> u8 {
+    let result = ((self.is_flag(StatusFlags::Carry) as u8) << 7) | (value >> 1);
+    self.set_flag(StatusFlags::Carry, (value & 0x01) == 0x01);
+    self.set_flags_from(result);
+    result
+  }
+
+  /// Stores `value` ANDed with the high byte of the base address plus one, as
+  /// `SHA`, `SHX`, `SHY` and `TAS` do. When indexing by `index` crossed a page
+  /// the stored value also replaces the high byte of the address.
+  fn store_high_and(&mut self, address: u16, index: u8, value: u8) {
+    let base = address.wrapping_sub(index as u16);
+    let value = value & ((base >> 8) as u8).wrapping_add(1);
+    let address = match (base ^ address) & 0xFF00 != 0 {
+      true => ((value as u16) << 8) | (address & 0x00FF),
+      false => address,
+    };
+    self.write_cycle(address, value);
+  }
+
+  /// Writes the result of a read-modify-write instruction back to the
+  /// accumulator or memory
+  fn store(&mut self, mode: &AddressingMode, address: u16, value: u8) {
+    match mode {
+      AddressingMode::Accumulator | AddressingMode::Implied => self.a = value,
+      _ => self.write_cycle(address, value),
+    }
+  }
+
+  /// Loads the status register from the stack. B and the unused bit only
+  /// exist in the pushed copy, so they are not taken over.
+  fn pull_status(&mut self, value: u8) {
+    self.status = BitFlags::from_bits_truncate(value);
+    self.set_flag(StatusFlags::Break, false);
+    self.set_flag(StatusFlags::Unused, true);
+  }
+
+  fn set_flags_from(&mut self, value: u8) {
+    self.set_flag(StatusFlags::Zero, value == 0x00);
+    self.set_flag(StatusFlags::Negative, (value & 0x80) == 0x80);
+  }
+
+  /// Branches to the target of a `BBR` or `BBS` if the bit of `data` it tests
+  /// equals `set`
+  fn branch_on_bit(&mut self, mode: &AddressingMode, data: u8, opcode: OpCode, set: bool) {
+    let bit = opcode.bit().unwrap_or_default();
+    if let AddressingMode::ZeroPageRelative(address, offset) = *mode {
+      self.dummy_read(address as u16);
+      if ((data >> bit) & 0x01 == 0x01) == set {
+        self.branch(offset as i8 as u16);
+      }
+    }
+  }
+
+  /// Adds the sign extended `offset` to the program counter. The next op code
+  /// is read while the target is computed, and once more from the wrong page
+  /// when the branch crosses one.
+  fn branch(&mut self, offset: u16) {
+    self.dummy_read(self.program_counter);
+    let target = self.program_counter.wrapping_add(offset);
+    if (self.program_counter ^ target) & 0xFF00 != 0 {
+      self.dummy_read((self.program_counter & 0xFF00) | (target & 0x00FF));
+    }
+    self.program_counter = target;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::core::{
+    cpu::Cpu,
+    ram::Ram,
+    step::{Interrupt, StepEvent},
+    variant::CpuVariant,
+  };
+
+  /// Runs `program` from `$0200` with `memory` written at its addresses and
+  /// returns the CPU after `steps` instructions
+  fn run(program: &[u8], memory: &[(u16, u8)], steps: usize) -> Cpu {
+    run_on(CpuVariant::Nmos6502, program, memory, steps)
+  }
+
+  fn run_on(variant: CpuVariant, program: &[u8], memory: &[(u16, u8)], steps: usize) -> Cpu {
+    let mut cpu = load(variant, program, memory);
+    for _ in 0..steps {
+      cpu.step_instruction().unwrap();
+    }
+    cpu
+  }
+
+  /// A reset CPU with `program` at `$0200` and `memory` written at its
+  /// addresses
+  fn load(variant: CpuVariant, program: &[u8], memory: &[(u16, u8)]) -> Cpu {
+    let mut ram = Ram::new(0x0000..=0xFFFF);
+    ram.write_all(0x0200, program);
+    ram.write_all(0xFFFC, &[0x00, 0x02]);
+    let mut cpu = Cpu::with_variant(1.0, variant);
+    cpu.set_undocumented_opcodes(true);
+    cpu.connect(ram);
+    for &(address, value) in memory {
+      cpu.write(address, value);
+    }
+    cpu.reset();
+    cpu
+  }
+
+  #[test]
+  fn zero_page_indexed_operands_are_one_byte_and_wrap() {
+    // LDX #$FF; LDA $10,X
+    let cpu = run(&[0xA2, 0xFF, 0xB5, 0x10], &[(0x000F, 0x42), (0x010F, 0x99)], 2);
+    let registers = cpu.registers();
+    assert_eq!(registers.program_counter, 0x0204);
+    assert_eq!(registers.a, 0x42);
+
+    // LDY #$FF; LDX $10,Y
+    let cpu = run(&[0xA0, 0xFF, 0xB6, 0x10], &[(0x000F, 0x42)], 2);
+    assert_eq!(cpu.registers().program_counter, 0x0204);
+    assert_eq!(cpu.registers().x, 0x42);
+  }
+
+  #[test]
+  fn indirect_indexed_operands_are_one_byte_and_wrap() {
+    // LDX #$01; LDA ($FE,X) reads its pointer from $FF and $00
+    let cpu = run(&[0xA2, 0x01, 0xA1, 0xFE], &[(0x00FF, 0x34), (0x0000, 0x12), (0x1234, 0x42)], 2);
+    assert_eq!(cpu.registers().program_counter, 0x0204);
+    assert_eq!(cpu.registers().a, 0x42);
+
+    // LDY #$01; LDA ($FF),Y reads its pointer from $FF and $00
+    let cpu = run(&[0xA0, 0x01, 0xB1, 0xFF], &[(0x00FF, 0x34), (0x0000, 0x12), (0x1235, 0x42)], 2);
+    assert_eq!(cpu.registers().program_counter, 0x0204);
+    assert_eq!(cpu.registers().a, 0x42);
+  }
+
+  #[test]
+  fn sh_stores_and_the_high_byte_plus_one() {
+    // LDY #$F0; LDX #$01; SHY $3400,X
+    let mut cpu = run(&[0xA0, 0xF0, 0xA2, 0x01, 0x9C, 0x00, 0x34], &[], 3);
+    assert_eq!(cpu.read(0x3401), 0x30);
+
+    // crossing a page replaces the high byte of the address with the value
+    // LDY #$F0; LDX #$01; SHY $34FF,X
+    let mut cpu = run(&[0xA0, 0xF0, 0xA2, 0x01, 0x9C, 0xFF, 0x34], &[], 3);
+    assert_eq!(cpu.read(0x3000), 0x30);
+    assert_ne!(cpu.read(0x3500), 0x30);
+  }
+
+  #[test]
+  fn cmos_shifts_indexed_by_x_only_fix_up_across_pages() {
+    // LDX #$01; ASL $3400,X; ASL $34FF,X
+    let program = [0xA2, 0x01, 0x1E, 0x00, 0x34, 0x1E, 0xFF, 0x34];
+    // cycles of each instruction after the first
+    let cycles = |variant| {
+      let total = |steps| run_on(variant, &program, &[], steps).total_cycles();
+      [total(2) - total(1), total(3) - total(2)]
     };
+    assert_eq!(cycles(CpuVariant::Nmos6502), [7, 7]);
+    assert_eq!(cycles(CpuVariant::Cmos65C02), [6, 7]);
+  }
 
-    let data = self.read(address);
+  #[test]
+  fn interrupt_mask_changes_take_effect_after_the_next_instruction() {
+    let irq = |cpu: &mut Cpu| {
+      let step = cpu.step_instruction().unwrap();
+      matches!(step.event, StepEvent::Interrupt(Interrupt::Irq))
+    };
 
-    (data, address)
+    // CLI; NOP with the IRQ line asserted
+    let mut cpu = load(CpuVariant::Nmos6502, &[0x58, 0xEA], &[]);
+    cpu.set_irq_line(true);
+    assert!(!irq(&mut cpu));
+    assert!(!irq(&mut cpu));
+    assert!(irq(&mut cpu));
+
+    // CLI; SEI with the IRQ line asserted after both
+    let mut cpu = load(CpuVariant::Nmos6502, &[0x58, 0x78, 0xEA], &[]);
+    assert!(!irq(&mut cpu));
+    assert!(!irq(&mut cpu));
+    cpu.set_irq_line(true);
+    assert!(irq(&mut cpu));
   }
 
-  fn branch(&mut self, payload: u16, cycles: &mut u8) {
-    *cycles += 1;
-    let (addr, overflow) = self.program_counter.overflowing_add(payload);
-    if overflow {
-      *cycles += 1;
+  /// Runs `SED`, sets the carry to `carry`, loads `a` and applies the
+  /// immediate `opcode` to `operand`. Returns A and the C, Z, V and N flags.
+  fn decimal(cpu: &mut Cpu, opcode: u8, a: u8, operand: u8, carry: bool) -> (u8, u8) {
+    let program = [0xF8, if carry { 0x38 } else { 0x18 }, 0xA9, a, opcode, operand];
+    for (offset, byte) in program.into_iter().enumerate() {
+      cpu.write(0x0200 + offset as u16, byte);
+    }
+    cpu.reset();
+    for _ in 0..4 {
+      cpu.step_instruction().unwrap();
     }
-    self.program_counter = addr;
+    let registers = cpu.registers();
+    (registers.a, registers.status & 0xC3)
+  }
+
+  const ADC: u8 = 0x69;
+  const SBC: u8 = 0xE9;
+  const C: u8 = 0x01;
+  const Z: u8 = 0x02;
+  const V: u8 = 0x40;
+  const N: u8 = 0x80;
+
+  #[test]
+  fn nmos_decimal_arithmetic_and_flags() {
+    let mut cpu = load(CpuVariant::Nmos6502, &[], &[]);
+    // N and V come from the sum after adjusting only the low digit
+    assert_eq!(decimal(&mut cpu, ADC, 0x58, 0x46, true), (0x05, C | V | N));
+    assert_eq!(decimal(&mut cpu, ADC, 0x12, 0x34, false), (0x46, 0));
+    // Z reflects the binary sum $9A rather than the result
+    assert_eq!(decimal(&mut cpu, ADC, 0x99, 0x01, false), (0x00, C | N));
+    // invalid BCD digits
+    assert_eq!(decimal(&mut cpu, ADC, 0x0F, 0x00, false), (0x15, 0));
+    assert_eq!(decimal(&mut cpu, ADC, 0x1A, 0x00, false), (0x20, 0));
+
+    // every flag reflects the binary difference
+    assert_eq!(decimal(&mut cpu, SBC, 0x00, 0x01, true), (0x99, N));
+    assert_eq!(decimal(&mut cpu, SBC, 0x46, 0x12, true), (0x34, C));
+    assert_eq!(decimal(&mut cpu, SBC, 0x40, 0x13, true), (0x27, C));
+    assert_eq!(decimal(&mut cpu, SBC, 0x21, 0x21, true), (0x00, C | Z));
+  }
+
+  #[test]
+  fn cmos_decimal_flags_reflect_the_result() {
+    let mut cpu = load(CpuVariant::Cmos65C02, &[], &[]);
+    assert_eq!(decimal(&mut cpu, ADC, 0x99, 0x01, false), (0x00, C | Z));
+    assert_eq!(decimal(&mut cpu, SBC, 0x00, 0x01, true), (0x99, N));
+  }
+
+  #[test]
+  fn decimal_flag_is_ignored_without_decimal_mode() {
+    let mut cpu = load(CpuVariant::Ricoh2A03, &[], &[]);
+    assert_eq!(decimal(&mut cpu, ADC, 0x58, 0x46, true), (0x9F, V | N));
+    assert_eq!(decimal(&mut cpu, SBC, 0x00, 0x01, true), (0xFF, N));
+
+    let mut cpu = load(CpuVariant::Nmos6502, &[], &[]);
+    cpu.set_decimal_mode(false);
+    assert_eq!(decimal(&mut cpu, ADC, 0x09, 0x01, false), (0x0A, 0));
+  }
+
+  #[test]
+  fn undocumented_loads_and_stores() {
+    // LDA #$F0; LDX #$3C; SAX $10; LAX $11
+    let program = [0xA9, 0xF0, 0xA2, 0x3C, 0x87, 0x10, 0xA7, 0x11];
+    let mut cpu = run(&program, &[(0x0011, 0x80)], 4);
+    assert_eq!(cpu.read(0x0010), 0x30);
+    let registers = cpu.registers();
+    assert_eq!((registers.a, registers.x, registers.status & (Z | N)), (0x80, 0x80, N));
+  }
+
+  #[test]
+  fn undocumented_read_modify_writes() {
+    // LDA #$42; DCP $10 compares A with the decremented value
+    let mut cpu = run(&[0xA9, 0x42, 0xC7, 0x10], &[(0x0010, 0x43)], 2);
+    assert_eq!(cpu.read(0x0010), 0x42);
+    assert_eq!(cpu.registers().status & (C | Z | N), C | Z);
+
+    // SEC; LDA #$20; ISC $10 subtracts the incremented value
+    let mut cpu = run(&[0x38, 0xA9, 0x20, 0xE7, 0x10], &[(0x0010, 0x0F)], 3);
+    assert_eq!(cpu.read(0x0010), 0x10);
+    assert_eq!(cpu.registers().a, 0x10);
+    assert_eq!(cpu.registers().status & (C | Z | N), C);
+  }
+
+  #[test]
+  fn undocumented_immediates() {
+    // CLC; LDA #$C0; ARR #$FF takes C from bit 6 and V from bits 6 and 5
+    let cpu = run(&[0x18, 0xA9, 0xC0, 0x6B, 0xFF], &[], 3);
+    assert_eq!(cpu.registers().a, 0x60);
+    assert_eq!(cpu.registers().status & (C | V), C);
+
+    // LDA #$0F; LDX #$F3; SBX #$01 sets X to (A & X) - 1 without borrowing
+    let cpu = run(&[0xA9, 0x0F, 0xA2, 0xF3, 0xCB, 0x01], &[], 3);
+    assert_eq!(cpu.registers().x, 0x02);
+    assert_eq!(cpu.registers().status & (C | Z | N), C);
+  }
+
+  #[test]
+  fn jam_halts_until_reset() {
+    let mut cpu = run(&[0x02, 0xEA], &[], 1);
+    assert!(cpu.is_jammed());
+
+    let registers = cpu.registers();
+    let step = cpu.step_instruction().unwrap();
+    assert!(matches!(step.event, StepEvent::Jammed));
+    assert_eq!(cpu.registers(), registers);
+
+    cpu.reset();
+    assert!(!cpu.is_jammed());
+    let step = cpu.step_instruction().unwrap();
+    assert!(matches!(step.event, StepEvent::Instruction(_)));
+  }
+
+  /// Handlers at `$0300` for IRQ and `$0400` for NMI, each a `NOP` and `RTI`
+  const HANDLERS: [(u16, u8); 8] = [
+    (0xFFFE, 0x00),
+    (0xFFFF, 0x03),
+    (0xFFFA, 0x00),
+    (0xFFFB, 0x04),
+    (0x0300, 0xEA),
+    (0x0301, 0x40),
+    (0x0400, 0xEA),
+    (0x0401, 0x40),
+  ];
+
+  /// Steps once and returns the interrupt taken, if any, after checking that
+  /// its sequence takes 7 cycles
+  fn interrupt(cpu: &mut Cpu) -> Option<Interrupt> {
+    let step = cpu.step_instruction().unwrap();
+    match step.event {
+      StepEvent::Interrupt(interrupt) => {
+        assert_eq!(step.cycles, 7);
+        Some(interrupt)
+      }
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn irq_is_taken_while_asserted_and_unmasked() {
+    // CLI; NOP; NOP
+    let mut cpu = load(CpuVariant::Nmos6502, &[0x58, 0xEA, 0xEA], &HANDLERS);
+    cpu.set_irq_line(true);
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(interrupt(&mut cpu), Some(Interrupt::Irq));
+    assert_eq!(cpu.registers().program_counter, 0x0300);
+    // the pushed status has B clear and the return address is the next NOP
+    assert_eq!(cpu.read(0x01FB) & 0x10, 0x00);
+    assert_eq!((cpu.read(0x01FC), cpu.read(0x01FD)), (0x02, 0x02));
+
+    // the handler runs masked, and the IRQ is taken again after RTI as long
+    // as the line stays asserted
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(interrupt(&mut cpu), Some(Interrupt::Irq));
+
+    cpu.set_irq_line(false);
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(cpu.registers().program_counter, 0x0202);
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(cpu.registers().program_counter, 0x0203);
+  }
+
+  #[test]
+  fn nmi_is_taken_once_per_rising_edge() {
+    // NOPs with IRQs masked by the reset
+    let mut cpu = load(CpuVariant::Nmos6502, &[0xEA; 8], &HANDLERS);
+    cpu.set_nmi_line(true);
+    assert_eq!(interrupt(&mut cpu), Some(Interrupt::Nmi));
+    assert_eq!(cpu.registers().program_counter, 0x0400);
+
+    // NOP; RTI, after which the line is still asserted
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(interrupt(&mut cpu), None);
+    assert_eq!(cpu.registers().program_counter, 0x0200);
+    assert_eq!(interrupt(&mut cpu), None);
+
+    cpu.set_nmi_line(false);
+    cpu.set_nmi_line(true);
+    assert_eq!(interrupt(&mut cpu), Some(Interrupt::Nmi));
+  }
+
+  #[test]
+  #[allow(deprecated)]
+  fn deprecated_interrupts_are_taken_right_away() {
+    let mut cpu = load(CpuVariant::Nmos6502, &[0x58, 0xEA], &HANDLERS);
+    cpu.step_instruction().unwrap();
+    cpu.interrupt();
+    assert_eq!(cpu.registers().program_counter, 0x0300);
+
+    let mut cpu = load(CpuVariant::Nmos6502, &[0xEA], &HANDLERS);
+    cpu.step_instruction().unwrap();
+    // masked by the reset
+    cpu.interrupt();
+    assert_eq!(cpu.registers().program_counter, 0x0201);
+    cpu.non_maskable_interrupt();
+    assert_eq!(cpu.registers().program_counter, 0x0400);
   }
 }
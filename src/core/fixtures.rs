@@ -0,0 +1,22 @@
+//! Locates the test suites that are too large or not licensed to be bundled.
+//! Tests that need them are ignored by default, so a run that asks for them
+//! fails when they are missing instead of passing without testing anything.
+
+use std::{
+  env,
+  path::{Path, PathBuf},
+};
+
+/// Directory `variable` points at, or else `default` below the crate root.
+/// Panics if it does not exist.
+pub(crate) fn directory(variable: &str, default: &str) -> PathBuf {
+  let directory = env::var_os(variable)
+    .map(PathBuf::from)
+    .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join(default));
+  assert!(
+    directory.is_dir(),
+    "`{}` does not exist, put the fixtures there or point `{variable}` at them",
+    directory.display()
+  );
+  directory
+}
@@ -1,15 +1,19 @@
-use strum::Display;
+use strum::{Display, EnumDiscriminants, EnumString};
 
-use crate::{core::cpu::Cpu, error::CpuError};
+use crate::{
+  core::{cpu::Cpu, observer::CpuObserver},
+  error::CpuError,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Instruction {
   pub opcode: OpCode,
   pub mode: AddressingMode,
   pub cycles: u8,
 }
 
-#[derive(Debug, Display)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, EnumDiscriminants)]
+#[strum_discriminants(derive(Hash))]
 pub enum AddressingMode {
   Accumulator,
   Implied,
@@ -17,101 +21,192 @@ pub enum AddressingMode {
   Relative(u8),
 
   ZeroPage(u8),
-  ZeroPageX(u16),
-  ZeroPageY(u16),
+  ZeroPageX(u8),
+  ZeroPageY(u8),
 
   Absolute(u16),
   AbsoluteX(u16),
   AbsoluteY(u16),
 
   Indirect(u16),
-  IndirectX(u16),
-  IndirectY(u16),
+  IndirectX(u8),
+  IndirectY(u8),
+
+  // 65C02 only
+  /// `(zp)`
+  ZeroPageIndirect(u8),
+  /// `(abs,X)`, used by `JMP`
+  AbsoluteIndexedIndirect(u16),
+  /// `zp,rel`, the zero page address and branch offset of `BBR` and `BBS`
+  ZeroPageRelative(u8, u8),
 }
 
 impl AddressingMode {
-  pub fn accumulator(_cpu: &mut Cpu) -> Result<Self, CpuError> {
+  /// Builds the addressing mode `mode` from its little endian operand bytes,
+  /// which must be at least [`AddressingModeDiscriminants::operand_len`] long
+  pub fn from_operand(mode: AddressingModeDiscriminants, operand: &[u8]) -> Self {
+    type Mode = AddressingModeDiscriminants;
+
+    let byte = || operand[0];
+    let word = || u16::from_le_bytes([operand[0], operand[1]]);
+    match mode {
+      Mode::Accumulator => Self::Accumulator,
+      Mode::Implied => Self::Implied,
+      Mode::Immediate => Self::Immediate(byte()),
+      Mode::Relative => Self::Relative(byte()),
+      Mode::ZeroPage => Self::ZeroPage(byte()),
+      Mode::ZeroPageX => Self::ZeroPageX(byte()),
+      Mode::ZeroPageY => Self::ZeroPageY(byte()),
+      Mode::Absolute => Self::Absolute(word()),
+      Mode::AbsoluteX => Self::AbsoluteX(word()),
+      Mode::AbsoluteY => Self::AbsoluteY(word()),
+      Mode::Indirect => Self::Indirect(word()),
+      Mode::IndirectX => Self::IndirectX(byte()),
+      Mode::IndirectY => Self::IndirectY(byte()),
+      Mode::ZeroPageIndirect => Self::ZeroPageIndirect(byte()),
+      Mode::AbsoluteIndexedIndirect => Self::AbsoluteIndexedIndirect(word()),
+      Mode::ZeroPageRelative => Self::ZeroPageRelative(operand[0], operand[1]),
+    }
+  }
+
+  /// Little endian operand bytes of the mode, the inverse of
+  /// [`AddressingMode::from_operand`]
+  pub fn operand(&self) -> Vec<u8> {
+    match *self {
+      Self::Accumulator | Self::Implied => Vec::new(),
+      Self::Immediate(value)
+      | Self::Relative(value)
+      | Self::ZeroPage(value)
+      | Self::ZeroPageX(value)
+      | Self::ZeroPageY(value)
+      | Self::IndirectX(value)
+      | Self::IndirectY(value)
+      | Self::ZeroPageIndirect(value) => vec![value],
+      Self::Absolute(value)
+      | Self::AbsoluteX(value)
+      | Self::AbsoluteY(value)
+      | Self::Indirect(value)
+      | Self::AbsoluteIndexedIndirect(value) => value.to_le_bytes().to_vec(),
+      Self::ZeroPageRelative(address, offset) => vec![address, offset],
+    }
+  }
+
+  pub fn accumulator<O: CpuObserver>(_cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     Ok(Self::Accumulator)
   }
 
-  pub fn implied(_cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn implied<O: CpuObserver>(_cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     Ok(Self::Implied)
   }
 
-  pub fn immediate(cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn immediate<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     let value = cpu.fetch()?;
     Ok(AddressingMode::Immediate(value))
   }
 
-  pub fn relative(cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn relative<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     let value = cpu.fetch()?;
     Ok(AddressingMode::Relative(value))
   }
 
-  pub fn zero_page(cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn zero_page<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     let value = cpu.fetch()?;
     Ok(AddressingMode::ZeroPage(value))
   }
 
-  pub fn zero_page_x(cpu: &mut Cpu) -> Result<Self, CpuError> {
-    let first = cpu.fetch()?;
-    let second = cpu.fetch()?;
-    let value = u16::from_le_bytes([first, second]);
+  pub fn zero_page_x<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
+    let value = cpu.fetch()?;
     Ok(AddressingMode::ZeroPageX(value))
   }
 
-  pub fn zero_page_y(cpu: &mut Cpu) -> Result<Self, CpuError> {
-    let first = cpu.fetch()?;
-    let second = cpu.fetch()?;
-    let value = u16::from_le_bytes([first, second]);
+  pub fn zero_page_y<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
+    let value = cpu.fetch()?;
     Ok(AddressingMode::ZeroPageY(value))
   }
 
-  pub fn absolute(cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn absolute<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     let first = cpu.fetch()?;
     let second = cpu.fetch()?;
     let value = u16::from_le_bytes([first, second]);
     Ok(AddressingMode::Absolute(value))
   }
 
-  pub fn absolute_x(cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn absolute_x<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     let first = cpu.fetch()?;
     let second = cpu.fetch()?;
     let value = u16::from_le_bytes([first, second]);
     Ok(AddressingMode::AbsoluteX(value))
   }
 
-  pub fn absolute_y(cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn absolute_y<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     let first = cpu.fetch()?;
     let second = cpu.fetch()?;
     let value = u16::from_le_bytes([first, second]);
     Ok(AddressingMode::AbsoluteY(value))
   }
 
-  pub fn indirect(cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn indirect<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     let first = cpu.fetch()?;
     let second = cpu.fetch()?;
     let value = u16::from_le_bytes([first, second]);
     Ok(AddressingMode::Indirect(value))
   }
 
-  pub fn indirect_x(cpu: &mut Cpu) -> Result<Self, CpuError> {
-    let first = cpu.fetch()?;
-    let second = cpu.fetch()?;
-    let value = u16::from_le_bytes([first, second]);
+  pub fn indirect_x<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
+    let value = cpu.fetch()?;
     Ok(AddressingMode::IndirectX(value))
   }
 
-  pub fn indirect_y(cpu: &mut Cpu) -> Result<Self, CpuError> {
+  pub fn indirect_y<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
+    let value = cpu.fetch()?;
+    Ok(AddressingMode::IndirectY(value))
+  }
+
+  pub fn zero_page_indirect<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
+    let value = cpu.fetch()?;
+    Ok(AddressingMode::ZeroPageIndirect(value))
+  }
+
+  pub fn absolute_indexed_indirect<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
     let first = cpu.fetch()?;
     let second = cpu.fetch()?;
     let value = u16::from_le_bytes([first, second]);
-    Ok(AddressingMode::IndirectY(value))
+    Ok(AddressingMode::AbsoluteIndexedIndirect(value))
+  }
+
+  pub fn zero_page_relative<O: CpuObserver>(cpu: &mut Cpu<O>) -> Result<Self, CpuError> {
+    let address = cpu.fetch()?;
+    let offset = cpu.fetch()?;
+    Ok(AddressingMode::ZeroPageRelative(address, offset))
+  }
+}
+
+impl AddressingModeDiscriminants {
+  /// Number of operand bytes that follow the op code
+  pub fn operand_len(&self) -> u16 {
+    match self {
+      Self::Accumulator | Self::Implied => 0,
+      Self::Immediate
+      | Self::Relative
+      | Self::ZeroPage
+      | Self::ZeroPageX
+      | Self::ZeroPageY
+      | Self::IndirectX
+      | Self::IndirectY
+      | Self::ZeroPageIndirect => 1,
+      Self::Absolute
+      | Self::AbsoluteX
+      | Self::AbsoluteY
+      | Self::Indirect
+      | Self::AbsoluteIndexedIndirect
+      | Self::ZeroPageRelative => 2,
+    }
   }
 }
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug, Display)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash, EnumString)]
+#[strum(ascii_case_insensitive)]
 pub enum OpCode {
   ADC,
   AND,
@@ -169,4 +264,96 @@ pub enum OpCode {
   TXA,
   TXS,
   TYA,
+
+  // undocumented NMOS op codes
+  ALR,
+  ANC,
+  /// Also known as `XAA`
+  ANE,
+  ARR,
+  DCP,
+  ISC,
+  /// Locks up the CPU until it is reset, also known as `KIL`
+  JAM,
+  LAS,
+  LAX,
+  /// `LAX` with an immediate operand, whose result is unstable on hardware
+  LXA,
+  RLA,
+  RRA,
+  SAX,
+  SBX,
+  /// Also known as `AHX`
+  SHA,
+  SHX,
+  SHY,
+  SLO,
+  SRE,
+  /// Also known as `SHS`
+  TAS,
+
+  // 65C02 op codes
+  BRA,
+  PHX,
+  PHY,
+  PLX,
+  PLY,
+  STZ,
+  TRB,
+  TSB,
+  // Rockwell and WDC 65C02 bit op codes
+  BBR0,
+  BBR1,
+  BBR2,
+  BBR3,
+  BBR4,
+  BBR5,
+  BBR6,
+  BBR7,
+  BBS0,
+  BBS1,
+  BBS2,
+  BBS3,
+  BBS4,
+  BBS5,
+  BBS6,
+  BBS7,
+  RMB0,
+  RMB1,
+  RMB2,
+  RMB3,
+  RMB4,
+  RMB5,
+  RMB6,
+  RMB7,
+  SMB0,
+  SMB1,
+  SMB2,
+  SMB3,
+  SMB4,
+  SMB5,
+  SMB6,
+  SMB7,
+  // WDC 65C02 op codes
+  /// Waits for an interrupt
+  WAI,
+  /// Stops the CPU until it is reset
+  STP,
+}
+
+impl OpCode {
+  /// Bit tested or changed by the `BBR`, `BBS`, `RMB` and `SMB` families
+  pub fn bit(&self) -> Option<u8> {
+    match self {
+      Self::BBR0 | Self::BBS0 | Self::RMB0 | Self::SMB0 => Some(0),
+      Self::BBR1 | Self::BBS1 | Self::RMB1 | Self::SMB1 => Some(1),
+      Self::BBR2 | Self::BBS2 | Self::RMB2 | Self::SMB2 => Some(2),
+      Self::BBR3 | Self::BBS3 | Self::RMB3 | Self::SMB3 => Some(3),
+      Self::BBR4 | Self::BBS4 | Self::RMB4 | Self::SMB4 => Some(4),
+      Self::BBR5 | Self::BBS5 | Self::RMB5 | Self::SMB5 => Some(5),
+      Self::BBR6 | Self::BBS6 | Self::RMB6 | Self::SMB6 => Some(6),
+      Self::BBR7 | Self::BBS7 | Self::RMB7 | Self::SMB7 => Some(7),
+      _ => None,
+    }
+  }
 }
@@ -0,0 +1,311 @@
+//! Traces in the format of the `nestest.log` that comes with Kevin Horton's
+//! nestest ROM, which most emulators can produce, and compares them
+//! field by field. A line looks like
+//!
+//! ```text
+//! C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7
+//! ```
+//!
+//! Logs of other emulators annotate operands with memory contents and add a
+//! `PPU:` field, so [`diff`] only compares the address, the instruction bytes,
+//! the registers and `CYC`.
+
+use std::io::Write;
+
+use crate::{
+  core::{
+    cpu::Registers,
+    decode::{lookup, lookup_cmos},
+    instruction::Instruction,
+    observer::CpuObserver,
+    variant::CpuVariant,
+  },
+  disassembler::Disassembled,
+  error::TraceError,
+};
+
+/// Column the registers start in
+const REGISTERS_COLUMN: usize = 48;
+
+/// Register fields in the order they appear on a line
+const REGISTER_FIELDS: [&str; 6] = ["A", "X", "Y", "P", "SP", "CYC"];
+
+/// Writes a line per instruction in the nestest format. Undocumented op codes
+/// are marked with a `*` in front of their mnemonic.
+pub struct NestestTracer<W: Write> {
+  writer: W,
+  fetched: Option<(u16, u8, Registers, u64)>,
+}
+
+impl<W: Write> NestestTracer<W> {
+  pub fn new(writer: W) -> Self {
+    Self { writer, fetched: None }
+  }
+
+  pub fn get_ref(&self) -> &W {
+    &self.writer
+  }
+
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+impl<W: Write> CpuObserver for NestestTracer<W> {
+  fn fetch(&mut self, address: u16, opcode: u8, registers: &Registers, clock: u64) {
+    self.fetched = Some((address, opcode, *registers, clock));
+  }
+
+  fn decoded(&mut self, instruction: &Instruction) {
+    let Some((address, opcode, registers, clock)) = self.fetched.take() else {
+      return;
+    };
+
+    let mut bytes = vec![opcode];
+    bytes.extend(instruction.mode.operand());
+    let hex = bytes
+      .iter()
+      .map(|byte| format!("{byte:02X}"))
+      .collect::<Vec<_>>()
+      .join(" ");
+    let text = Disassembled {
+      address,
+      bytes,
+      instruction: Some((instruction.opcode, instruction.mode)),
+    }
+    .to_string();
+    let marker = match is_documented(opcode, instruction) {
+      true => ' ',
+      false => '*',
+    };
+    let Registers {
+      a,
+      x,
+      y,
+      stack_ptr,
+      status,
+      ..
+    } = registers;
+
+    let line = format!("{address:04X}  {hex:<8} {marker}{text}");
+    // a tracer has nowhere to report a failed write, so it stops silently
+    let _ = writeln!(
+      self.writer,
+      "{line:<REGISTERS_COLUMN$}A:{a:02X} X:{x:02X} Y:{y:02X} P:{status:02X} SP:{stack_ptr:02X} CYC:{clock}"
+    );
+  }
+}
+
+/// Whether any member of the family documents `opcode` as `instruction`
+fn is_documented(opcode: u8, instruction: &Instruction) -> bool {
+  let documented = lookup(opcode).or_else(|| lookup_cmos(CpuVariant::Wdc65C02, opcode));
+  documented.is_some_and(|(documented, ..)| documented == instruction.opcode)
+}
+
+/// Compares the trace of a run with a reference log line by line and stops at
+/// the first field that differs. Fields missing from the reference are not
+/// compared and blank lines are skipped in both. Returns the number of lines
+/// that matched, which is every line of the reference unless the run ended
+/// early.
+pub fn diff(expected: &str, actual: &str) -> Result<usize, TraceError> {
+  let mut actual_lines = actual.lines().filter(|line| !line.trim().is_empty());
+
+  for (index, expected_line) in expected.lines().enumerate() {
+    let line = index + 1;
+    if expected_line.trim().is_empty() {
+      continue;
+    }
+    let Some(actual_line) = actual_lines.next() else {
+      return Err(TraceError::Ended { line });
+    };
+
+    let expected_fields = fields(expected_line).ok_or_else(|| TraceError::Syntax {
+      line,
+      text: expected_line.into(),
+    })?;
+    let actual_fields = fields(actual_line).ok_or_else(|| TraceError::Syntax {
+      line,
+      text: actual_line.into(),
+    })?;
+
+    for (field, expected_value) in expected_fields {
+      let actual_value = actual_fields
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|(_, value)| value.as_str());
+      if actual_value != Some(expected_value.as_str()) {
+        return Err(TraceError::Mismatch {
+          line,
+          field,
+          expected: expected_value,
+          actual: actual_value.unwrap_or("nothing").into(),
+          expected_line: expected_line.into(),
+          actual_line: actual_line.into(),
+        });
+      }
+    }
+  }
+
+  Ok(expected.lines().filter(|line| !line.trim().is_empty()).count())
+}
+
+/// Splits a line into the fields [`diff`] compares, or `None` if it does not
+/// start with an address
+fn fields(line: &str) -> Option<Vec<(&'static str, String)>> {
+  let address = line.get(0..4)?;
+  u16::from_str_radix(address, 16).ok()?;
+
+  // the bytes sit between the address and the mnemonic, which may be marked
+  let bytes = line
+    .get(4..)?
+    .split_whitespace()
+    .take_while(|token| token.len() == 2 && token.chars().all(|c| c.is_ascii_hexdigit()))
+    .take(3)
+    .collect::<Vec<_>>()
+    .join(" ");
+
+  let mut fields = vec![("PC", address.to_uppercase()), ("bytes", bytes.to_uppercase())];
+  for field in REGISTER_FIELDS {
+    let tag = format!(" {field}:");
+    if let Some(start) = line.find(&tag) {
+      let value = line[start + tag.len()..].split_whitespace().next().unwrap_or_default();
+      fields.push((field, value.to_uppercase()));
+    }
+  }
+  Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+  //! Runs the nestest ROM from https://www.qmtpro.com/~nes/misc/ in its
+  //! automated mode and compares the trace with `nestest.log`. Neither file is
+  //! bundled: put both in `tests/nestest`, or point `NESTEST` at the directory
+  //! holding them, then run `cargo test nestest -- --ignored`.
+
+  use std::fs;
+
+  use super::*;
+  use crate::core::{cpu::Cpu, fixtures, ram::Ram};
+
+  const DEFAULT_DIRECTORY: &str = "tests/nestest";
+
+  /// Where the automated mode starts, instead of the reset vector
+  const ENTRY: u16 = 0xC000;
+
+  const HEADER: usize = 16;
+  const PRG_BANK: usize = 0x4000;
+
+  #[test]
+  #[ignore = "needs the nestest ROM and log"]
+  fn nestest() {
+    let directory = fixtures::directory("NESTEST", DEFAULT_DIRECTORY);
+    let (Ok(rom), Ok(log)) = (
+      fs::read(directory.join("nestest.nes")),
+      fs::read_to_string(directory.join("nestest.log")),
+    ) else {
+      panic!("`{}` does not hold nestest.nes and nestest.log", directory.display());
+    };
+
+    let mut cpu = Cpu::with_variant(1.0, CpuVariant::Ricoh2A03).with_observer(NestestTracer::new(Vec::new()));
+    cpu.set_undocumented_opcodes(true);
+    connect_rom(&mut cpu, &rom);
+    cpu.reset();
+    cpu.set_registers(Registers {
+      program_counter: ENTRY,
+      ..cpu.registers()
+    });
+
+    let lines = log.lines().filter(|line| !line.trim().is_empty()).count();
+    for _ in 0..lines {
+      // the log ends once the ROM is done, which may be on an op code the CPU
+      // refuses, so failing to run is left for the comparison to report
+      if cpu.step_instruction().is_err() {
+        break;
+      }
+    }
+
+    let trace = String::from_utf8(cpu.observer().get_ref().clone()).unwrap();
+    if let Err(error) = diff(&log, &trace) {
+      panic!("{error}");
+    }
+  }
+
+  const LOG: &str = "\
+C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7
+C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD CYC:10
+
+C5F7  86 00     STX $00                         A:00 X:00 Y:00 P:26 SP:FD CYC:12
+";
+
+  #[test]
+  fn diff_matches_logs_that_agree_on_the_compared_fields() {
+    // other emulators annotate operands and add the PPU position
+    let trace = "\
+C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10
+C5F7  86 00     STX $00 = 00                    A:00 X:00 Y:00 P:26 SP:FD PPU:  0, 36 CYC:12
+";
+    assert_eq!(diff(LOG, trace), Ok(3));
+    assert_eq!(diff(LOG, LOG), Ok(3));
+  }
+
+  #[test]
+  fn diff_reports_the_first_field_that_differs() {
+    let trace = LOG.replace("P:24 SP:FD CYC:10", "P:A4 SP:FD CYC:11");
+    let actual_line = "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:A4 SP:FD CYC:11";
+    assert_eq!(
+      diff(LOG, &trace),
+      Err(TraceError::Mismatch {
+        line: 2,
+        field: "P",
+        expected: "24".into(),
+        actual: "A4".into(),
+        expected_line: LOG.lines().nth(1).unwrap().into(),
+        actual_line: actual_line.into(),
+      })
+    );
+
+    // blank lines of the log still count towards the line number
+    let trace = LOG.replace("86 00", "86 01");
+    assert!(matches!(
+      diff(LOG, &trace),
+      Err(TraceError::Mismatch {
+        line: 4,
+        field: "bytes",
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn diff_reports_a_trace_that_ends_early() {
+    let trace = LOG.lines().take(2).collect::<Vec<_>>().join("\n");
+    assert_eq!(diff(LOG, &trace), Err(TraceError::Ended { line: 4 }));
+    assert_eq!(diff(LOG, ""), Err(TraceError::Ended { line: 1 }));
+  }
+
+  #[test]
+  fn diff_rejects_lines_without_an_address() {
+    let trace = LOG.replacen("C000", "JMP ", 1);
+    assert!(matches!(diff(LOG, &trace), Err(TraceError::Syntax { line: 1, .. })));
+  }
+
+  /// Maps 2 KiB of zeroed work RAM and the PRG ROM of an NROM cartridge,
+  /// mirroring a single 16 KiB bank
+  fn connect_rom(cpu: &mut Cpu<NestestTracer<Vec<u8>>>, rom: &[u8]) {
+    assert!(rom.starts_with(b"NES\x1A"), "nestest.nes is not an iNES file");
+    let banks = rom[4] as usize;
+    let prg = &rom[HEADER..HEADER + banks * PRG_BANK];
+
+    let mut ram = Ram::new(0x0000..=0x07FF);
+    ram.write_all(0x0000, &[0; 0x0800]);
+    cpu.connect(ram);
+
+    let mut cartridge = Ram::new(0x8000..=0xFFFF);
+    cartridge.write_all(0x0000, prg);
+    if banks == 1 {
+      cartridge.write_all(PRG_BANK as u16, prg);
+    }
+    cpu.connect(cartridge);
+  }
+}
@@ -0,0 +1,205 @@
+use std::io::{self, Stdout, Write};
+
+use crate::core::{cpu::Registers, instruction::Instruction, step::Interrupt};
+
+/// Receives what the CPU does as it runs. Every method does nothing by
+/// default, so implementations only override what they are interested in.
+pub trait CpuObserver {
+  /// The op code at `address` was fetched. `registers` are the ones the
+  /// instruction starts with and `clock` the cycle it starts on.
+  fn fetch(&mut self, _address: u16, _opcode: u8, _registers: &Registers, _clock: u64) {}
+
+  /// The instruction fetched last was decoded, operand included
+  fn decoded(&mut self, _instruction: &Instruction) {}
+
+  /// The instruction fetched last finished after `cycles` cycles
+  fn executed(&mut self, _instruction: &Instruction, _registers: &Registers, _cycles: u8) {}
+
+  /// The CPU read `value` from `address`
+  fn read(&mut self, _address: u16, _value: u8) {}
+
+  /// The CPU wrote `value` to `address`
+  fn write(&mut self, _address: u16, _value: u8) {}
+
+  /// An IRQ or NMI is about to be serviced
+  fn interrupt(&mut self, _interrupt: Interrupt) {}
+
+  fn reset(&mut self) {}
+}
+
+/// Observer of a CPU nobody is watching, which compiles down to nothing
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopObserver;
+
+impl CpuObserver for NoopObserver {}
+
+/// Writes the registers before and after every instruction, to standard
+/// output by default
+#[derive(Debug)]
+pub struct HumanTracer<W: Write = Stdout> {
+  writer: W,
+  before: Option<(Registers, u64)>,
+}
+
+impl Default for HumanTracer {
+  fn default() -> Self {
+    Self::new(io::stdout())
+  }
+}
+
+impl<W: Write> HumanTracer<W> {
+  pub fn new(writer: W) -> Self {
+    Self { writer, before: None }
+  }
+
+  pub fn get_ref(&self) -> &W {
+    &self.writer
+  }
+
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+impl<W: Write> CpuObserver for HumanTracer<W> {
+  fn fetch(&mut self, _address: u16, _opcode: u8, registers: &Registers, clock: u64) {
+    self.before = Some((*registers, clock));
+  }
+
+  fn executed(&mut self, instruction: &Instruction, registers: &Registers, cycles: u8) {
+    if let Some((before, clock)) = self.before.take() {
+      // a trace that cannot be written is lost rather than stopping the CPU
+      let _ = writeln!(
+        self.writer,
+        "[{clock}]\nBefore: [{}]\n  => {instruction:?}\nAfter: [{}]\n",
+        describe(&before, 0),
+        describe(registers, cycles)
+      );
+    }
+  }
+}
+
+/// Formats registers the way the `Debug` output of [`super::cpu::Cpu`] does
+pub(crate) fn describe(registers: &Registers, cycles: u8) -> String {
+  let Registers {
+    a,
+    x,
+    y,
+    stack_ptr,
+    program_counter,
+    ..
+  } = *registers;
+  format!(
+    "a: {a:#04X} ({a}) | x: {x:#04X} ({x}) | y: {y:#04X} ({y}) | stack_ptr: {stack_ptr:#04X} | prog_counter: {program_counter:#06X} | cycles: {cycles}"
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::{cpu::Cpu, instruction::OpCode, ram::Ram};
+
+  #[derive(Debug, PartialEq)]
+  enum Event {
+    Fetch(u16, u8, u64),
+    Decoded(OpCode),
+    Executed(OpCode, u8),
+    Read(u16, u8),
+    Write(u16, u8),
+    Interrupted(Interrupt),
+    Reset,
+  }
+
+  #[derive(Default)]
+  struct Recorder(Vec<Event>);
+
+  impl CpuObserver for Recorder {
+    fn fetch(&mut self, address: u16, opcode: u8, _registers: &Registers, clock: u64) {
+      self.0.push(Event::Fetch(address, opcode, clock));
+    }
+
+    fn decoded(&mut self, instruction: &Instruction) {
+      self.0.push(Event::Decoded(instruction.opcode));
+    }
+
+    fn executed(&mut self, instruction: &Instruction, _registers: &Registers, cycles: u8) {
+      self.0.push(Event::Executed(instruction.opcode, cycles));
+    }
+
+    fn read(&mut self, address: u16, value: u8) {
+      self.0.push(Event::Read(address, value));
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+      self.0.push(Event::Write(address, value));
+    }
+
+    fn interrupt(&mut self, interrupt: Interrupt) {
+      self.0.push(Event::Interrupted(interrupt));
+    }
+
+    fn reset(&mut self) {
+      self.0.push(Event::Reset);
+    }
+  }
+
+  /// A reset CPU with `program` at `$0200`, watched by `observer`
+  fn load<O: CpuObserver>(program: &[u8], observer: O) -> Cpu<O> {
+    let mut ram = Ram::new(0x0000..=0xFFFF);
+    ram.write_all(0x0200, program);
+    ram.write_all(0xFFFC, &[0x00, 0x02]);
+    ram.write_all(0xFFFA, &[0x00, 0x03]);
+    let mut cpu = Cpu::new(1.0).with_observer(observer);
+    cpu.connect(ram);
+    cpu.reset();
+    cpu
+  }
+
+  #[test]
+  fn hooks_see_everything_the_cpu_does() {
+    use Event::*;
+
+    // LDA $10; STA $11
+    let mut cpu = load(&[0xA5, 0x10, 0x85, 0x11], Recorder::default());
+    cpu.step_instruction().unwrap();
+    cpu.step_instruction().unwrap();
+    cpu.trigger_nmi();
+    cpu.step_instruction().unwrap();
+    assert_eq!(cpu.observer().0, [
+      Reset,
+      Read(0x0200, 0xA5),
+      Fetch(0x0200, 0xA5, 7),
+      Read(0x0201, 0x10),
+      Decoded(OpCode::LDA),
+      Read(0x0010, 0xEA),
+      Executed(OpCode::LDA, 3),
+      Read(0x0202, 0x85),
+      Fetch(0x0202, 0x85, 10),
+      Read(0x0203, 0x11),
+      Decoded(OpCode::STA),
+      Write(0x0011, 0xEA),
+      Executed(OpCode::STA, 3),
+      Interrupted(Interrupt::Nmi),
+      Write(0x01FD, 0x02),
+      Write(0x01FC, 0x04),
+      Write(0x01FB, 0xA4),
+      Read(0xFFFA, 0x00),
+      Read(0xFFFB, 0x03),
+    ]);
+  }
+
+  #[test]
+  fn human_tracer_writes_the_registers_before_and_after_each_instruction() {
+    // LDA #$42
+    let mut cpu = load(&[0xA9, 0x42], HumanTracer::new(Vec::new()));
+    cpu.step_instruction().unwrap();
+    let trace = String::from_utf8(cpu.observer().get_ref().clone()).unwrap();
+    assert_eq!(
+      trace,
+      "[7]\n\
+       Before: [a: 0x00 (0) | x: 0x00 (0) | y: 0x00 (0) | stack_ptr: 0xFD | prog_counter: 0x0200 | cycles: 0]\n\
+       \x20 => Instruction { opcode: LDA, mode: Immediate(66), cycles: 2 }\n\
+       After: [a: 0x42 (66) | x: 0x00 (0) | y: 0x00 (0) | stack_ptr: 0xFD | prog_counter: 0x0202 | cycles: 2]\n\n"
+    );
+  }
+}
@@ -2,6 +2,8 @@ use std::ops::RangeInclusive;
 
 use crate::core::bus::BusItem;
 
+/// Memory covering `address_range`. Bus addresses are translated to offsets
+/// from the start of the range, so RAM at `$8000` keeps `$8000` at offset 0.
 pub struct Ram {
   address_range: RangeInclusive<u16>,
   data: Vec<u8>,
@@ -15,11 +17,8 @@ impl Ram {
   }
 
   pub fn write_all(&mut self, offset: u16, data: &[u8]) {
-    let mut offset = offset as usize;
-    for byte in data {
-      self.data[offset] = *byte;
-      offset += 1;
-    }
+    let offset = offset as usize;
+    self.data[offset..offset + data.len()].copy_from_slice(data);
   }
 }
 
@@ -29,11 +28,13 @@ impl BusItem for Ram {
   }
 
   fn read(&mut self, address: u16, _read_only: bool) -> u8 {
-    self.data.get(address as usize).cloned().unwrap_or_default()
+    let offset = address - self.address_range.start();
+    self.data.get(offset as usize).cloned().unwrap_or_default()
   }
 
   fn write(&mut self, address: u16, value: u8) {
-    match self.data.get_mut(address as usize) {
+    let offset = address - self.address_range.start();
+    match self.data.get_mut(offset as usize) {
       None => (),
       Some(data) => *data = value,
     }
@@ -8,6 +8,8 @@ use std::{
 
 use crate::core::bus::BusItem;
 
+/// Memory backed by a file, addressed from the start of `address_range` like
+/// [`super::ram::Ram`]
 pub struct RamFile {
   address_range: RangeInclusive<u16>,
   file: File,
@@ -42,8 +44,10 @@ impl RamFile {
     Self { address_range, file }
   }
 
-  pub fn write_all(&mut self, address: u16, data: &[u8]) {
-    self.file.seek(SeekFrom::Start(address as u64)).unwrap();
+  /// Writes `data` at `offset` from the start of the address range, like
+  /// [`super::ram::Ram::write_all`]
+  pub fn write_all(&mut self, offset: u16, data: &[u8]) {
+    self.file.seek(SeekFrom::Start(offset as u64)).unwrap();
     self.file.write_all(data).unwrap();
   }
 }
@@ -54,14 +58,16 @@ impl BusItem for RamFile {
   }
 
   fn read(&mut self, address: u16, _read_only: bool) -> u8 {
+    let offset = address - self.address_range.start();
     let mut buffer = [0; 1];
-    self.file.seek(SeekFrom::Start(address as u64)).unwrap();
+    self.file.seek(SeekFrom::Start(offset as u64)).unwrap();
     self.file.read_exact(&mut buffer).unwrap();
     buffer[0]
   }
 
   fn write(&mut self, address: u16, value: u8) {
-    self.file.seek(SeekFrom::Start(address as u64)).unwrap();
+    let offset = address - self.address_range.start();
+    self.file.seek(SeekFrom::Start(offset as u64)).unwrap();
     self.file.write_all(&[value]).unwrap();
     self.file.sync_all().unwrap();
   }
@@ -0,0 +1,256 @@
+use std::{
+  fmt::{Display, Formatter},
+  thread,
+  time::{Duration, Instant},
+};
+
+use crate::{
+  core::{cpu::Cpu, observer::CpuObserver},
+  error::{CpuError, SpeedError},
+};
+
+/// Emulated time run between two checks of the wall clock
+const DEFAULT_BATCH: Duration = Duration::from_millis(2);
+
+/// How far the CPU may fall behind the wall clock before the schedule gives
+/// up on catching up, e.g. after the process was suspended
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// How fast a [`Scheduler`] runs the CPU relative to its clock speed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+  /// `1.0` is the clock speed of the CPU, `0.5` half and `2.0` twice that.
+  /// Must be positive and finite.
+  Multiplier(f64),
+  /// As fast as the host allows
+  Unthrottled,
+}
+
+impl Default for Speed {
+  fn default() -> Self {
+    Self::Multiplier(1.0)
+  }
+}
+
+/// Achieved against requested frequency, in Hz
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frequency {
+  /// `None` when running unthrottled
+  pub requested: Option<f64>,
+  pub achieved: f64,
+}
+
+impl Display for Frequency {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self.requested {
+      Some(requested) => write!(
+        f,
+        "{} of {} ({:.1}%)",
+        hertz(self.achieved),
+        hertz(requested),
+        self.achieved / requested * 100.0
+      ),
+      None => write!(f, "{} unthrottled", hertz(self.achieved)),
+    }
+  }
+}
+
+/// Whether `value` can scale a frequency, which rules out zero, negative
+/// values, infinity and NaN
+fn is_positive(value: f64) -> bool {
+  value.is_finite() && value > 0.0
+}
+
+/// Formats a frequency with the largest unit it has at least one of
+fn hertz(frequency: f64) -> String {
+  match frequency {
+    frequency if frequency >= 1e6 => format!("{:.3} MHz", frequency / 1e6),
+    frequency if frequency >= 1e3 => format!("{:.3} kHz", frequency / 1e3),
+    frequency => format!("{frequency:.1} Hz"),
+  }
+}
+
+/// Runs a CPU at its clock speed in real time. Cycles run in batches, after
+/// each of which the scheduler sleeps until the wall clock reaches the time
+/// those cycles take on real hardware. Sleeping to an absolute target rather
+/// than for a fixed duration keeps oversleeping from adding up to drift.
+#[derive(Debug)]
+pub struct Scheduler {
+  clock_speed: f64,
+  speed: Speed,
+  batch: Duration,
+  /// Start of the current schedule and the cycles run since
+  epoch: Option<Instant>,
+  scheduled: u64,
+  /// Start of the current measurement and the cycles run since
+  measured_since: Option<Instant>,
+  measured: u64,
+}
+
+impl Scheduler {
+  /// A scheduler running at `clock_speed` Hz, which must be positive and
+  /// finite
+  pub fn new(clock_speed: f64) -> Result<Self, SpeedError> {
+    if !is_positive(clock_speed) {
+      return Err(SpeedError::InvalidClockSpeed(clock_speed));
+    }
+
+    Ok(Self {
+      clock_speed,
+      speed: Speed::default(),
+      batch: DEFAULT_BATCH,
+      epoch: None,
+      scheduled: 0,
+      measured_since: None,
+      measured: 0,
+    })
+  }
+
+  /// A scheduler running at the clock speed of `cpu`
+  pub fn for_cpu<O: CpuObserver>(cpu: &Cpu<O>) -> Result<Self, SpeedError> {
+    Self::new(cpu.clock_speed())
+  }
+
+  pub fn speed(&self) -> Speed {
+    self.speed
+  }
+
+  /// Changes the speed, which starts a new schedule and measurement
+  pub fn set_speed(&mut self, speed: Speed) -> Result<(), SpeedError> {
+    if let Speed::Multiplier(multiplier) = speed {
+      if !is_positive(multiplier) {
+        return Err(SpeedError::InvalidMultiplier(multiplier));
+      }
+    }
+
+    self.speed = speed;
+    self.epoch = None;
+    self.scheduled = 0;
+    self.reset_measurement();
+    Ok(())
+  }
+
+  /// Sets the emulated time run between two checks of the wall clock. Shorter
+  /// batches track the wall clock more closely at the cost of more sleeps.
+  pub fn set_batch(&mut self, batch: Duration) {
+    self.batch = batch;
+  }
+
+  /// Runs one batch of cycles, then sleeps until it is due. Returns the number
+  /// of cycles run.
+  pub fn run_batch<O: CpuObserver>(&mut self, cpu: &mut Cpu<O>) -> Result<u64, CpuError> {
+    let now = Instant::now();
+    let epoch = *self.epoch.get_or_insert(now);
+    self.measured_since.get_or_insert(now);
+
+    let cycles = self.batch_cycles();
+    cpu.run_cycles(cycles)?;
+    self.scheduled += cycles;
+    self.measured += cycles;
+
+    let Some(frequency) = self.requested() else {
+      return Ok(cycles);
+    };
+    let target = epoch + Duration::from_secs_f64(self.scheduled as f64 / frequency);
+    let now = Instant::now();
+    if target > now {
+      thread::sleep(target - now);
+    } else if now - target > MAX_LAG {
+      // the host cannot keep up, so start over from here instead of running
+      // flat out to make up for lost time
+      self.epoch = Some(now);
+      self.scheduled = 0;
+    }
+
+    Ok(cycles)
+  }
+
+  /// Runs batches until `duration` of wall clock time passed
+  pub fn run_for<O: CpuObserver>(&mut self, cpu: &mut Cpu<O>, duration: Duration) -> Result<u64, CpuError> {
+    let start = Instant::now();
+    let mut cycles = 0;
+    while start.elapsed() < duration {
+      cycles += self.run_batch(cpu)?;
+    }
+    Ok(cycles)
+  }
+
+  /// Frequency achieved since the speed was last set or the measurement reset
+  pub fn frequency(&self) -> Frequency {
+    let elapsed = self
+      .measured_since
+      .map_or(0.0, |measured_since| measured_since.elapsed().as_secs_f64());
+
+    Frequency {
+      requested: self.requested(),
+      achieved: match elapsed > 0.0 {
+        true => self.measured as f64 / elapsed,
+        false => 0.0,
+      },
+    }
+  }
+
+  /// Starts a new measurement for [`Scheduler::frequency`]
+  pub fn reset_measurement(&mut self) {
+    self.measured_since = None;
+    self.measured = 0;
+  }
+
+  /// Frequency in Hz to run at, or `None` when unthrottled
+  fn requested(&self) -> Option<f64> {
+    match self.speed {
+      Speed::Multiplier(multiplier) => Some(self.clock_speed * multiplier),
+      Speed::Unthrottled => None,
+    }
+  }
+
+  /// Cycles in a batch, sized by the clock speed when unthrottled
+  fn batch_cycles(&self) -> u64 {
+    let frequency = self.requested().unwrap_or(self.clock_speed);
+    ((frequency * self.batch.as_secs_f64()) as u64).max(1)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::ram::Ram;
+
+  #[test]
+  fn rejects_speeds_that_are_not_positive() {
+    for clock_speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+      assert!(matches!(Scheduler::new(clock_speed), Err(SpeedError::InvalidClockSpeed(_))));
+    }
+
+    let mut scheduler = Scheduler::new(1_000_000.0).unwrap();
+    for multiplier in [0.0, -0.5, f64::NAN, f64::INFINITY] {
+      assert!(matches!(
+        scheduler.set_speed(Speed::Multiplier(multiplier)),
+        Err(SpeedError::InvalidMultiplier(_))
+      ));
+    }
+    assert_eq!(scheduler.speed(), Speed::default());
+    assert_eq!(scheduler.set_speed(Speed::Multiplier(2.0)), Ok(()));
+    assert_eq!(scheduler.set_speed(Speed::Unthrottled), Ok(()));
+  }
+
+  #[test]
+  fn unthrottled_batches_run_the_cycles_of_the_batch_at_the_clock_speed() {
+    let mut cpu = Cpu::new(1_000_000.0);
+    cpu.connect(Ram::new(0x0000..=0xFFFF));
+    cpu.reset();
+
+    let mut scheduler = Scheduler::for_cpu(&cpu).unwrap();
+    scheduler.set_speed(Speed::Unthrottled).unwrap();
+    assert_eq!(scheduler.run_batch(&mut cpu).unwrap(), 2_000);
+    assert_eq!(cpu.total_cycles(), 2_000);
+
+    scheduler.set_batch(Duration::from_micros(500));
+    assert_eq!(scheduler.run_batch(&mut cpu).unwrap(), 500);
+    assert_eq!(cpu.total_cycles(), 2_500);
+
+    // a batch shorter than a cycle still runs one
+    scheduler.set_batch(Duration::from_nanos(1));
+    assert_eq!(scheduler.run_batch(&mut cpu).unwrap(), 1);
+  }
+}
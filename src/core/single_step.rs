@@ -0,0 +1,242 @@
+//! Runs the single step tests of https://github.com/SingleStepTests/65x02
+//! against the NMOS 6502. Each case gives the registers and memory before and
+//! after one instruction along with every bus cycle in between. The tests are
+//! not bundled: point `SINGLE_STEP_TESTS` at a local copy of the `6502/v1`
+//! directory, or place it in `tests/6502/v1`.
+//!
+//! Run with `cargo test single_step -- --ignored --nocapture` to see the
+//! statistics.
+
+use std::{cell::RefCell, collections::BTreeMap, fmt::Write, fs, ops::RangeInclusive, rc::Rc};
+
+use serde::Deserialize;
+
+use crate::core::{
+  bus::BusItem,
+  cpu::{Cpu, Registers},
+  fixtures,
+  instruction::{Instruction, OpCode},
+  step::StepEvent,
+};
+
+const DEFAULT_DIRECTORY: &str = "tests/6502/v1";
+
+/// B and the unused bit only exist in a pushed status, so they are not
+/// compared
+const STATUS_MASK: u8 = 0xCF;
+
+#[derive(Deserialize)]
+struct Case {
+  name: String,
+  initial: State,
+  #[serde(rename = "final")]
+  expected: State,
+  cycles: Vec<(u16, u8, Kind)>,
+}
+
+#[derive(Deserialize)]
+struct State {
+  pc: u16,
+  s: u8,
+  a: u8,
+  x: u8,
+  y: u8,
+  p: u8,
+  ram: Vec<(u16, u8)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+  Read,
+  Write,
+}
+
+/// Flat 64 KiB of memory that records every access the CPU makes
+struct TestRam {
+  range: RangeInclusive<u16>,
+  memory: Vec<u8>,
+  cycles: Rc<RefCell<Vec<(u16, u8, Kind)>>>,
+}
+
+impl BusItem for TestRam {
+  fn address_range(&self) -> &RangeInclusive<u16> {
+    &self.range
+  }
+
+  fn read(&mut self, address: u16, read_only: bool) -> u8 {
+    let value = self.memory[address as usize];
+    if !read_only {
+      self.cycles.borrow_mut().push((address, value, Kind::Read));
+    }
+    value
+  }
+
+  fn write(&mut self, address: u16, value: u8) {
+    self.memory[address as usize] = value;
+    self.cycles.borrow_mut().push((address, value, Kind::Write));
+  }
+}
+
+#[derive(Default)]
+struct Stats {
+  passed: usize,
+  failed: usize,
+  /// Cases of `JAM`, after which the bus activity is not meaningful
+  skipped: usize,
+  first_failure: Option<String>,
+}
+
+enum Outcome {
+  Passed,
+  Failed(String),
+  Skipped,
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests fixtures"]
+fn single_step() {
+  let directory = fixtures::directory("SINGLE_STEP_TESTS", DEFAULT_DIRECTORY);
+  let mut files: Vec<_> = fs::read_dir(&directory)
+    .unwrap()
+    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+    .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+    .collect();
+  files.sort();
+  assert!(!files.is_empty(), "`{}` holds no tests", directory.display());
+
+  let mut stats: BTreeMap<u8, Stats> = BTreeMap::new();
+  for file in files {
+    let text = fs::read_to_string(&file).unwrap();
+    let cases: Vec<Case> = serde_json::from_str(&text).unwrap_or_else(|error| panic!("{}: {error}", file.display()));
+
+    for case in &cases {
+      let opcode = opcode(case);
+      let stats = stats.entry(opcode).or_default();
+      match run(case) {
+        Outcome::Passed => stats.passed += 1,
+        Outcome::Skipped => stats.skipped += 1,
+        Outcome::Failed(message) => {
+          stats.failed += 1;
+          stats
+            .first_failure
+            .get_or_insert_with(|| format!("{}: {message}", case.name));
+        }
+      }
+    }
+  }
+
+  println!("op  passed  failed  skipped");
+  for (opcode, stats) in &stats {
+    println!("{opcode:02X}  {:>6}  {:>6}  {:>7}", stats.passed, stats.failed, stats.skipped);
+    if let Some(failure) = &stats.first_failure {
+      println!("    {failure}");
+    }
+  }
+
+  let failed = stats.values().filter(|stats| stats.failed > 0).count();
+  assert_eq!(failed, 0, "{failed} op codes failed");
+}
+
+fn opcode(case: &Case) -> u8 {
+  case
+    .initial
+    .ram
+    .iter()
+    .find(|(address, _)| *address == case.initial.pc)
+    .map_or(0, |(_, value)| *value)
+}
+
+/// Runs the single instruction of `case` in cycle stepped mode and compares
+/// the registers, memory and bus cycles with the expected ones
+fn run(case: &Case) -> Outcome {
+  let cycles = Rc::new(RefCell::new(Vec::new()));
+  let mut cpu = Cpu::new(1.0);
+  cpu.set_undocumented_opcodes(true);
+  cpu.set_cycle_stepped(true);
+  cpu.connect(TestRam {
+    range: 0x0000..=0xFFFF,
+    memory: vec![0; 0x10000],
+    cycles: cycles.clone(),
+  });
+
+  let State {
+    pc,
+    s,
+    a,
+    x,
+    y,
+    p,
+    ref ram,
+  } = case.initial;
+  for &(address, value) in ram {
+    cpu.write(address, value);
+  }
+  cpu.set_registers(Registers {
+    a,
+    x,
+    y,
+    stack_ptr: s,
+    program_counter: pc,
+    status: p,
+  });
+  cycles.borrow_mut().clear();
+
+  match cpu.step_instruction() {
+    // the bus activity of a jammed CPU is not meaningful
+    Ok(step)
+      if matches!(
+        step.event,
+        StepEvent::Instruction(Instruction {
+          opcode: OpCode::JAM,
+          ..
+        })
+      ) =>
+    {
+      return Outcome::Skipped
+    }
+    Ok(_) => (),
+    Err(error) => return Outcome::Failed(error.to_string()),
+  }
+
+  let mut errors = String::new();
+  let registers = cpu.registers();
+  let expected = &case.expected;
+  let mut compare = |name: &str, actual: u16, expected: u16| {
+    if actual != expected {
+      write!(errors, "{name}: expected ${expected:02X}, got ${actual:02X}; ").unwrap();
+    }
+  };
+  compare("pc", registers.program_counter, expected.pc);
+  compare("s", registers.stack_ptr as u16, expected.s as u16);
+  compare("a", registers.a as u16, expected.a as u16);
+  compare("x", registers.x as u16, expected.x as u16);
+  compare("y", registers.y as u16, expected.y as u16);
+  compare("p", (registers.status & STATUS_MASK) as u16, (expected.p & STATUS_MASK) as u16);
+  for &(address, value) in &expected.ram {
+    compare(&format!("${address:04X}"), cpu.peek(address) as u16, value as u16);
+  }
+
+  let actual = cycles.borrow();
+  if let Some(cycle) =
+    (0..actual.len().max(case.cycles.len())).find(|&cycle| actual.get(cycle) != case.cycles.get(cycle))
+  {
+    let format = |access: Option<&(u16, u8, Kind)>| match access {
+      Some((address, value, kind)) => format!("{kind:?} ${address:04X} = ${value:02X}"),
+      None => "nothing".into(),
+    };
+    write!(
+      errors,
+      "cycle {}: expected {}, got {}",
+      cycle + 1,
+      format(case.cycles.get(cycle)),
+      format(actual.get(cycle))
+    )
+    .unwrap();
+  }
+
+  match errors.is_empty() {
+    true => Outcome::Passed,
+    false => Outcome::Failed(errors.trim_end_matches("; ").into()),
+  }
+}
@@ -0,0 +1,181 @@
+use crate::{
+  core::{cpu::Cpu, instruction::Instruction, observer::CpuObserver},
+  error::CpuError,
+};
+
+/// What [`Cpu::step_instruction`] ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+  /// Program counter before the step
+  pub address: u16,
+  pub event: StepEvent,
+  pub cycles: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepEvent {
+  Instruction(Instruction),
+  Interrupt(Interrupt),
+  /// A cycle spent stopped by `JAM` or `STP`
+  Jammed,
+  /// A cycle spent in `WAI`
+  Waiting,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+  Irq,
+  Nmi,
+}
+
+impl<O: CpuObserver> Cpu<O> {
+  /// Runs a single cycle
+  pub fn step_cycle(&mut self) -> Result<(), CpuError> {
+    self.cycle().map(|_| ())
+  }
+
+  /// Runs the next instruction or interrupt sequence to completion. An
+  /// instruction left unfinished by [`Cpu::step_cycle`] is completed first.
+  /// A stopped or waiting CPU only runs one cycle.
+  pub fn step_instruction(&mut self) -> Result<StepInfo, CpuError> {
+    self.finish()?;
+
+    let address = self.program_counter;
+    let start = self.clock;
+    let event = match self.cycle()? {
+      Some(event) => event,
+      None if self.is_jammed() => StepEvent::Jammed,
+      None => StepEvent::Waiting,
+    };
+    self.finish()?;
+
+    Ok(StepInfo {
+      address,
+      event,
+      cycles: self.clock - start,
+    })
+  }
+
+  pub fn run_cycles(&mut self, cycles: u64) -> Result<(), CpuError> {
+    for _ in 0..cycles {
+      self.cycle()?;
+    }
+    Ok(())
+  }
+
+  /// Runs whole steps until `predicate` holds after one of them and returns
+  /// the number of cycles that took
+  pub fn run_until(&mut self, mut predicate: impl FnMut(&Self, &StepInfo) -> bool) -> Result<u64, CpuError> {
+    let start = self.clock;
+    loop {
+      let step = self.step_instruction()?;
+      if predicate(self, &step) {
+        return Ok(self.clock - start);
+      }
+    }
+  }
+
+  /// Runs the remaining cycles of the instruction in progress
+  fn finish(&mut self) -> Result<(), CpuError> {
+    while self.cycles > 0 {
+      self.cycle()?;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::core::{
+    instruction::{AddressingMode, OpCode},
+    ram::Ram,
+    variant::CpuVariant,
+  };
+
+  /// A CPU with `program` at `$0200` that already ran its reset sequence
+  fn load(variant: CpuVariant, program: &[u8]) -> Cpu {
+    let mut ram = Ram::new(0x0000..=0xFFFF);
+    ram.write_all(0x0200, program);
+    ram.write_all(0xFFFC, &[0x00, 0x02]);
+    let mut cpu = Cpu::with_variant(1.0, variant);
+    cpu.set_undocumented_opcodes(true);
+    cpu.connect(ram);
+    cpu.reset();
+    cpu.run_cycles(7).unwrap();
+    cpu
+  }
+
+  #[test]
+  fn steps_report_what_ran() {
+    // LDA #$42
+    let mut cpu = load(CpuVariant::Nmos6502, &[0xA9, 0x42]);
+    assert_eq!(cpu.step_instruction().unwrap(), StepInfo {
+      address: 0x0200,
+      event: StepEvent::Instruction(Instruction {
+        opcode: OpCode::LDA,
+        mode: AddressingMode::Immediate(0x42),
+        cycles: 2,
+      }),
+      cycles: 2,
+    });
+    assert_eq!(cpu.registers().a, 0x42);
+    assert_eq!(cpu.total_cycles(), 7 + 2);
+  }
+
+  #[test]
+  fn steps_finish_the_instruction_in_progress_first() {
+    // LDA $1234; NOP
+    let mut cpu = load(CpuVariant::Nmos6502, &[0xAD, 0x34, 0x12, 0xEA]);
+    cpu.step_cycle().unwrap();
+    let step = cpu.step_instruction().unwrap();
+    assert_eq!(step.address, 0x0203);
+    assert_eq!(step.cycles, 2);
+    assert_eq!(cpu.total_cycles(), 7 + 4 + 2);
+  }
+
+  #[test]
+  fn stopped_and_waiting_cpus_step_one_cycle() {
+    // JAM
+    let mut cpu = load(CpuVariant::Nmos6502, &[0x02]);
+    cpu.step_instruction().unwrap();
+    let step = cpu.step_instruction().unwrap();
+    assert_eq!((step.event, step.cycles), (StepEvent::Jammed, 1));
+
+    // WAI
+    let mut cpu = load(CpuVariant::Wdc65C02, &[0xCB]);
+    cpu.step_instruction().unwrap();
+    let step = cpu.step_instruction().unwrap();
+    assert_eq!((step.event, step.cycles), (StepEvent::Waiting, 1));
+  }
+
+  #[test]
+  fn run_until_stops_after_the_step_the_predicate_holds_for() {
+    // LDX #$00; loop: INX; JMP loop
+    let program = [0xA2, 0x00, 0xE8, 0x4C, 0x02, 0x02];
+
+    let mut cpu = load(CpuVariant::Nmos6502, &program);
+    let cycles = cpu.run_until(|cpu, _| cpu.registers().x == 3).unwrap();
+    assert_eq!(cycles, 2 + 3 * 2 + 2 * 3);
+    assert_eq!(cpu.registers().program_counter, 0x0203);
+
+    // the predicate sees each step, and holding for the first one stops there
+    let mut cpu = load(CpuVariant::Nmos6502, &program);
+    let mut addresses = Vec::new();
+    let cycles = cpu
+      .run_until(|_, step| {
+        addresses.push(step.address);
+        matches!(
+          step.event,
+          StepEvent::Instruction(Instruction {
+            opcode: OpCode::JMP,
+            ..
+          })
+        )
+      })
+      .unwrap();
+    assert_eq!(cycles, 2 + 2 + 3);
+    assert_eq!(addresses, [0x0200, 0x0202, 0x0203]);
+    assert_eq!(cpu.run_until(|_, _| true).unwrap(), 2);
+  }
+}
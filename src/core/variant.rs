@@ -0,0 +1,39 @@
+/// Member of the 6502 family to emulate
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CpuVariant {
+  /// Original NMOS 6502, with the indirect `JMP` page bug and optional
+  /// undocumented op codes
+  #[default]
+  Nmos6502,
+  /// CMOS 65C02 with the new instructions and `(zp)` addressing, where every
+  /// undefined op code is a `NOP`
+  Cmos65C02,
+  /// 65C02 plus the Rockwell `BBR`, `BBS`, `RMB` and `SMB` bit instructions
+  Rockwell65C02,
+  /// Rockwell 65C02 plus `WAI` and `STP`
+  Wdc65C02,
+  /// NMOS core of the NES without decimal arithmetic
+  Ricoh2A03,
+}
+
+impl CpuVariant {
+  /// Whether this is one of the CMOS 65C02 variants
+  pub fn is_cmos(&self) -> bool {
+    matches!(self, Self::Cmos65C02 | Self::Rockwell65C02 | Self::Wdc65C02)
+  }
+
+  /// Whether `BBR`, `BBS`, `RMB` and `SMB` are available
+  pub fn has_bit_instructions(&self) -> bool {
+    matches!(self, Self::Rockwell65C02 | Self::Wdc65C02)
+  }
+
+  /// Whether `WAI` and `STP` are available
+  pub fn has_wait_and_stop(&self) -> bool {
+    matches!(self, Self::Wdc65C02)
+  }
+
+  /// Whether ADC and SBC honor the decimal flag
+  pub fn has_decimal_mode(&self) -> bool {
+    !matches!(self, Self::Ricoh2A03)
+  }
+}
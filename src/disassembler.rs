@@ -0,0 +1,206 @@
+use std::{
+  collections::HashMap,
+  fmt::{Display, Formatter},
+};
+
+use crate::core::{
+  decode::lookup_variant,
+  instruction::{AddressingMode, OpCode},
+  variant::CpuVariant,
+};
+
+/// Decodes `bytes` as if they were loaded at `origin` without running them.
+/// Decodes the documented NMOS 6502 op codes unless configured otherwise.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Disassembler<'_> {
+  Disassembler {
+    bytes,
+    origin,
+    offset: 0,
+    variant: CpuVariant::default(),
+    undocumented_opcodes: false,
+  }
+}
+
+/// Iterator over the instructions in a slice of machine code
+pub struct Disassembler<'a> {
+  bytes: &'a [u8],
+  origin: u16,
+  offset: usize,
+  variant: CpuVariant,
+  undocumented_opcodes: bool,
+}
+
+impl Disassembler<'_> {
+  /// Decodes the op codes of `variant`, like a [`crate::core::cpu::Cpu`] of
+  /// it would
+  pub fn with_variant(mut self, variant: CpuVariant) -> Self {
+    self.variant = variant;
+    self
+  }
+
+  /// Decodes the undocumented NMOS op codes such as `LAX` instead of
+  /// rendering them as `.byte`
+  pub fn with_undocumented_opcodes(mut self, enabled: bool) -> Self {
+    self.undocumented_opcodes = enabled;
+    self
+  }
+}
+
+/// One decoded instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembled {
+  pub address: u16,
+  pub bytes: Vec<u8>,
+  /// `None` for a byte that is not a valid op code or starts an instruction
+  /// cut off by the end of the input
+  pub instruction: Option<(OpCode, AddressingMode)>,
+}
+
+impl Iterator for Disassembler<'_> {
+  type Item = Disassembled;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let rest = self.bytes.get(self.offset..).filter(|rest| !rest.is_empty())?;
+    let address = self.origin.wrapping_add(self.offset as u16);
+
+    let instruction = lookup_variant(self.variant, self.undocumented_opcodes, rest[0]).and_then(|(opcode, mode, _)| {
+      let len = 1 + mode.operand_len() as usize;
+      let operand = rest.get(1..len)?;
+      Some((opcode, AddressingMode::from_operand(mode, operand), len))
+    });
+
+    let len = instruction.map_or(1, |(.., len)| len);
+    self.offset += len;
+
+    Some(Disassembled {
+      address,
+      bytes: rest[..len].to_vec(),
+      instruction: instruction.map(|(opcode, mode, _)| (opcode, mode)),
+    })
+  }
+}
+
+impl Disassembled {
+  /// Renders the instruction with addresses that appear in `labels` replaced
+  /// by their names
+  pub fn render(&self, labels: &HashMap<u16, String>) -> String {
+    self.format(|address, digits| match labels.get(&address) {
+      Some(label) => label.clone(),
+      None => format!("${address:0digits$X}"),
+    })
+  }
+
+  /// Address of the instruction following this one
+  pub fn next_address(&self) -> u16 {
+    self.address.wrapping_add(self.bytes.len() as u16)
+  }
+
+  /// Formats the instruction, with `address` rendering an operand address
+  /// using the given number of hex digits
+  fn format(&self, address: impl Fn(u16, usize) -> String) -> String {
+    let Some((opcode, mode)) = self.instruction else {
+      return format!(".byte ${:02X}", self.bytes[0]);
+    };
+
+    match mode {
+      AddressingMode::Implied => opcode.to_string(),
+      AddressingMode::Accumulator => format!("{opcode} A"),
+      AddressingMode::Immediate(value) => format!("{opcode} #${value:02X}"),
+      AddressingMode::Relative(offset) => {
+        let target = self.next_address().wrapping_add(offset as i8 as u16);
+        format!("{opcode} {}", address(target, 4))
+      }
+      AddressingMode::ZeroPage(value) => format!("{opcode} {}", address(value as u16, 2)),
+      AddressingMode::ZeroPageX(value) => format!("{opcode} {},X", address(value as u16, 2)),
+      AddressingMode::ZeroPageY(value) => format!("{opcode} {},Y", address(value as u16, 2)),
+      AddressingMode::Absolute(value) => format!("{opcode} {}", address(value, 4)),
+      AddressingMode::AbsoluteX(value) => format!("{opcode} {},X", address(value, 4)),
+      AddressingMode::AbsoluteY(value) => format!("{opcode} {},Y", address(value, 4)),
+      AddressingMode::Indirect(value) => format!("{opcode} ({})", address(value, 4)),
+      AddressingMode::IndirectX(value) => format!("{opcode} ({},X)", address(value as u16, 2)),
+      AddressingMode::IndirectY(value) => format!("{opcode} ({}),Y", address(value as u16, 2)),
+      AddressingMode::ZeroPageIndirect(value) => format!("{opcode} ({})", address(value as u16, 2)),
+      AddressingMode::AbsoluteIndexedIndirect(value) => format!("{opcode} ({},X)", address(value, 4)),
+      AddressingMode::ZeroPageRelative(value, offset) => {
+        let target = self.next_address().wrapping_add(offset as i8 as u16);
+        format!("{opcode} {},{}", address(value as u16, 2), address(target, 4))
+      }
+    }
+  }
+}
+
+impl Display for Disassembled {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.format(|address, digits| format!("${address:0digits$X}")))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn text(disassembler: Disassembler) -> Vec<String> {
+    disassembler.map(|disassembled| disassembled.to_string()).collect()
+  }
+
+  #[test]
+  fn renders_standard_syntax() {
+    let bytes = [0xB1, 0x20, 0xA1, 0x20, 0x6C, 0x34, 0x12, 0x0A, 0xA9, 0x01];
+    assert_eq!(text(disassemble(&bytes, 0x8000)), [
+      "LDA ($20),Y",
+      "LDA ($20,X)",
+      "JMP ($1234)",
+      "ASL A",
+      "LDA #$01"
+    ]);
+  }
+
+  #[test]
+  fn renders_branch_targets() {
+    // BNE forward, BEQ back onto itself
+    let bytes = [0xD0, 0x02, 0xF0, 0xFE];
+    assert_eq!(text(disassemble(&bytes, 0x8000)), ["BNE $8004", "BEQ $8002"]);
+  }
+
+  #[test]
+  fn substitutes_labels() {
+    let labels = HashMap::from([(0x8000, "loop".to_string()), (0x1234, "print".to_string())]);
+    // JSR print; BNE loop; LDA $20
+    let bytes = [0x20, 0x34, 0x12, 0xD0, 0xFB, 0xA5, 0x20];
+    let rendered: Vec<_> = disassemble(&bytes, 0x8000)
+      .map(|disassembled| disassembled.render(&labels))
+      .collect();
+    assert_eq!(rendered, ["JSR print", "BNE loop", "LDA $20"]);
+  }
+
+  #[test]
+  fn renders_truncated_operands_as_bytes() {
+    let disassembled: Vec<_> = disassemble(&[0xEA, 0xAD, 0x00], 0x8000).collect();
+    assert_eq!(disassembled[1], Disassembled {
+      address: 0x8001,
+      bytes: vec![0xAD],
+      instruction: None,
+    });
+    assert_eq!(disassembled[1].to_string(), ".byte $AD");
+    // decoding carries on with the byte after the cut off op code
+    assert_eq!(disassembled[2].address, 0x8002);
+  }
+
+  #[test]
+  fn decodes_the_op_codes_of_the_variant() {
+    // LDA ($20); JMP ($8000,X); BBR0 $20,$8007
+    let bytes = [0xB2, 0x20, 0x7C, 0x00, 0x80, 0x0F, 0x20, 0x00];
+    assert_eq!(text(disassemble(&bytes[..1], 0x8000)), [".byte $B2"]);
+    assert_eq!(text(disassemble(&bytes, 0x8000).with_variant(CpuVariant::Rockwell65C02)), [
+      "LDA ($20)",
+      "JMP ($8000,X)",
+      "BBR0 $20,$8008"
+    ]);
+
+    // LAX $10
+    assert_eq!(text(disassemble(&[0xA7, 0x10], 0x8000))[0], ".byte $A7");
+    assert_eq!(text(disassemble(&[0xA7, 0x10], 0x8000).with_undocumented_opcodes(true)), [
+      "LAX $10"
+    ]);
+  }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::{assembler::Position, core::instruction::OpCode};
+
 #[derive(Error, Debug)]
 pub enum CpuError {
   #[error("op code `{0:02X}` is invalid")]
@@ -13,3 +15,96 @@ pub enum CpuError {
   #[error("{0}")]
   Other(String),
 }
+
+#[derive(Error, Debug)]
+pub enum AssembleError {
+  #[error("{position}: unexpected character `{character}`")]
+  UnexpectedCharacter { position: Position, character: char },
+  #[error("{position}: {message}")]
+  Syntax { position: Position, message: String },
+  #[error("{position}: `{opcode}` does not support {mode} addressing")]
+  InvalidAddressingMode {
+    position: Position,
+    opcode: OpCode,
+    mode: String,
+  },
+  #[error("{position}: undefined symbol `{name}`")]
+  UndefinedLabel { position: Position, name: String },
+  #[error("{position}: symbol `{name}` is already defined at {previous}")]
+  DuplicateLabel {
+    position: Position,
+    name: String,
+    previous: Position,
+  },
+  #[error("{position}: value `{value}` does not fit in {bits} bits")]
+  ValueOutOfRange { position: Position, value: i64, bits: u8 },
+  #[error("{position}: branch target is {offset} bytes away, outside of -128..=127")]
+  BranchOutOfRange { position: Position, offset: i64 },
+  #[error("{position}: address `0x{address:X}` is outside of the address space")]
+  AddressOutOfRange { position: Position, address: u32 },
+  #[error("{position}: address `0x{address:04X}` is written more than once")]
+  Overlap { position: Position, address: u16 },
+  #[error("{position}: file `{path}` was not found")]
+  FileNotFound { position: Position, path: String },
+  #[error("{position}: failed to read `{path}`: {error}")]
+  Read {
+    position: Position,
+    path: String,
+    error: std::io::Error,
+  },
+  #[error("{position}: `{path}` is already being included")]
+  IncludeCycle { position: Position, path: String },
+  #[error("{error}\n  in expansion of macro `{name}` at {position}")]
+  Macro {
+    name: String,
+    position: Position,
+    error: Box<AssembleError>,
+  },
+}
+
+#[derive(Error, Debug)]
+pub enum OutputError {
+  #[error("an image of {size:#X} bytes at `0x{base:04X}` does not fit in the address space")]
+  InvalidImage { base: u16, size: usize },
+  #[error("address `0x{address:04X}` was written but lies outside the image `0x{start:04X}-0x{end:04X}`")]
+  OutsideImage { address: u16, start: u16, end: u16 },
+}
+
+#[derive(Error, Debug)]
+pub enum LoadError {
+  #[error("line {line}: {message}")]
+  Syntax { line: usize, message: String },
+  #[error("line {line}: checksum is `{actual:02X}` but should be `{expected:02X}`")]
+  Checksum { line: usize, expected: u8, actual: u8 },
+  #[error("address `0x{0:X}` lies outside the 16 bit address space")]
+  AddressOutOfRange(u32),
+  #[error("memory address `0x{0:04X}` is not mapped to any device")]
+  Unmapped(u16),
+  #[error("`.prg` file is too short to contain a load address")]
+  MissingLoadAddress,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum TraceError {
+  #[error("line {line}: {field} is `{actual}` but should be `{expected}`\n  expected: {expected_line}\n  actual:   {actual_line}")]
+  Mismatch {
+    line: usize,
+    field: &'static str,
+    expected: String,
+    actual: String,
+    expected_line: String,
+    actual_line: String,
+  },
+  #[error("line {line}: the trace ended before the reference log")]
+  Ended { line: usize },
+  #[error("line {line}: `{text}` is not a trace line")]
+  Syntax { line: usize, text: String },
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SpeedError {
+  #[error("clock speed `{0}` Hz is not a positive number")]
+  InvalidClockSpeed(f64),
+  #[error("speed multiplier `{0}` is not a positive number")]
+  InvalidMultiplier(f64),
+}
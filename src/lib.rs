@@ -1,6 +1,5 @@
-#![feature(bigint_helper_methods)]
-
-// assembler is WIP
-// pub mod assembler;
+pub mod assembler;
 pub mod core;
+pub mod disassembler;
 pub mod error;
+pub mod loader;
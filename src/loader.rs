@@ -0,0 +1,355 @@
+use crate::{
+  assembler::Program,
+  core::{bus::Bus, cpu::Cpu, observer::CpuObserver},
+  error::LoadError,
+};
+
+/// Something programs can be loaded into
+pub trait Target {
+  fn is_mapped(&self, address: u16) -> bool;
+
+  fn write(&mut self, address: u16, value: u8);
+}
+
+impl Target for Bus {
+  fn is_mapped(&self, address: u16) -> bool {
+    Bus::is_mapped(self, address)
+  }
+
+  fn write(&mut self, address: u16, value: u8) {
+    Bus::write(self, address, value)
+  }
+}
+
+impl<O: CpuObserver> Target for Cpu<O> {
+  fn is_mapped(&self, address: u16) -> bool {
+    Cpu::is_mapped(self, address)
+  }
+
+  fn write(&mut self, address: u16, value: u8) {
+    Cpu::write(self, address, value)
+  }
+}
+
+/// Writes every segment of `program` into `target`. Nothing is written if
+/// any byte would land on an address no device responds to.
+pub fn load(target: &mut impl Target, program: &Program) -> Result<(), LoadError> {
+  let addresses = || {
+    program.segments().iter().flat_map(|segment| {
+      segment
+        .data
+        .iter()
+        .enumerate()
+        .map(|(index, value)| (segment.origin + index as u16, *value))
+    })
+  };
+
+  if let Some((address, _)) = addresses().find(|(address, _)| !target.is_mapped(*address)) {
+    return Err(LoadError::Unmapped(address));
+  }
+
+  for (address, value) in addresses() {
+    target.write(address, value);
+  }
+
+  Ok(())
+}
+
+pub fn load_intel_hex(target: &mut impl Target, text: &str) -> Result<(), LoadError> {
+  load(target, &parse_intel_hex(text)?)
+}
+
+pub fn load_srec(target: &mut impl Target, text: &str) -> Result<(), LoadError> {
+  load(target, &parse_srec(text)?)
+}
+
+pub fn load_prg(target: &mut impl Target, bytes: &[u8]) -> Result<(), LoadError> {
+  load(target, &parse_prg(bytes)?)
+}
+
+/// Parses Intel HEX data, extended segment and extended linear address
+/// records. Start address records are accepted but ignored.
+pub fn parse_intel_hex(text: &str) -> Result<Program, LoadError> {
+  let mut program = Program::new(0x00);
+  let mut base = 0u32;
+  let mut ended = false;
+
+  for (index, text) in text.lines().enumerate() {
+    let line = index + 1;
+    let text = text.trim();
+    if text.is_empty() {
+      continue;
+    }
+    if ended {
+      return Err(LoadError::Syntax {
+        line,
+        message: "record after the end of file record".into(),
+      });
+    }
+
+    let Some(hex) = text.strip_prefix(':') else {
+      return Err(LoadError::Syntax {
+        line,
+        message: "record does not start with `:`".into(),
+      });
+    };
+    let bytes = decode(line, hex)?;
+    if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+      return Err(LoadError::Syntax {
+        line,
+        message: "record length does not match its byte count".into(),
+      });
+    }
+
+    let (record, checksum) = bytes.split_at(bytes.len() - 1);
+    verify(line, sum(record).wrapping_neg(), checksum[0])?;
+
+    let offset = u16::from_be_bytes([record[1], record[2]]) as u32;
+    let data = &record[4..];
+    match (record[3], data) {
+      (0x00, _) => push(&mut program, base + offset, data)?,
+      (0x01, _) => ended = true,
+      (0x02, &[hi, lo]) => base = (u16::from_be_bytes([hi, lo]) as u32) << 4,
+      (0x04, &[hi, lo]) => base = (u16::from_be_bytes([hi, lo]) as u32) << 16,
+      (0x03 | 0x05, _) => (),
+      (kind, _) => {
+        return Err(LoadError::Syntax {
+          line,
+          message: format!("unsupported record type `{kind:02X}`"),
+        })
+      }
+    }
+  }
+
+  if !ended {
+    return Err(LoadError::Syntax {
+      line: text.lines().count(),
+      message: "missing end of file record".into(),
+    });
+  }
+
+  Ok(program)
+}
+
+/// Parses Motorola S-record `S1`, `S2` and `S3` data records. Header, count
+/// and termination records are accepted but ignored.
+pub fn parse_srec(text: &str) -> Result<Program, LoadError> {
+  let mut program = Program::new(0x00);
+
+  for (index, text) in text.lines().enumerate() {
+    let line = index + 1;
+    let text = text.trim();
+    if text.is_empty() {
+      continue;
+    }
+
+    let mut characters = text.chars();
+    let (Some('S'), Some(kind)) = (characters.next(), characters.next()) else {
+      return Err(LoadError::Syntax {
+        line,
+        message: "record does not start with `S`".into(),
+      });
+    };
+    let bytes = decode(line, characters.as_str())?;
+    if bytes.len() < 2 || bytes.len() != bytes[0] as usize + 1 {
+      return Err(LoadError::Syntax {
+        line,
+        message: "record length does not match its byte count".into(),
+      });
+    }
+
+    let (record, checksum) = bytes.split_at(bytes.len() - 1);
+    verify(line, !sum(record), checksum[0])?;
+
+    let width = match kind {
+      '0' | '5' | '6' | '7' | '8' | '9' => continue,
+      '1' => 2,
+      '2' => 3,
+      '3' => 4,
+      kind => {
+        return Err(LoadError::Syntax {
+          line,
+          message: format!("unsupported record type `S{kind}`"),
+        })
+      }
+    };
+    if record.len() < width + 1 {
+      return Err(LoadError::Syntax {
+        line,
+        message: "record is too short for its address".into(),
+      });
+    }
+
+    let address = record[1..=width]
+      .iter()
+      .fold(0u32, |address, byte| address << 8 | *byte as u32);
+    push(&mut program, address, &record[width + 1..])?;
+  }
+
+  Ok(program)
+}
+
+/// Parses a Commodore `.prg` file: a little endian load address followed by
+/// the data to load there
+pub fn parse_prg(bytes: &[u8]) -> Result<Program, LoadError> {
+  let [lo, hi, data @ ..] = bytes else {
+    return Err(LoadError::MissingLoadAddress);
+  };
+
+  let mut program = Program::new(0x00);
+  push(&mut program, u16::from_le_bytes([*lo, *hi]) as u32, data)?;
+
+  Ok(program)
+}
+
+fn push(program: &mut Program, address: u32, data: &[u8]) -> Result<(), LoadError> {
+  for (index, value) in data.iter().enumerate() {
+    let address = address + index as u32;
+    let address = u16::try_from(address).map_err(|_| LoadError::AddressOutOfRange(address))?;
+    program.push(address, *value);
+  }
+
+  Ok(())
+}
+
+/// Decodes pairs of hex digits
+fn decode(line: usize, hex: &str) -> Result<Vec<u8>, LoadError> {
+  if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+    return Err(LoadError::Syntax {
+      line,
+      message: "record has an odd number of hex digits".into(),
+    });
+  }
+
+  (0..hex.len())
+    .step_by(2)
+    .map(|index| {
+      u8::from_str_radix(&hex[index..index + 2], 16).map_err(|_| LoadError::Syntax {
+        line,
+        message: format!("invalid hex digits `{}`", &hex[index..index + 2]),
+      })
+    })
+    .collect()
+}
+
+fn sum(bytes: &[u8]) -> u8 {
+  bytes.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn verify(line: usize, expected: u8, actual: u8) -> Result<(), LoadError> {
+  match expected == actual {
+    true => Ok(()),
+    false => Err(LoadError::Checksum { line, expected, actual }),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{assembler::Segment, core::ram::Ram};
+
+  fn segments(program: &Program) -> Vec<(u16, Vec<u8>)> {
+    program
+      .segments()
+      .iter()
+      .map(|Segment { origin, data }| (*origin, data.clone()))
+      .collect()
+  }
+
+  #[test]
+  fn parses_intel_hex_with_extended_addresses() {
+    let text = ":03020000A9018DC4\n:020000020F00ED\n:0100100042AD\n:00000001FF\n";
+    let program = parse_intel_hex(text).unwrap();
+    assert_eq!(segments(&program), [(0x0200, vec![0xA9, 0x01, 0x8D]), (0xF010, vec![0x42])]);
+
+    // an extended linear address moves the data past the 16 bit address space
+    let text = ":020000040001F9\n:0100000042BD\n:00000001FF\n";
+    assert!(matches!(parse_intel_hex(text), Err(LoadError::AddressOutOfRange(0x10000))));
+  }
+
+  #[test]
+  fn rejects_malformed_intel_hex() {
+    let error = parse_intel_hex(":03020000A9018DC5\n:00000001FF\n").unwrap_err();
+    assert!(matches!(error, LoadError::Checksum {
+      line: 1,
+      expected: 0xC4,
+      actual: 0xC5
+    }));
+
+    // the byte count says 4 but only 3 data bytes follow
+    let error = parse_intel_hex(":04020000A9018DC3\n:00000001FF\n").unwrap_err();
+    assert!(matches!(error, LoadError::Syntax { line: 1, .. }), "{error}");
+
+    let error = parse_intel_hex(":03020000A9018DC4\n").unwrap_err();
+    assert!(matches!(error, LoadError::Syntax { .. }), "{error}");
+  }
+
+  #[test]
+  fn parses_s1_s2_and_s3_records() {
+    let text = "S00600004844521B\nS1050200A9014E\nS20500F00042C8\nS3070000FF00434472\nS9030200FA\n";
+    let program = parse_srec(text).unwrap();
+    assert_eq!(segments(&program), [
+      (0x0200, vec![0xA9, 0x01]),
+      (0xF000, vec![0x42]),
+      (0xFF00, vec![0x43, 0x44])
+    ]);
+
+    assert!(matches!(
+      parse_srec("S20501000042B7\n"),
+      Err(LoadError::AddressOutOfRange(0x10000))
+    ));
+  }
+
+  #[test]
+  fn rejects_malformed_s_records() {
+    let error = parse_srec("S1050200A9014F\n").unwrap_err();
+    assert!(matches!(error, LoadError::Checksum {
+      line: 1,
+      expected: 0x4E,
+      actual: 0x4F
+    }));
+
+    let error = parse_srec("S1060200A9014E\n").unwrap_err();
+    assert!(matches!(error, LoadError::Syntax { line: 1, .. }), "{error}");
+  }
+
+  #[test]
+  fn parses_the_load_address_of_prg_files() {
+    let program = parse_prg(&[0x01, 0x08, 0xA9, 0x01]).unwrap();
+    assert_eq!(segments(&program), [(0x0801, vec![0xA9, 0x01])]);
+
+    assert!(matches!(parse_prg(&[0x01]), Err(LoadError::MissingLoadAddress)));
+  }
+
+  #[test]
+  fn loads_nothing_if_any_byte_is_unmapped() {
+    let mut bus = Bus::new();
+    bus.connect(Ram::new(0x0000..=0x00FF));
+
+    let program = parse_prg(&[0xFE, 0x00, 0x01, 0x02, 0x03]).unwrap();
+    assert!(matches!(load(&mut bus, &program), Err(LoadError::Unmapped(0x0100))));
+    assert_eq!(bus.read(0x00FE, true), 0xEA);
+    assert_eq!(bus.read(0x00FF, true), 0xEA);
+
+    let program = parse_prg(&[0xFE, 0x00, 0x01, 0x02]).unwrap();
+    load(&mut bus, &program).unwrap();
+    assert_eq!(bus.read(0x00FE, true), 0x01);
+    assert_eq!(bus.read(0x00FF, true), 0x02);
+  }
+
+  #[test]
+  fn loads_into_devices_not_based_at_zero() {
+    // devices see full bus addresses and index their memory from the start
+    // of their range, which `write_all` takes offsets from
+    let mut ram = Ram::new(0x8000..=0x80FF);
+    ram.write_all(0x0020, &[0x24]);
+    let mut bus = Bus::new();
+    bus.connect(ram);
+
+    let program = parse_prg(&[0x10, 0x80, 0x42]).unwrap();
+    load(&mut bus, &program).unwrap();
+    assert_eq!(bus.read(0x8010, true), 0x42);
+    assert_eq!(bus.read(0x8020, true), 0x24);
+    assert_eq!(bus.read(0x8000, true), 0xEA);
+  }
+}
@@ -1,12 +1,12 @@
 use std::io::stdin;
 
-use vcpu::core::{cpu::Cpu, ram_file::RamFile};
+use vcpu::core::{cpu::Cpu, observer::HumanTracer, ram_file::RamFile};
 
 fn main() {
   let mut ram = RamFile::new(0x0000..=0xFFFF);
   ram.write_all(0x0000, include_bytes!("../assets/a.out"));
 
-  let mut cpu = Cpu::new(100.0);
+  let mut cpu = Cpu::new(100.0).with_observer(HumanTracer::default());
   cpu.connect(ram);
 
   cpu.reset();